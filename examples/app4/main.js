@@ -0,0 +1,24 @@
+let resolution = [640, 360];
+
+let black = new Color(0, 0, 0);
+let white = new Color(1, 1, 1);
+let video = new Video("sample.y4m");
+
+video.onended = () => {
+  console.log("Video ended, restarting in 1 s");
+  setTimeout(() => video.play(), 1000);
+};
+
+export function init() {
+  set_resolution(...resolution);
+  video.play();
+}
+
+export function draw(dt) {
+  clear_screen(black);
+  
+  draw_video(video, 0, 0, 640, 340);
+  
+  let progress = video.currentTime / video.duration;
+  draw_rectangle(0, 344, 640 * progress, 12, white);
+}
@@ -0,0 +1,256 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use image::{AnimationDecoder, Frame};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use tracing::warn;
+
+// Like browsers, treat the tiny delays old GIF tools wrote as "as fast as
+// possible" as a normal 10 fps
+const MIN_DELAY_MS: f64 = 20.;
+const DEFAULT_DELAY_MS: f64 = 100.;
+
+/// Decodes every frame of an animated GIF or APNG, composited to the full
+/// image size. Returns `None` for still images and other formats, which load
+/// as ordinary images.
+///
+/// Animations are timed from `load_delays`, so images whose decoded frames
+/// don't match its delays also load as still images rather than showing the
+/// wrong frames.
+pub fn load_frames(path: &Path) -> Option<Vec<Frame>> {
+    let frames = decode_frames(path)?;
+    let delay_count = load_delays(path).map_or(0, |delays| delays.len());
+
+    if frames.len() != delay_count {
+        warn!(
+            "{} has {} frames but {} frame delays, showing it as a still image",
+            path.display(), frames.len(), delay_count
+        );
+        return None;
+    }
+
+    Some(frames)
+}
+
+fn decode_frames(path: &Path) -> Option<Vec<Frame>> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let reader = BufReader::new(File::open(path).ok()?);
+
+    let frames = match extension.as_str() {
+        "gif" => GifDecoder::new(reader).ok()?.into_frames().collect_frames().ok()?,
+        "png" | "apng" => {
+            let decoder = PngDecoder::new(reader).ok()?;
+            if !decoder.is_apng() {
+                return None;
+            }
+            decoder.apng().into_frames().collect_frames().ok()?
+        },
+        _ => return None,
+    };
+
+    (frames.len() > 1).then_some(frames)
+}
+
+/// Reads the delay of each frame of an animated GIF or APNG from its headers
+/// without decoding any pixels, so animations can be timed on the JS thread
+/// while the renderers decode the frames. Returns `None` for still images and
+/// other formats.
+pub fn load_delays(path: &Path) -> Option<Vec<f64>> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let data = std::fs::read(path).ok()?;
+
+    let delays = match extension.as_str() {
+        "gif" => gif_delays(&data)?,
+        "png" | "apng" => apng_delays(&data)?,
+        _ => return None,
+    };
+
+    (delays.len() > 1).then_some(delays)
+}
+
+fn clamp_delay(delay_ms: f64) -> f64 {
+    if delay_ms < MIN_DELAY_MS { DEFAULT_DELAY_MS } else { delay_ms }
+}
+
+/// Walks the GIF blocks, taking each image's delay from the graphic control
+/// extension before it.
+fn gif_delays(data: &[u8]) -> Option<Vec<f64>> {
+    if !data.starts_with(b"GIF") {
+        return None;
+    }
+
+    let color_table_size = |packed: u8| {
+        if packed & 0x80 != 0 { 3 << ((packed & 0x07) + 1) } else { 0 }
+    };
+
+    let mut pos = 13 + color_table_size(*data.get(10)?);
+    let mut delay_ms = 0.;
+    let mut delays = vec![];
+
+    loop {
+        match *data.get(pos)? {
+            // Extension, of which only graphic control has a delay
+            0x21 => {
+                if *data.get(pos + 1)? == 0xF9 {
+                    let delay = u16::from_le_bytes([*data.get(pos + 4)?, *data.get(pos + 5)?]);
+                    delay_ms = delay as f64 * 10.;
+                }
+                pos = skip_sub_blocks(data, pos + 2)?;
+            },
+            // Image descriptor, then an optional color table and the image data
+            0x2C => {
+                let packed = *data.get(pos + 9)?;
+                pos = skip_sub_blocks(data, pos + 11 + color_table_size(packed))?;
+                delays.push(clamp_delay(delay_ms));
+                delay_ms = 0.;
+            },
+            0x3B => break,
+            _ => return None,
+        }
+    }
+
+    Some(delays)
+}
+
+fn skip_sub_blocks(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let size = *data.get(pos)? as usize;
+        pos += 1 + size;
+        if size == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+/// Walks the PNG chunks, taking each frame's delay from its `fcTL` chunk.
+/// Images without an `acTL` chunk aren't animated.
+fn apng_delays(data: &[u8]) -> Option<Vec<f64>> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if !data.starts_with(SIGNATURE) {
+        return None;
+    }
+
+    let read_u32 = |pos: usize| Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?));
+    let read_u16 = |pos: usize| Some(u16::from_be_bytes(data.get(pos..pos + 2)?.try_into().ok()?));
+
+    let mut pos = SIGNATURE.len();
+    let mut animated = false;
+    let mut delays = vec![];
+
+    while pos + 8 <= data.len() {
+        let length = read_u32(pos)? as usize;
+        let chunk = pos + 8;
+
+        match data.get(pos + 4..pos + 8)? {
+            b"acTL" => animated = true,
+            b"fcTL" => {
+                let numerator = read_u16(chunk + 20)? as f64;
+                let denominator = match read_u16(chunk + 22)? {
+                    0 => 100.,
+                    denominator => denominator as f64,
+                };
+                delays.push(clamp_delay(numerator * 1000. / denominator));
+            },
+            b"IEND" => break,
+            _ => {},
+        }
+
+        // Chunk data is followed by a CRC
+        pos = chunk + length + 4;
+    }
+
+    animated.then_some(delays)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gif(delays: &[u16]) -> Vec<u8> {
+        // A 1x1 image with a two color global table
+        let mut data = b"GIF89a\x01\x00\x01\x00\x80\x00\x00".to_vec();
+        data.extend([0, 0, 0, 255, 255, 255]);
+
+        for delay in delays {
+            data.extend([0x21, 0xF9, 0x04, 0x00]);
+            data.extend(delay.to_le_bytes());
+            data.extend([0x00, 0x00]);
+            data.extend([0x2C, 0, 0, 0, 0, 1, 0, 1, 0, 0x00]);
+            data.extend([0x02, 0x02, 0x44, 0x01, 0x00]);
+        }
+
+        data.push(0x3B);
+        data
+    }
+
+    fn chunk(data: &mut Vec<u8>, kind: &[u8], body: &[u8]) {
+        data.extend((body.len() as u32).to_be_bytes());
+        data.extend(kind);
+        data.extend(body);
+        // Chunk CRCs aren't checked
+        data.extend([0; 4]);
+    }
+
+    fn apng(delays: &[(u16, u16)]) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(&mut data, b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+        if !delays.is_empty() {
+            chunk(&mut data, b"acTL", &[0, 0, 0, delays.len() as u8, 0, 0, 0, 0]);
+        }
+
+        for (i, (numerator, denominator)) in delays.iter().enumerate() {
+            let mut fctl = vec![0, 0, 0, i as u8, 0, 0, 0, 1, 0, 0, 0, 1];
+            fctl.extend([0; 8]);
+            fctl.extend(numerator.to_be_bytes());
+            fctl.extend(denominator.to_be_bytes());
+            fctl.extend([0, 0]);
+            chunk(&mut data, b"fcTL", &fctl);
+            chunk(&mut data, b"IDAT", &[0; 4]);
+        }
+
+        chunk(&mut data, b"IEND", &[]);
+        data
+    }
+
+    #[test]
+    fn gif_delays_come_from_graphic_control_extensions() {
+        assert_eq!(gif_delays(&gif(&[10, 5])), Some(vec![100., 50.]));
+    }
+
+    #[test]
+    fn tiny_gif_delays_play_at_ten_fps() {
+        assert_eq!(gif_delays(&gif(&[1, 0])), Some(vec![100., 100.]));
+    }
+
+    #[test]
+    fn truncated_gifs_have_no_delays() {
+        let data = gif(&[10, 10]);
+        assert_eq!(gif_delays(&data[..data.len() - 4]), None);
+    }
+
+    #[test]
+    fn apng_delays_come_from_frame_controls() {
+        assert_eq!(apng_delays(&apng(&[(1, 10), (25, 1000), (3, 0)])), Some(vec![100., 25., 30.]));
+    }
+
+    #[test]
+    fn pngs_without_animation_control_are_still() {
+        assert_eq!(apng_delays(&apng(&[])), None);
+    }
+
+    #[test]
+    fn gif_frames_match_their_delays() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("signrs-animated-{}.gif", std::process::id()));
+        std::fs::write(&path, gif(&[10, 5, 20])).unwrap();
+
+        let frames = load_frames(&path);
+        let delays = load_delays(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(frames.map(|frames| frames.len()), Some(3));
+        assert_eq!(delays, Some(vec![100., 50., 200.]));
+    }
+}
@@ -0,0 +1,38 @@
+use std::str::FromStr;
+
+/// How drawing combines with what is already on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    /// Adds colors, brightening toward white, for light leaks and glows.
+    Additive,
+    /// Multiplies colors, darkening toward black, for vignettes and tints.
+    Multiply,
+    /// Inverts, multiplies and inverts again, brightening without clipping.
+    Screen,
+}
+
+impl BlendMode {
+    pub fn to_skia(self) -> tiny_skia::BlendMode {
+        match self {
+            BlendMode::Normal => tiny_skia::BlendMode::SourceOver,
+            BlendMode::Additive => tiny_skia::BlendMode::Plus,
+            BlendMode::Multiply => tiny_skia::BlendMode::Multiply,
+            BlendMode::Screen => tiny_skia::BlendMode::Screen,
+        }
+    }
+}
+
+impl FromStr for BlendMode {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "normal" => Ok(BlendMode::Normal),
+            "additive" => Ok(BlendMode::Additive),
+            "multiply" => Ok(BlendMode::Multiply),
+            "screen" => Ok(BlendMode::Screen),
+            _ => Err(()),
+        }
+    }
+}
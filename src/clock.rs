@@ -0,0 +1,70 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone, Utc};
+
+pub type SharedClock = Arc<Mutex<VirtualClock>>;
+
+/// The player-controlled time reported to apps through `Date` and `dt`.
+///
+/// Time advances by the frame time multiplied by `time_scale`, starting from
+/// `anchor`, so apps can be run frozen (scale 0), accelerated, or at a fixed
+/// date without editing app code.
+#[derive(Debug)]
+pub struct VirtualClock {
+    anchor: DateTime<Utc>,
+    elapsed: f64,
+    time_scale: f64,
+}
+
+impl VirtualClock {
+    pub fn new(start_time: Option<DateTime<Utc>>, time_scale: f64) -> Self {
+        VirtualClock {
+            anchor: start_time.unwrap_or_else(Utc::now),
+            elapsed: 0.,
+            time_scale,
+        }
+    }
+
+    pub fn shared(start_time: Option<DateTime<Utc>>, time_scale: f64) -> SharedClock {
+        Arc::new(Mutex::new(VirtualClock::new(start_time, time_scale)))
+    }
+
+    /// Advances the clock by a real frame time, returning the scaled `dt`.
+    pub fn advance(&mut self, real_dt: f32) -> f32 {
+        let dt = real_dt as f64 * self.time_scale;
+        self.elapsed += dt;
+        dt as f32
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.anchor + Duration::microseconds((self.elapsed * 1e6) as i64)
+    }
+
+    pub fn set_now(&mut self, now: DateTime<Utc>) {
+        self.anchor = now;
+        self.elapsed = 0.;
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale;
+    }
+}
+
+/// Parses an RFC 3339 timestamp, or a local time like `2024-06-04T09:00:00`.
+pub fn parse_time(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S")
+        .map_err(|err| format!("Invalid time '{}': {}", s, err))?;
+
+    Local.from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or(format!("Invalid local time '{}'", s))
+}
@@ -0,0 +1,97 @@
+use speedy2d::dimen::UVec2;
+use tiny_skia::{ColorU8, Pixmap};
+
+use crate::js_env::ImageRef;
+
+// Rec. 709 luma weights, as CSS filters use
+const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// CSS filter style color adjustments for images, applied in software before
+/// an image is tinted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorAdjust {
+    /// Multiplies colors, so 0 is black and 1 is unchanged.
+    pub brightness: f32,
+    /// Scales colors about middle gray, so 0 is gray and 1 is unchanged.
+    pub contrast: f32,
+    /// Scales colors about their luma, so 0 is gray and 1 is unchanged.
+    pub saturation: f32,
+    /// Blends toward luma, so 0 is unchanged and 1 is fully gray.
+    pub grayscale: f32,
+}
+
+/// An image with its colors adjusted, rasterized at `svg_size` for SVGs.
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+pub struct AdjustedImageKey {
+    pub path: String,
+    frame: usize,
+    svg_size: Option<(u32, u32)>,
+    adjust: [u32; 4],
+}
+
+impl AdjustedImageKey {
+    pub fn new(image_ref: &ImageRef, svg_size: Option<UVec2>, adjust: &ColorAdjust) -> Self {
+        AdjustedImageKey {
+            path: image_ref.path.clone(),
+            frame: image_ref.frame,
+            svg_size: svg_size.map(|size| (size.x, size.y)),
+            adjust: adjust.cache_key(),
+        }
+    }
+}
+
+impl Default for ColorAdjust {
+    fn default() -> Self {
+        ColorAdjust { brightness: 1., contrast: 1., saturation: 1., grayscale: 0. }
+    }
+}
+
+impl ColorAdjust {
+    pub fn is_identity(&self) -> bool {
+        *self == ColorAdjust::default()
+    }
+
+    pub fn cache_key(&self) -> [u32; 4] {
+        [
+            self.brightness.to_bits(),
+            self.contrast.to_bits(),
+            self.saturation.to_bits(),
+            self.grayscale.to_bits(),
+        ]
+    }
+
+    /// Rows of an affine transform of straight RGB in 0 to 1, applying
+    /// saturation and grayscale, then contrast, then brightness.
+    fn matrix(&self) -> [[f32; 4]; 3] {
+        // Saturation matrices multiply, so grayscale is a further saturation
+        let s = self.saturation * (1. - self.grayscale.clamp(0., 1.));
+        let scale = self.brightness * self.contrast;
+        let offset = self.brightness * 0.5 * (1. - self.contrast);
+
+        let mut matrix = [[0.; 4]; 3];
+        for (row, values) in matrix.iter_mut().enumerate() {
+            for column in 0..3 {
+                let identity = if row == column { 1. } else { 0. };
+                values[column] = scale * (LUMA[column] + s * (identity - LUMA[column]));
+            }
+            values[3] = offset;
+        }
+        matrix
+    }
+
+    pub fn apply(&self, pixmap: &mut Pixmap) {
+        let matrix = self.matrix();
+
+        for pixel in pixmap.pixels_mut() {
+            let c = pixel.demultiply();
+            let rgb = [c.red() as f32 / 255., c.green() as f32 / 255., c.blue() as f32 / 255.];
+            let channel = |row: &[f32; 4]| {
+                let value = row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2] + row[3];
+                (value.clamp(0., 1.) * 255.).round() as u8
+            };
+            *pixel = ColorU8::from_rgba(
+                channel(&matrix[0]), channel(&matrix[1]), channel(&matrix[2]), c.alpha()
+            ).premultiply();
+        }
+    }
+}
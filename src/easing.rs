@@ -0,0 +1,284 @@
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+// Newton's method usually converges in a few steps, with bisection as a
+// fallback for flat parts of the curve
+const BEZIER_NEWTON_STEPS: usize = 8;
+const BEZIER_BISECTION_STEPS: usize = 30;
+const BEZIER_EPSILON: f64 = 1e-7;
+
+/// The shape of an easing curve, defined by how it eases in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    Quad,
+    Cubic,
+    Elastic,
+    Bounce,
+}
+
+impl Curve {
+    fn ease_in(self, x: f64) -> f64 {
+        match self {
+            Curve::Quad => x * x,
+            Curve::Cubic => x * x * x,
+            Curve::Elastic if x <= 0. => 0.,
+            Curve::Elastic if x >= 1. => 1.,
+            Curve::Elastic => {
+                -(2f64.powf(10. * x - 10.)) * ((x * 10. - 10.75) * (2. * PI / 3.)).sin()
+            },
+            Curve::Bounce => 1. - bounce_out(1. - x),
+        }
+    }
+}
+
+fn bounce_out(x: f64) -> f64 {
+    const N: f64 = 7.5625;
+    const D: f64 = 2.75;
+
+    if x < 1. / D {
+        N * x * x
+    } else if x < 2. / D {
+        let x = x - 1.5 / D;
+        N * x * x + 0.75
+    } else if x < 2.5 / D {
+        let x = x - 2.25 / D;
+        N * x * x + 0.9375
+    } else {
+        let x = x - 2.625 / D;
+        N * x * x + 0.984375
+    }
+}
+
+/// Maps linear progress from 0 to 1 to eased progress, which may overshoot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    In(Curve),
+    Out(Curve),
+    InOut(Curve),
+    /// A CSS-style curve from (0, 0) to (1, 1) with control points
+    /// `(x1, y1)` and `(x2, y2)`.
+    CubicBezier(f64, f64, f64, f64),
+}
+
+impl Easing {
+    /// A cubic bezier easing, with control x values clamped to 0 to 1 as in
+    /// CSS.
+    pub fn cubic_bezier(x1: f64, y1: f64, x2: f64, y2: f64) -> Self {
+        Easing::CubicBezier(x1.clamp(0., 1.), y1, x2.clamp(0., 1.), y2)
+    }
+
+    pub fn ease(&self, x: f64) -> f64 {
+        let x = x.clamp(0., 1.);
+
+        match *self {
+            Easing::Linear => x,
+            Easing::In(curve) => curve.ease_in(x),
+            Easing::Out(curve) => 1. - curve.ease_in(1. - x),
+            Easing::InOut(curve) if x < 0.5 => curve.ease_in(2. * x) / 2.,
+            Easing::InOut(curve) => 1. - curve.ease_in(2. - 2. * x) / 2.,
+            Easing::CubicBezier(x1, y1, x2, y2) => {
+                let t = bezier_t_for_x(x, x1, x2);
+                bezier(t, y1, y2)
+            },
+        }
+    }
+}
+
+/// One coordinate of a cubic bezier from 0 to 1 with control values `p1`
+/// and `p2`.
+fn bezier(t: f64, p1: f64, p2: f64) -> f64 {
+    let u = 1. - t;
+    3. * u * u * t * p1 + 3. * u * t * t * p2 + t * t * t
+}
+
+fn bezier_slope(t: f64, p1: f64, p2: f64) -> f64 {
+    let u = 1. - t;
+    3. * u * u * p1 + 6. * u * t * (p2 - p1) + 3. * t * t * (1. - p2)
+}
+
+/// Finds where the curve reaches `x`, which is unique as control x values
+/// are kept between 0 and 1.
+fn bezier_t_for_x(x: f64, x1: f64, x2: f64) -> f64 {
+    let mut t = x;
+    for _ in 0..BEZIER_NEWTON_STEPS {
+        let error = bezier(t, x1, x2) - x;
+        if error.abs() < BEZIER_EPSILON {
+            return t;
+        }
+        let slope = bezier_slope(t, x1, x2);
+        if slope.abs() < BEZIER_EPSILON {
+            break;
+        }
+        t = (t - error / slope).clamp(0., 1.);
+    }
+
+    let (mut low, mut high) = (0., 1.);
+    t = x;
+    for _ in 0..BEZIER_BISECTION_STEPS {
+        let value = bezier(t, x1, x2);
+        if (value - x).abs() < BEZIER_EPSILON {
+            break;
+        }
+        if value < x {
+            low = t;
+        } else {
+            high = t;
+        }
+        t = (low + high) / 2.;
+    }
+    t
+}
+
+impl FromStr for Easing {
+    type Err = ();
+
+    /// Parses names like `"easeOutCubic"`, the CSS keywords `"ease"`,
+    /// `"ease-in"`, `"ease-out"` and `"ease-in-out"`, and
+    /// `"cubic-bezier(x1, y1, x2, y2)"`.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.trim();
+
+        if let Some(args) = name.strip_prefix("cubic-bezier(").and_then(|rest| rest.strip_suffix(')')) {
+            let values: Vec<f64> = args.split(',')
+                .map(|value| value.trim().parse::<f64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| ())?;
+            return match values[..] {
+                [x1, y1, x2, y2] => Ok(Easing::cubic_bezier(x1, y1, x2, y2)),
+                _ => Err(()),
+            };
+        }
+
+        match name {
+            "linear" => return Ok(Easing::Linear),
+            "ease" => return Ok(Easing::cubic_bezier(0.25, 0.1, 0.25, 1.)),
+            "ease-in" => return Ok(Easing::cubic_bezier(0.42, 0., 1., 1.)),
+            "ease-out" => return Ok(Easing::cubic_bezier(0., 0., 0.58, 1.)),
+            "ease-in-out" => return Ok(Easing::cubic_bezier(0.42, 0., 0.58, 1.)),
+            _ => {},
+        }
+
+        let (direction, curve) = if let Some(curve) = name.strip_prefix("easeInOut") {
+            (Easing::InOut as fn(Curve) -> Easing, curve)
+        } else if let Some(curve) = name.strip_prefix("easeIn") {
+            (Easing::In as fn(Curve) -> Easing, curve)
+        } else if let Some(curve) = name.strip_prefix("easeOut") {
+            (Easing::Out as fn(Curve) -> Easing, curve)
+        } else {
+            return Err(());
+        };
+
+        let curve = match curve {
+            "Quad" => Curve::Quad,
+            "Cubic" => Curve::Cubic,
+            "Elastic" => Curve::Elastic,
+            "Bounce" => Curve::Bounce,
+            _ => return Err(()),
+        };
+        Ok(direction(curve))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [Curve; 4] = [Curve::Quad, Curve::Cubic, Curve::Elastic, Curve::Bounce];
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-4, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn curves_start_at_0_and_end_at_1() {
+        for curve in CURVES {
+            for easing in [Easing::In(curve), Easing::Out(curve), Easing::InOut(curve)] {
+                assert_close(easing.ease(0.), 0.);
+                assert_close(easing.ease(1.), 1.);
+            }
+        }
+    }
+
+    #[test]
+    fn progress_is_clamped() {
+        assert_eq!(Easing::Linear.ease(-1.), 0.);
+        assert_eq!(Easing::Linear.ease(2.), 1.);
+        assert_close(Easing::In(Curve::Quad).ease(1.5), 1.);
+    }
+
+    #[test]
+    fn curves_ease_as_named() {
+        assert_close(Easing::Linear.ease(0.3), 0.3);
+        assert_close(Easing::In(Curve::Quad).ease(0.5), 0.25);
+        assert_close(Easing::Out(Curve::Quad).ease(0.5), 0.75);
+        assert_close(Easing::InOut(Curve::Cubic).ease(0.25), 0.0625);
+        assert_close(Easing::Out(Curve::Bounce).ease(0.5), 0.765625);
+    }
+
+    #[test]
+    fn in_out_curves_are_symmetric() {
+        for curve in CURVES {
+            let easing = Easing::InOut(curve);
+            assert_close(easing.ease(0.5), 0.5);
+            for x in [0.1, 0.2, 0.35, 0.45] {
+                assert_close(easing.ease(x) + easing.ease(1. - x), 1.);
+            }
+        }
+    }
+
+    #[test]
+    fn elastic_overshoots() {
+        let easing = Easing::Out(Curve::Elastic);
+        assert!((1..100).any(|i| easing.ease(i as f64 / 100.) > 1.));
+    }
+
+    #[test]
+    fn bezier_solves_for_x() {
+        for (x1, x2) in [(0.25, 0.25), (0.42, 0.58), (1., 0.), (0., 1.)] {
+            for x in [0., 0.1, 0.25, 0.5, 0.75, 0.9, 1.] {
+                assert_close(bezier(bezier_t_for_x(x, x1, x2), x1, x2), x);
+            }
+        }
+    }
+
+    #[test]
+    fn cubic_beziers_match_css() {
+        assert_close(Easing::cubic_bezier(0., 0., 1., 1.).ease(0.3), 0.3);
+        assert_close("ease".parse::<Easing>().unwrap().ease(0.5), 0.8024);
+
+        let ease_in_out = "ease-in-out".parse::<Easing>().unwrap();
+        assert_close(ease_in_out.ease(0.5), 0.5);
+        assert_close(ease_in_out.ease(0.2) + ease_in_out.ease(0.8), 1.);
+    }
+
+    #[test]
+    fn bezier_control_x_values_are_clamped() {
+        assert_eq!(Easing::cubic_bezier(-0.5, 2., 1.5, -1.), Easing::CubicBezier(0., 2., 1., -1.));
+    }
+
+    #[test]
+    fn parses_names() {
+        assert_eq!("linear".parse::<Easing>(), Ok(Easing::Linear));
+        assert_eq!("easeInQuad".parse::<Easing>(), Ok(Easing::In(Curve::Quad)));
+        assert_eq!("easeOutBounce".parse::<Easing>(), Ok(Easing::Out(Curve::Bounce)));
+        assert_eq!(" easeInOutElastic ".parse::<Easing>(), Ok(Easing::InOut(Curve::Elastic)));
+        assert_eq!("ease-out".parse::<Easing>(), Ok(Easing::CubicBezier(0., 0., 0.58, 1.)));
+        assert_eq!("cubic-bezier(0.1, 0.2, 1.3, 0.4)".parse::<Easing>(), Ok(Easing::CubicBezier(0.1, 0.2, 1., 0.4)));
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        let names = [
+            "",
+            "easeInSine",
+            "easeQuad",
+            "cubic-bezier(0.1, 0.2, 0.3)",
+            "cubic-bezier(a, b, c, d)",
+            "cubic-bezier(0, 0, 1, 1",
+        ];
+        for name in names {
+            assert_eq!(name.parse::<Easing>(), Err(()), "{}", name);
+        }
+    }
+}
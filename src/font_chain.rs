@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use rusttype::{point, Font, Scale, VMetrics};
+
+/// A primary font face followed by fallback faces for the characters it has
+/// no glyphs for.
+#[derive(Clone)]
+pub struct FontChain {
+    faces: Vec<Arc<Font<'static>>>,
+}
+
+impl FontChain {
+    /// `faces` must not be empty.
+    pub fn new(faces: Vec<Arc<Font<'static>>>) -> Self {
+        assert!(!faces.is_empty(), "A font chain needs at least one face");
+        FontChain { faces }
+    }
+
+    pub fn face(&self, index: usize) -> &Arc<Font<'static>> {
+        &self.faces[index]
+    }
+
+    /// The first face with a glyph for `c`, or the primary face if none has
+    /// one, so missing glyphs look the same as without fallbacks.
+    pub fn face_for(&self, c: char) -> usize {
+        self.faces.iter()
+            .position(|face| face.glyph(c).id().0 != 0)
+            .unwrap_or(0)
+    }
+
+    pub fn needs_fallback(&self, text: &str) -> bool {
+        text.chars().any(|c| !c.is_whitespace() && self.face_for(c) != 0)
+    }
+
+    /// Splits text into runs drawn with the same face. Whitespace stays in the
+    /// current run, since every face can space text.
+    pub fn runs<'a>(&self, text: &'a str) -> Vec<(usize, &'a str)> {
+        let mut runs = vec![];
+        let mut run_face = None;
+        let mut run_start = 0;
+
+        for (i, c) in text.char_indices() {
+            if c.is_whitespace() {
+                continue;
+            }
+
+            let face = self.face_for(c);
+            match run_face {
+                Some(current) if current != face => {
+                    runs.push((current, &text[run_start..i]));
+                    run_start = i;
+                },
+                _ => {},
+            }
+            run_face = Some(face);
+        }
+
+        if run_start < text.len() {
+            runs.push((run_face.unwrap_or(0), &text[run_start..]));
+        }
+
+        runs
+    }
+
+    /// The width of a single line across all the faces it uses.
+    pub fn measure(&self, text: &str, size: f32) -> f32 {
+        self.runs(text).iter()
+            .map(|(face, run)| self.measure_face(*face, run, size))
+            .sum()
+    }
+
+    pub fn measure_face(&self, face: usize, text: &str, size: f32) -> f32 {
+        self.faces[face].layout(text, Scale::uniform(size), point(0., 0.))
+            .last()
+            .map(|glyph| glyph.position().x + glyph.unpositioned().h_metrics().advance_width)
+            .unwrap_or(0.)
+    }
+
+    /// Lines are spaced by the primary face, whatever faces they use.
+    pub fn v_metrics(&self, size: f32) -> VMetrics {
+        self.faces[0].v_metrics(Scale::uniform(size))
+    }
+
+    pub fn line_height(&self, size: f32) -> f32 {
+        let v_metrics = self.v_metrics(size);
+        v_metrics.ascent - v_metrics.descent + v_metrics.line_gap
+    }
+
+    /// How far to move text drawn with a fallback face down so it sits on the
+    /// primary face's baseline.
+    pub fn baseline_shift(&self, face: usize, size: f32) -> f32 {
+        let scale = Scale::uniform(size);
+        self.faces[0].v_metrics(scale).ascent - self.faces[face].v_metrics(scale).ascent
+    }
+}
@@ -0,0 +1,290 @@
+use std::path::{Path, PathBuf};
+
+use tiny_skia::{ColorU8, Pixmap};
+use tracing::info;
+
+use crate::clock::SharedClock;
+use crate::headless::{self, HeadlessError, HeadlessRenderer};
+
+const GOLDEN_DIR: &str = "golden";
+const DIFF_DIR: &str = "diff";
+
+pub struct TestOptions {
+    pub frames: Option<Vec<u32>>,
+    pub fps: f32,
+    pub tolerance: u8,
+    pub update: bool,
+    pub diff_dir: Option<PathBuf>,
+}
+
+enum FrameResult {
+    Passed,
+    Updated,
+    Missing,
+    SizeMismatch((u32, u32), (u32, u32)),
+    PixelMismatch(usize),
+}
+
+/// Renders the app headless and compares frames against the reference PNGs in
+/// `<app>/golden`. Returns `Ok(true)` if every frame matched.
+pub fn run(app_path: &Path, options: &TestOptions, clock: SharedClock) -> Result<bool, HeadlessError> {
+    let mut golden_dir = app_path.to_path_buf();
+    golden_dir.push(GOLDEN_DIR);
+
+    let diff_dir = options.diff_dir.clone().unwrap_or_else(|| {
+        let mut dir = golden_dir.clone();
+        dir.push(DIFF_DIR);
+        dir
+    });
+
+    let frames = match &options.frames {
+        Some(frames) => frames.clone(),
+        None => find_golden_frames(&golden_dir),
+    };
+
+    if frames.is_empty() {
+        println!("FAILED, no references in {} and no --frames given", golden_dir.display());
+        return Ok(false);
+    }
+
+    if options.update {
+        std::fs::create_dir_all(&golden_dir)?;
+    }
+
+    let mut all_passed = true;
+
+    headless::render_frames(app_path, &frames, options.fps, clock, |frame, renderer| {
+        let mut golden_path = golden_dir.clone();
+        golden_path.push(headless::frame_file_name(frame));
+
+        let result = check_frame(renderer, &golden_path, &diff_dir, frame, options)?;
+
+        match result {
+            FrameResult::Passed => info!("Frame {}: passed", frame),
+            FrameResult::Updated => println!("Frame {}: updated {}", frame, golden_path.display()),
+            FrameResult::Missing => {
+                println!("Frame {}: FAILED, missing reference {}", frame, golden_path.display());
+            },
+            FrameResult::SizeMismatch(actual, expected) => {
+                println!("Frame {}: FAILED, size {}x{} differs from reference {}x{}",
+                    frame, actual.0, actual.1, expected.0, expected.1);
+            },
+            FrameResult::PixelMismatch(count) => {
+                println!("Frame {}: FAILED, {} pixels outside tolerance {}",
+                    frame, count, options.tolerance);
+            },
+        }
+
+        if !matches!(result, FrameResult::Passed | FrameResult::Updated) {
+            all_passed = false;
+        }
+
+        Ok(())
+    })?;
+
+    Ok(all_passed)
+}
+
+fn check_frame(
+    renderer: &HeadlessRenderer,
+    golden_path: &Path,
+    diff_dir: &Path,
+    frame: u32,
+    options: &TestOptions
+) -> Result<FrameResult, HeadlessError> {
+    if options.update {
+        renderer.save_png(golden_path)?;
+        return Ok(FrameResult::Updated);
+    }
+
+    let Ok(expected) = Pixmap::load_png(golden_path) else {
+        return Ok(FrameResult::Missing);
+    };
+
+    let actual = renderer.pixmap();
+
+    if actual.width() != expected.width() || actual.height() != expected.height() {
+        return Ok(FrameResult::SizeMismatch(
+            (actual.width(), actual.height()),
+            (expected.width(), expected.height()),
+        ));
+    }
+
+    let (count, diff) = compare(actual, &expected, options.tolerance);
+
+    if count == 0 {
+        return Ok(FrameResult::Passed);
+    }
+
+    std::fs::create_dir_all(diff_dir)?;
+    let mut diff_path = diff_dir.to_path_buf();
+    diff_path.push(headless::frame_file_name(frame));
+    let data = diff.encode_png()
+        .map_err(|err| HeadlessError::PngEncoding(err.to_string()))?;
+    std::fs::write(&diff_path, data)?;
+    println!("Frame {}: wrote diff {}", frame, diff_path.display());
+
+    Ok(FrameResult::PixelMismatch(count))
+}
+
+/// Counts pixels where any channel differs by more than `tolerance`, and builds
+/// a diff image showing those pixels in red over a faded copy of the frame.
+fn compare(actual: &Pixmap, expected: &Pixmap, tolerance: u8) -> (usize, Pixmap) {
+    let mut diff = actual.clone();
+    let mut count = 0;
+
+    let pixels = actual.pixels().iter().zip(expected.pixels());
+
+    for (out, (a, e)) in diff.pixels_mut().iter_mut().zip(pixels) {
+        let a = a.demultiply();
+        let e = e.demultiply();
+
+        let max_delta = [
+            a.red().abs_diff(e.red()),
+            a.green().abs_diff(e.green()),
+            a.blue().abs_diff(e.blue()),
+            a.alpha().abs_diff(e.alpha()),
+        ].into_iter().max().unwrap_or(0);
+
+        *out = if max_delta > tolerance {
+            count += 1;
+            ColorU8::from_rgba(255, 0, 0, 255).premultiply()
+        } else {
+            let gray = ((a.red() as u16 + a.green() as u16 + a.blue() as u16) / 3) as u8;
+            let faded = 192 + gray / 4;
+            ColorU8::from_rgba(faded, faded, faded, 255).premultiply()
+        };
+    }
+
+    (count, diff)
+}
+
+/// Finds the frame numbers of the `frame_NNNNN.png` references in `golden_dir`.
+fn find_golden_frames(golden_dir: &Path) -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir(golden_dir) else { return vec![] };
+
+    let mut frames: Vec<u32> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry.file_name().to_str()?
+                .strip_prefix("frame_")?
+                .strip_suffix(".png")?
+                .parse().ok()
+        })
+        .collect();
+
+    frames.sort();
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::VirtualClock;
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> Pixmap {
+        let mut pixmap = Pixmap::new(width, height).unwrap();
+        let [r, g, b, a] = rgba;
+        pixmap.fill(tiny_skia::Color::from_rgba8(r, g, b, a));
+        pixmap
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("signrs-golden-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn options(update: bool, diff_dir: &Path) -> TestOptions {
+        TestOptions {
+            frames: None,
+            fps: 60.,
+            tolerance: 2,
+            update,
+            diff_dir: Some(diff_dir.to_path_buf()),
+        }
+    }
+
+    #[test]
+    fn compare_allows_differences_up_to_tolerance() {
+        let actual = solid(4, 4, [100, 100, 100, 255]);
+        let expected = solid(4, 4, [102, 98, 100, 255]);
+
+        let (count, _) = compare(&actual, &expected, 2);
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn compare_counts_differences_past_tolerance() {
+        let actual = solid(4, 4, [100, 100, 100, 255]);
+        let expected = solid(4, 4, [100, 100, 103, 255]);
+
+        let (count, diff) = compare(&actual, &expected, 2);
+        assert_eq!(count, 16);
+        assert_eq!(diff.pixels()[0].demultiply(), ColorU8::from_rgba(255, 0, 0, 255));
+    }
+
+    #[test]
+    fn compare_with_zero_tolerance_needs_exact_match() {
+        let actual = solid(2, 2, [10, 20, 30, 255]);
+
+        assert_eq!(compare(&actual, &actual.clone(), 0).0, 0);
+        assert_eq!(compare(&actual, &solid(2, 2, [10, 20, 31, 255]), 0).0, 4);
+    }
+
+    #[test]
+    fn compare_checks_alpha() {
+        let actual = solid(2, 2, [0, 0, 0, 255]);
+        let expected = solid(2, 2, [0, 0, 0, 128]);
+
+        assert_eq!(compare(&actual, &expected, 2).0, 4);
+    }
+
+    #[test]
+    fn check_frame_reports_size_mismatch() {
+        let dir = scratch_dir("size");
+        let golden_path = dir.join(headless::frame_file_name(0));
+        std::fs::write(&golden_path, solid(4, 4, [0, 0, 0, 255]).encode_png().unwrap()).unwrap();
+
+        let renderer = HeadlessRenderer::new(&dir);
+        let result = check_frame(&renderer, &golden_path, &dir, 0, &options(false, &dir)).unwrap();
+
+        let (width, height) = (renderer.pixmap().width(), renderer.pixmap().height());
+        assert!(matches!(result, FrameResult::SizeMismatch(actual, (4, 4)) if actual == (width, height)));
+    }
+
+    #[test]
+    fn check_frame_reports_missing_reference() {
+        let dir = scratch_dir("missing");
+        let golden_path = dir.join(headless::frame_file_name(0));
+
+        let renderer = HeadlessRenderer::new(&dir);
+        let result = check_frame(&renderer, &golden_path, &dir, 0, &options(false, &dir)).unwrap();
+
+        assert!(matches!(result, FrameResult::Missing));
+    }
+
+    #[test]
+    fn update_writes_reference_that_then_passes() {
+        let dir = scratch_dir("update");
+        let golden_path = dir.join(headless::frame_file_name(3));
+
+        let renderer = HeadlessRenderer::new(&dir);
+        let result = check_frame(&renderer, &golden_path, &dir, 3, &options(true, &dir)).unwrap();
+        assert!(matches!(result, FrameResult::Updated));
+        assert_eq!(find_golden_frames(&dir), vec![3]);
+
+        let result = check_frame(&renderer, &golden_path, &dir, 3, &options(false, &dir)).unwrap();
+        assert!(matches!(result, FrameResult::Passed));
+    }
+
+    #[test]
+    fn run_fails_without_references() {
+        let dir = scratch_dir("none");
+        let clock = VirtualClock::shared(None, 1.);
+
+        assert!(!run(&dir, &options(false, &dir), clock).unwrap());
+    }
+}
@@ -0,0 +1,309 @@
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use tiny_skia::{
+    BlendMode, GradientStop, LinearGradient, Paint, Pixmap, Point, RadialGradient, Rect, Shader,
+    SpreadMode, Transform
+};
+
+use crate::headless::to_skia_color;
+
+// Largest color difference tolerated when approximating a radial gradient
+// with per-vertex colors
+const RADIAL_TOLERANCE: f32 = 1.5 / 255.;
+const MAX_RADIAL_DEPTH: u32 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientKind {
+    Linear { start: Vec2, end: Vec2 },
+    Radial { center: Vec2, radius: f32 },
+}
+
+/// A gradient in the coordinates of the drawing call it is used with, so it
+/// follows the current transform like the shape it fills.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    /// Offsets between 0 and 1 in ascending order, with at least one stop.
+    pub stops: Vec<(f32, Color)>,
+}
+
+/// What a rectangle, shape or text is painted with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fill {
+    Solid(Color),
+    Gradient(Gradient),
+}
+
+impl From<Color> for Fill {
+    fn from(color: Color) -> Self {
+        Fill::Solid(color)
+    }
+}
+
+impl Gradient {
+    pub fn new(kind: GradientKind, mut stops: Vec<(f32, Color)>) -> Self {
+        for stop in stops.iter_mut() {
+            stop.0 = stop.0.clamp(0., 1.);
+        }
+        // Stable, so stops sharing an offset keep their order for hard edges
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        if stops.is_empty() {
+            stops.push((0., Color::TRANSPARENT));
+        }
+
+        Gradient { kind, stops }
+    }
+
+    /// The unclamped position of `p` along the gradient, 0 at the start and 1
+    /// at the end.
+    pub fn offset_at(&self, p: Vec2) -> f32 {
+        match self.kind {
+            GradientKind::Linear { start, end } => {
+                let axis = end - start;
+                let length_squared = axis.x * axis.x + axis.y * axis.y;
+                if length_squared == 0. {
+                    return 1.;
+                }
+                let relative = p - start;
+                (relative.x * axis.x + relative.y * axis.y) / length_squared
+            },
+            GradientKind::Radial { center, radius } => {
+                if radius <= 0. {
+                    return 1.;
+                }
+                (p - center).magnitude() / radius
+            },
+        }
+    }
+
+    pub fn color_at_offset(&self, offset: f32) -> Color {
+        let first = self.stops[0];
+        let last = self.stops[self.stops.len() - 1];
+
+        if offset <= first.0 {
+            return first.1;
+        }
+        if offset >= last.0 {
+            return last.1;
+        }
+
+        let next = self.stops.iter().position(|stop| stop.0 > offset).unwrap_or(self.stops.len() - 1);
+        let (start, start_color) = self.stops[next - 1];
+        let (end, end_color) = self.stops[next];
+        lerp_color(start_color, end_color, (offset - start) / (end - start))
+    }
+
+    pub fn color_at(&self, p: Vec2) -> Color {
+        self.color_at_offset(self.offset_at(p))
+    }
+
+    /// Splits triangles so the gradient can be drawn with per-vertex colors,
+    /// which GPUs interpolate linearly across each triangle.
+    pub fn shade_triangles(&self, triangles: &[[Vec2; 3]]) -> Vec<([Vec2; 3], [Color; 3])> {
+        let mut shaded = vec![];
+
+        for triangle in triangles {
+            match self.kind {
+                GradientKind::Linear { .. } => self.slice_linear(triangle, &mut shaded),
+                GradientKind::Radial { .. } => {
+                    self.subdivide_radial(triangle, MAX_RADIAL_DEPTH, &mut shaded)
+                },
+            }
+        }
+
+        shaded
+    }
+
+    /// Color is linear in position between two stops, so cutting the triangle
+    /// along each stop line makes the per-vertex colors exact.
+    fn slice_linear(&self, triangle: &[Vec2; 3], shaded: &mut Vec<([Vec2; 3], [Color; 3])>) {
+        let first = self.stops[0];
+        let last = self.stops[self.stops.len() - 1];
+
+        // Stops sharing an offset make a hard edge rather than a band
+        let mut bands = vec![((f32::NEG_INFINITY, first.1), first)];
+        bands.extend(self.stops.windows(2)
+            .filter(|pair| pair[0].0 < pair[1].0)
+            .map(|pair| (pair[0], pair[1])));
+        bands.push((last, (f32::INFINITY, last.1)));
+
+        for ((lower, lower_color), (upper, upper_color)) in bands {
+            let mut polygon = triangle.to_vec();
+            polygon = clip_polygon(&polygon, |p| self.offset_at(p) - lower);
+            polygon = clip_polygon(&polygon, |p| upper - self.offset_at(p));
+
+            let color = |p: Vec2| {
+                if !(lower.is_finite() && upper.is_finite()) {
+                    return lower_color;
+                }
+                let t = ((self.offset_at(p) - lower) / (upper - lower)).clamp(0., 1.);
+                lerp_color(lower_color, upper_color, t)
+            };
+
+            // Each band of a triangle is convex, so fan it out from the first point
+            for i in 1..polygon.len().saturating_sub(1) {
+                let points = [polygon[0], polygon[i], polygon[i + 1]];
+                shaded.push((points, points.map(&color)));
+            }
+        }
+    }
+
+    /// Subdivides until the per-vertex colors are close to the true gradient.
+    fn subdivide_radial(
+        &self, triangle: &[Vec2; 3], depth: u32, shaded: &mut Vec<([Vec2; 3], [Color; 3])>
+    ) {
+        let [a, b, c] = *triangle;
+        let colors = triangle.map(|p| self.color_at(p));
+
+        let ab = (a + b) / 2.;
+        let bc = (b + c) / 2.;
+        let ca = (c + a) / 2.;
+        let centroid = (a + b + c) / 3.;
+
+        let accurate = [
+            (ab, [0.5, 0.5, 0.]),
+            (bc, [0., 0.5, 0.5]),
+            (ca, [0.5, 0., 0.5]),
+            (centroid, [1. / 3., 1. / 3., 1. / 3.]),
+        ].iter().all(|(p, weights)| {
+            let expected = self.color_at(*p);
+            let interpolated = blend(&colors, weights);
+            color_distance(expected, interpolated) <= RADIAL_TOLERANCE
+        });
+
+        if depth == 0 || accurate {
+            shaded.push((*triangle, colors));
+            return;
+        }
+
+        for child in [[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]] {
+            self.subdivide_radial(&child, depth - 1, shaded);
+        }
+    }
+
+    pub fn to_skia_shader(&self) -> Shader<'static> {
+        let stops = self.stops.iter()
+            .map(|(offset, color)| GradientStop::new(*offset, to_skia_color(*color)))
+            .collect();
+
+        let shader = match self.kind {
+            GradientKind::Linear { start, end } => LinearGradient::new(
+                Point::from_xy(start.x, start.y),
+                Point::from_xy(end.x, end.y),
+                stops,
+                SpreadMode::Pad,
+                Transform::identity(),
+            ),
+            GradientKind::Radial { center, radius } => RadialGradient::new(
+                Point::from_xy(center.x, center.y),
+                Point::from_xy(center.x, center.y),
+                radius,
+                stops,
+                SpreadMode::Pad,
+                Transform::identity(),
+            ),
+        };
+
+        // Degenerate gradients are drawn with the color they end on
+        shader.unwrap_or_else(|| Shader::SolidColor(to_skia_color(self.color_at_offset(1.))))
+    }
+
+    /// Paints the gradient over the opaque parts of a pixmap, keeping its
+    /// alpha. `origin` is where the pixmap's top-left corner is drawn.
+    pub fn fill_masked(&self, pixmap: &mut Pixmap, origin: Vec2) {
+        let mut paint = Paint::default();
+        paint.shader = self.to_skia_shader();
+        paint.blend_mode = BlendMode::SourceIn;
+
+        let Some(rect) = Rect::from_xywh(0., 0., pixmap.width() as f32, pixmap.height() as f32) else {
+            return
+        };
+
+        pixmap.fill_rect(rect, &paint, Transform::from_translate(-origin.x, -origin.y), None);
+    }
+
+    /// Identifies the gradient relative to `origin`, so cached drawings can be
+    /// reused wherever the gradient and origin move together.
+    pub fn cache_key(&self, origin: Vec2) -> Vec<u32> {
+        let mut values = match self.kind {
+            GradientKind::Linear { start, end } => {
+                let start = start - origin;
+                let end = end - origin;
+                vec![0., start.x, start.y, end.x, end.y]
+            },
+            GradientKind::Radial { center, radius } => {
+                let center = center - origin;
+                vec![1., center.x, center.y, radius]
+            },
+        };
+
+        for (offset, color) in self.stops.iter() {
+            values.extend([*offset, color.r(), color.g(), color.b(), color.a()]);
+        }
+
+        values.iter().map(|value| value.to_bits()).collect()
+    }
+}
+
+impl Fill {
+    pub fn to_skia_paint(&self) -> Paint<'static> {
+        let mut paint = Paint::default();
+
+        match self {
+            Fill::Solid(color) => paint.set_color(to_skia_color(*color)),
+            Fill::Gradient(gradient) => paint.shader = gradient.to_skia_shader(),
+        }
+
+        paint
+    }
+
+    /// Pairs triangles with per-vertex colors.
+    pub fn shade_triangles(&self, triangles: &[[Vec2; 3]]) -> Vec<([Vec2; 3], [Color; 3])> {
+        match self {
+            Fill::Solid(color) => triangles.iter().map(|triangle| (*triangle, [*color; 3])).collect(),
+            Fill::Gradient(gradient) => gradient.shade_triangles(triangles),
+        }
+    }
+}
+
+fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    Color::from_rgba(lerp(a.r(), b.r()), lerp(a.g(), b.g()), lerp(a.b(), b.b()), lerp(a.a(), b.a()))
+}
+
+fn blend(colors: &[Color; 3], weights: &[f32; 3]) -> Color {
+    let channel = |f: fn(&Color) -> f32| {
+        colors.iter().zip(weights).map(|(color, weight)| f(color) * weight).sum()
+    };
+
+    Color::from_rgba(channel(Color::r), channel(Color::g), channel(Color::b), channel(Color::a))
+}
+
+fn color_distance(a: Color, b: Color) -> f32 {
+    [a.r() - b.r(), a.g() - b.g(), a.b() - b.b(), a.a() - b.a()]
+        .into_iter()
+        .fold(0., |max, delta| f32::max(max, delta.abs()))
+}
+
+/// Keeps the part of a convex polygon where `distance` is not negative.
+fn clip_polygon(polygon: &[Vec2], distance: impl Fn(Vec2) -> f32) -> Vec<Vec2> {
+    let mut clipped = vec![];
+
+    for (i, current) in polygon.iter().enumerate() {
+        let next = polygon[(i + 1) % polygon.len()];
+        let d_current = distance(*current);
+        let d_next = distance(next);
+
+        if d_current >= 0. {
+            clipped.push(*current);
+        }
+
+        if (d_current >= 0.) != (d_next >= 0.) {
+            let t = d_current / (d_current - d_next);
+            clipped.push(*current + (next - *current) * t);
+        }
+    }
+
+    clipped
+}
@@ -0,0 +1,873 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use speedy2d::color::Color;
+use speedy2d::dimen::{UVec2, Vec2};
+use speedy2d::numeric::RoundFloat;
+use speedy2d::shape::Rectangle;
+use thiserror::Error;
+use tiny_skia::{
+    BlendMode, ColorU8, FillRule, FilterQuality, LineJoin, Mask, Paint, PathBuilder, Pattern,
+    Pixmap, PixmapPaint, Rect, SpreadMode, Stroke, Transform
+};
+use tracing::{info, warn};
+
+use crate::animated_image;
+use crate::blend;
+use crate::clock::SharedClock;
+use crate::color_adjust::{AdjustedImageKey, ColorAdjust};
+use crate::gradient::Fill;
+use crate::nine_slice::{self, Insets};
+use crate::js_env::{JsEnv, GraphicsCalls, ImageRef};
+use crate::shape::Shape;
+use crate::svg_image::{self, SvgImage};
+use crate::text_effects::rasterize_effects;
+use crate::text_raster::rasterize_fill_text;
+use crate::transform::Affine;
+use crate::video::VideoFrame;
+
+const DEFAULT_RESOLUTION: (u32, u32) = (640, 480);
+// Renders a layer, adjusted image or SVG raster can go undrawn and unrendered
+// before it is dropped
+const LAYER_LIFETIME: u64 = 60;
+
+#[derive(Error, Debug)]
+pub enum HeadlessError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not encode PNG: {0}")]
+    PngEncoding(String),
+    #[error("No frames were requested")]
+    NoFrames,
+}
+
+/// The target and drawing state set aside while a layer is drawn.
+struct LayerTarget {
+    id: u32,
+    pixmap: Pixmap,
+    transform_stack: Vec<Affine>,
+    transform: Affine,
+    clip_stack: Vec<Option<Mask>>,
+    clip: Option<Mask>,
+}
+
+/// The target set aside while a blend mode's calls are drawn, to blend them
+/// onto it when the blend mode ends.
+struct BlendTarget {
+    mode: blend::BlendMode,
+    pixmap: Pixmap,
+}
+
+/// Executes `GraphicsCalls` with a CPU rasterizer so apps can be rendered
+/// without a window or OpenGL context.
+pub struct HeadlessRenderer {
+    pixmap: Pixmap,
+    root_path: PathBuf,
+    transform_stack: Vec<Affine>,
+    transform: Affine,
+    clip_stack: Vec<Option<Mask>>,
+    clip: Option<Mask>,
+    images: HashMap<String, Pixmap>,
+    /// Every frame of animated images, which aren't in `images`.
+    animated_images: HashMap<String, Vec<Pixmap>>,
+    /// Parsed SVGs, rasterized into `svg_rasters` at each size drawn.
+    svg_images: HashMap<String, SvgImage>,
+    /// SVGs rasterized by `svg_image::cache_key`, with the render they were
+    /// last drawn in.
+    svg_rasters: HashMap<String, (u64, Pixmap)>,
+    /// The last frame converted for each video, by video id.
+    video_frames: HashMap<u32, (u64, Pixmap)>,
+    /// Drawn layers by layer id, with the render they were last used in.
+    layers: HashMap<u32, (u64, Pixmap)>,
+    /// Images with their colors adjusted, with the render they were last
+    /// drawn in.
+    adjusted_images: HashMap<AdjustedImageKey, (u64, Pixmap)>,
+    layer_targets: Vec<LayerTarget>,
+    /// `None` for normal blending, which draws straight to the target.
+    blend_targets: Vec<Option<BlendTarget>>,
+    render_count: u64,
+}
+
+impl HeadlessRenderer {
+    pub fn new<P: AsRef<Path>>(app_root: P) -> Self {
+        let (width, height) = DEFAULT_RESOLUTION;
+
+        HeadlessRenderer {
+            pixmap: Pixmap::new(width, height).unwrap(),
+            root_path: app_root.as_ref().to_path_buf(),
+            transform_stack: vec![],
+            transform: Affine::IDENTITY,
+            clip_stack: vec![],
+            clip: None,
+            images: HashMap::new(),
+            animated_images: HashMap::new(),
+            svg_images: HashMap::new(),
+            svg_rasters: HashMap::new(),
+            video_frames: HashMap::new(),
+            layers: HashMap::new(),
+            adjusted_images: HashMap::new(),
+            layer_targets: vec![],
+            blend_targets: vec![],
+            render_count: 0,
+        }
+    }
+
+    pub fn pixmap(&self) -> &Pixmap {
+        &self.pixmap
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), HeadlessError> {
+        let data = self.pixmap.encode_png()
+            .map_err(|err| HeadlessError::PngEncoding(err.to_string()))?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Points image paths at another app, dropping images loaded for the old
+    /// one.
+    pub fn set_root_path(&mut self, root_path: &Path) {
+        if self.root_path != root_path {
+            self.root_path = root_path.to_path_buf();
+            self.images.clear();
+            self.animated_images.clear();
+            self.svg_images.clear();
+            self.svg_rasters.clear();
+            self.adjusted_images.clear();
+        }
+    }
+
+    /// A drawn layer, marked as used so it stays available to `DrawLayer`
+    /// calls in later renders.
+    pub fn use_layer(&mut self, id: u32) -> Option<&Pixmap> {
+        let render_count = self.render_count;
+        self.layers.get_mut(&id).map(|(last_used, pixmap)| {
+            *last_used = render_count;
+            &*pixmap
+        })
+    }
+
+    /// Removes a drawn layer, for one-off layers no other calls draw.
+    pub fn take_layer(&mut self, id: u32) -> Option<Pixmap> {
+        self.layers.remove(&id).map(|(_, pixmap)| pixmap)
+    }
+
+    pub fn render(&mut self, graphics_calls: &[GraphicsCalls]) {
+        self.transform_stack.clear();
+        self.transform = Affine::IDENTITY;
+        self.clip_stack.clear();
+        self.clip = None;
+        self.render_count += 1;
+
+        for call in graphics_calls.iter() {
+            use GraphicsCalls::*;
+            match call {
+                ClearScreenBlack => self.clear(tiny_skia::Color::BLACK),
+                ClearScreen(c) => self.clear(to_skia_color(*c)),
+                DrawRectangle(r, fill) => {
+                    let paint = fill.to_skia_paint();
+
+                    if let Some(rect) = to_skia_rect(r) {
+                        self.pixmap.fill_rect(
+                            rect, &paint, self.transform.to_skia(), self.clip.as_ref()
+                        );
+                    }
+                },
+                FillShape(shape, fill) => self.draw_shape(shape, None, fill),
+                StrokeShape(shape, thickness, fill) => self.draw_shape(shape, Some(*thickness), fill),
+                DrawRectangleImageTinted(r, image_ref, c) if svg_image::is_svg(&image_ref.path) => {
+                    self.draw_svg(r, &image_ref.path, *c);
+                },
+                DrawRectangleImageTinted(r, image_ref, c) => {
+                    self.draw_image(r, image_ref, *c);
+                },
+                DrawImageAdjusted(r, image_ref, c, adjust) => {
+                    self.draw_adjusted_image(r, image_ref, *c, adjust);
+                },
+                DrawVideo(r, frame, c) => self.draw_video(r, frame, *c),
+                DrawText(pos, fill, _, source) => {
+                    let Some((text, offset)) = rasterize_fill_text(source, fill, *pos) else {
+                        continue
+                    };
+                    self.draw_text_pixmap(*pos, &text, offset);
+                },
+                DrawTextEffects(pos, source, effects) => {
+                    let Some((layers, offset)) = rasterize_effects(source, effects) else { continue };
+                    self.draw_text_pixmap(*pos, &layers, offset);
+                },
+                DrawImageRegion(source, r, image_ref, c) => self.draw_image_region(source, r, image_ref, *c),
+                DrawImageNineSlice(r, insets, image_ref, c) => {
+                    self.draw_image_nine_slice(r, insets, image_ref, *c);
+                },
+                DrawImage(pos, image_ref) if svg_image::is_svg(&image_ref.path) => {
+                    let Some(svg) = self.get_svg_image(&image_ref.path) else { continue };
+                    let r = Rectangle::new(*pos, pos + svg.natural_size().into_f32());
+                    self.draw_svg(&r, &image_ref.path, Color::WHITE);
+                },
+                DrawImage(pos, image_ref) => {
+                    let Some(image) = self.get_image(image_ref) else { continue };
+                    let size = Vec2::new(image.width() as f32, image.height() as f32);
+                    let r = Rectangle::new(*pos, pos + size);
+                    self.draw_image(&r, image_ref, Color::WHITE);
+                },
+                PushTransform(transform) => {
+                    self.transform_stack.push(self.transform);
+                    self.transform = self.transform.then_apply(transform);
+                }
+                PopTransform => {
+                    self.transform = self.transform_stack.pop().unwrap_or(Affine::IDENTITY);
+                },
+                PushClip(r) => {
+                    let clip = self.intersect_clip(r);
+                    self.clip_stack.push(std::mem::replace(&mut self.clip, clip));
+                },
+                PopClip => {
+                    self.clip = self.clip_stack.pop().unwrap_or(None);
+                },
+                // Layers keep the size they were created with
+                SetResolution(_) if !self.layer_targets.is_empty() || !self.blend_targets.is_empty() => {},
+                SetResolution(uvec2) => {
+                    if uvec2.x != self.pixmap.width() || uvec2.y != self.pixmap.height() {
+                        if let Some(pixmap) = Pixmap::new(uvec2.x, uvec2.y) {
+                            self.pixmap = pixmap;
+                            // Masks must match the pixmap size
+                            self.clip_stack.clear();
+                            self.clip = None;
+                        }
+                    }
+                },
+                BeginLayer(id, size) => self.begin_layer(*id, *size),
+                EndLayer => self.end_layer(),
+                PushBlendMode(mode) => self.push_blend_mode(*mode),
+                PopBlendMode => self.pop_blend_mode(),
+                DrawLayer(r, id, c) => {
+                    let Some((last_used, image)) = self.layers.get_mut(id) else { continue };
+                    *last_used = self.render_count;
+                    draw_pixmap_rect(&mut self.pixmap, image, r, *c, self.transform, self.clip.as_ref());
+                },
+                ImageFileUpdate(pathbuf) => {
+                    self.update_image(pathbuf);
+                }
+            }
+        }
+
+        // Blend modes and layers left open still hold the screen
+        while !self.blend_targets.is_empty() {
+            self.pop_blend_mode();
+        }
+        while !self.layer_targets.is_empty() {
+            self.end_layer();
+        }
+
+        let render_count = self.render_count;
+        self.layers.retain(|_, (last_used, _)| render_count - *last_used < LAYER_LIFETIME);
+        self.adjusted_images.retain(|_, (last_used, _)| render_count - *last_used < LAYER_LIFETIME);
+        // SVGs drawn at a changing size leave a raster behind every render
+        self.svg_rasters.retain(|_, (last_used, _)| render_count - *last_used < LAYER_LIFETIME);
+    }
+
+    /// Sends drawing to a new transparent pixmap until the matching
+    /// `end_layer`.
+    fn begin_layer(&mut self, id: u32, size: UVec2) {
+        let pixmap = Pixmap::new(size.x.max(1), size.y.max(1)).unwrap();
+        self.layer_targets.push(LayerTarget {
+            id,
+            pixmap: std::mem::replace(&mut self.pixmap, pixmap),
+            transform_stack: std::mem::take(&mut self.transform_stack),
+            transform: std::mem::replace(&mut self.transform, Affine::IDENTITY),
+            clip_stack: std::mem::take(&mut self.clip_stack),
+            clip: self.clip.take(),
+        });
+    }
+
+    /// Sends drawing to a transparent pixmap of the same size, keeping the
+    /// transform and clip, until `pop_blend_mode` blends it onto the target.
+    fn push_blend_mode(&mut self, mode: blend::BlendMode) {
+        if mode == blend::BlendMode::Normal {
+            self.blend_targets.push(None);
+            return;
+        }
+
+        let pixmap = Pixmap::new(self.pixmap.width(), self.pixmap.height()).unwrap();
+        self.blend_targets.push(Some(BlendTarget {
+            mode,
+            pixmap: std::mem::replace(&mut self.pixmap, pixmap),
+        }));
+    }
+
+    fn pop_blend_mode(&mut self) {
+        let Some(Some(target)) = self.blend_targets.pop() else { return };
+        let group = std::mem::replace(&mut self.pixmap, target.pixmap);
+        let paint = PixmapPaint {
+            blend_mode: target.mode.to_skia(),
+            ..PixmapPaint::default()
+        };
+        self.pixmap.draw_pixmap(0, 0, group.as_ref(), &paint, Transform::identity(), None);
+    }
+
+    fn end_layer(&mut self) {
+        let Some(target) = self.layer_targets.pop() else { return };
+        let layer = std::mem::replace(&mut self.pixmap, target.pixmap);
+        self.transform_stack = target.transform_stack;
+        self.transform = target.transform;
+        self.clip_stack = target.clip_stack;
+        self.clip = target.clip;
+        self.layers.insert(target.id, (self.render_count, layer));
+    }
+
+    /// Draws rasterized text, or its effects, placed `offset` from `pos`.
+    fn draw_text_pixmap(&mut self, pos: Vec2, text: &Pixmap, (dx, dy): (i32, i32)) {
+        if self.transform.is_translation() {
+            // Match the window renderer, which rounds to whole pixels
+            let relative_pos = (pos + self.transform.translation()).round();
+            self.pixmap.draw_pixmap(
+                relative_pos.x as i32 + dx,
+                relative_pos.y as i32 + dy,
+                text.as_ref(),
+                &PixmapPaint::default(),
+                Transform::identity(),
+                self.clip.as_ref()
+            );
+        } else {
+            let paint = PixmapPaint {
+                quality: FilterQuality::Bilinear,
+                ..PixmapPaint::default()
+            };
+            let placement = Affine::translate(pos.x + dx as f32, pos.y + dy as f32);
+            let transform = self.transform.then_apply(&placement);
+            self.pixmap.draw_pixmap(
+                0, 0, text.as_ref(), &paint, transform.to_skia(), self.clip.as_ref()
+            );
+        }
+    }
+
+    fn draw_image(&mut self, r: &Rectangle, image_ref: &ImageRef, tint: Color) {
+        if self.get_image(image_ref).is_none() {
+            return;
+        }
+
+        let image = match self.animated_images.get(&image_ref.path) {
+            Some(frames) => &frames[image_ref.frame % frames.len()],
+            None => &self.images[&image_ref.path],
+        };
+        draw_pixmap_rect(&mut self.pixmap, image, r, tint, self.transform, self.clip.as_ref());
+    }
+
+    fn draw_adjusted_image(&mut self, r: &Rectangle, image_ref: &ImageRef, tint: Color, adjust: &ColorAdjust) {
+        let is_svg = svg_image::is_svg(&image_ref.path);
+        let svg_size = is_svg.then(|| {
+            let scale = self.transform.scale_factors();
+            svg_image::target_size(r.width(), r.height(), (scale.x, scale.y))
+        });
+        let key = AdjustedImageKey::new(image_ref, svg_size, adjust);
+
+        if !self.adjusted_images.contains_key(&key) {
+            let image = if is_svg {
+                let Some(key) = self.rasterize_svg(&image_ref.path, r.width(), r.height()) else { return };
+                &self.svg_rasters[&key].1
+            } else {
+                let Some(image) = self.get_image(image_ref) else { return };
+                image
+            };
+
+            let mut adjusted = image.clone();
+            adjust.apply(&mut adjusted);
+            self.adjusted_images.insert(key.clone(), (self.render_count, adjusted));
+        }
+
+        let (last_used, adjusted) = self.adjusted_images.get_mut(&key).unwrap();
+        *last_used = self.render_count;
+        draw_pixmap_rect(&mut self.pixmap, adjusted, r, tint, self.transform, self.clip.as_ref());
+    }
+
+    /// Draws an SVG rasterized at the size it covers in the output.
+    fn draw_svg(&mut self, r: &Rectangle, path_string: &str, tint: Color) {
+        let Some(key) = self.rasterize_svg(path_string, r.width(), r.height()) else { return };
+        let image = &self.svg_rasters[&key].1;
+        draw_pixmap_rect(&mut self.pixmap, image, r, tint, self.transform, self.clip.as_ref());
+    }
+
+    /// Rasterizes the SVG for drawing `width` by `height` in the current
+    /// transform, returning its key in `svg_rasters`.
+    fn rasterize_svg(&mut self, path_string: &str, width: f32, height: f32) -> Option<String> {
+        let scale = self.transform.scale_factors();
+        let size = svg_image::target_size(width, height, (scale.x, scale.y));
+        let key = svg_image::cache_key(path_string, size);
+
+        if let Some((last_used, _)) = self.svg_rasters.get_mut(&key) {
+            *last_used = self.render_count;
+        } else {
+            let pixmap = self.get_svg_image(path_string)?.rasterize(size)?;
+            self.svg_rasters.insert(key.clone(), (self.render_count, pixmap));
+        }
+
+        Some(key)
+    }
+
+    /// Draws the `source` part of an image, given in image pixels, over `r`.
+    fn draw_image_region(&mut self, source: &Rectangle, r: &Rectangle, image_ref: &ImageRef, tint: Color) {
+        if source.width() <= 0. || source.height() <= 0. {
+            return;
+        }
+
+        let (image, source) = if svg_image::is_svg(&image_ref.path) {
+            // Rasterize the whole SVG at the scale the region is drawn at
+            let Some(svg) = self.get_svg_image(&image_ref.path) else { return };
+            let natural = svg.natural_size().into_f32();
+            let sx = r.width() / source.width();
+            let sy = r.height() / source.height();
+            let Some(key) = self.rasterize_svg(&image_ref.path, natural.x * sx, natural.y * sy) else {
+                return
+            };
+
+            // Scale the region to the rasterized pixels
+            let image = &self.svg_rasters[&key].1;
+            let px = image.width() as f32 / natural.x;
+            let py = image.height() as f32 / natural.y;
+            let tl = *source.top_left();
+            let br = *source.bottom_right();
+            let source = Rectangle::new(Vec2::new(tl.x * px, tl.y * py), Vec2::new(br.x * px, br.y * py));
+            (image, source)
+        } else {
+            if self.get_image(image_ref).is_none() {
+                return;
+            }
+            let image = match self.animated_images.get(&image_ref.path) {
+                Some(frames) => &frames[image_ref.frame % frames.len()],
+                None => &self.images[&image_ref.path],
+            };
+            (image, source.clone())
+        };
+
+        draw_pixmap_region(
+            &mut self.pixmap, image, &source, r, tint, self.transform, self.clip.as_ref()
+        );
+    }
+
+    fn draw_image_nine_slice(&mut self, r: &Rectangle, insets: &Insets, image_ref: &ImageRef, tint: Color) {
+        let image_size = if svg_image::is_svg(&image_ref.path) {
+            let Some(svg) = self.get_svg_image(&image_ref.path) else { return };
+            svg.natural_size().into_f32()
+        } else {
+            let Some(image) = self.get_image(image_ref) else { return };
+            Vec2::new(image.width() as f32, image.height() as f32)
+        };
+
+        for (source, dest) in nine_slice::regions(image_size, insets, r) {
+            self.draw_image_region(&source, &dest, image_ref, tint);
+        }
+    }
+
+    fn get_svg_image(&mut self, path_string: &str) -> Option<&SvgImage> {
+        if !self.svg_images.contains_key(path_string) {
+            let mut path = self.root_path.clone();
+            path.push(path_string);
+            let Some(svg) = SvgImage::load(&path) else {
+                warn!("Could not load SVG {}", path.display());
+                return None;
+            };
+            self.svg_images.insert(path_string.to_owned(), svg);
+        }
+
+        self.svg_images.get(path_string)
+    }
+
+    fn draw_video(&mut self, r: &Rectangle, frame: &VideoFrame, tint: Color) {
+        let current = self.video_frames.get(&frame.video)
+            .map_or(false, |(index, _)| *index == frame.index);
+
+        if !current {
+            let Some(pixmap) = frame_to_pixmap(frame) else { return };
+            self.video_frames.insert(frame.video, (frame.index, pixmap));
+        }
+
+        let (_, image) = &self.video_frames[&frame.video];
+        draw_pixmap_rect(&mut self.pixmap, image, r, tint, self.transform, self.clip.as_ref());
+    }
+
+    /// Fills the shape, or strokes it when given a thickness.
+    fn draw_shape(&mut self, shape: &Shape, thickness: Option<f32>, fill: &Fill) {
+        let outline = shape.outline();
+        let Some((first, rest)) = outline.split_first() else { return };
+
+        let mut builder = PathBuilder::new();
+        builder.move_to(first.x, first.y);
+        for p in rest {
+            builder.line_to(p.x, p.y);
+        }
+        if shape.is_closed() {
+            builder.close();
+        }
+        let Some(path) = builder.finish() else { return };
+
+        let paint = fill.to_skia_paint();
+        let transform = self.transform.to_skia();
+
+        match thickness {
+            Some(width) => {
+                // Bevel joins match the window renderer's tessellation
+                let stroke = Stroke { width, line_join: LineJoin::Bevel, ..Stroke::default() };
+                self.pixmap.stroke_path(&path, &paint, &stroke, transform, self.clip.as_ref());
+            },
+            None => {
+                self.pixmap.fill_path(&path, &paint, FillRule::Winding, transform, self.clip.as_ref());
+            },
+        }
+    }
+
+    fn clear(&mut self, color: tiny_skia::Color) {
+        // Like the window renderer's scissor test, clearing respects the clip
+        let Some(clip) = &self.clip else {
+            self.pixmap.fill(color);
+            return;
+        };
+
+        let mut paint = Paint::default();
+        paint.set_color(color);
+        paint.blend_mode = BlendMode::Source;
+        let full = Rect::from_xywh(0., 0., self.pixmap.width() as f32, self.pixmap.height() as f32);
+
+        if let Some(full) = full {
+            self.pixmap.fill_rect(full, &paint, Transform::identity(), Some(clip));
+        }
+    }
+
+    /// Returns the current clip intersected with a rectangle in the current
+    /// transform's coordinates.
+    fn intersect_clip(&self, r: &Rectangle) -> Option<Mask> {
+        // An empty mask hides everything, as a zero-sized clip should
+        let Some(rect) = to_skia_rect(r) else {
+            return Mask::new(self.pixmap.width(), self.pixmap.height());
+        };
+        let path = PathBuilder::from_rect(rect);
+        let transform = self.transform.to_skia();
+
+        match &self.clip {
+            Some(clip) => {
+                let mut mask = clip.clone();
+                mask.intersect_path(&path, FillRule::Winding, true, transform);
+                Some(mask)
+            },
+            None => {
+                let mut mask = Mask::new(self.pixmap.width(), self.pixmap.height())?;
+                mask.fill_path(&path, FillRule::Winding, true, transform);
+                Some(mask)
+            },
+        }
+    }
+
+    fn get_image(&mut self, image_ref: &ImageRef) -> Option<&Pixmap> {
+        let path_string = &image_ref.path;
+
+        if !self.images.contains_key(path_string) && !self.animated_images.contains_key(path_string) {
+            let mut path = self.root_path.clone();
+            path.push(path_string);
+
+            if let Some(frames) = load_animated_pixmaps(&path) {
+                self.animated_images.insert(path_string.to_owned(), frames);
+            } else {
+                match load_pixmap(&path) {
+                    Some(pixmap) => {
+                        self.images.insert(path_string.to_owned(), pixmap);
+                    },
+                    None => {
+                        warn!("Could not load image {}", path.display());
+                        return None;
+                    }
+                }
+            }
+        }
+
+        match self.animated_images.get(path_string) {
+            Some(frames) => frames.get(image_ref.frame % frames.len()),
+            None => self.images.get(path_string),
+        }
+    }
+
+    pub fn update_image(&mut self, path: &Path) {
+        let Ok(key) = path.strip_prefix(&self.root_path) else { return };
+        let key = key.to_str().unwrap().to_owned();
+        self.adjusted_images.retain(|adjusted_key, _| adjusted_key.path != key);
+
+        if svg_image::is_svg(&key) {
+            let Some(svg) = SvgImage::load(path) else { return };
+            let sizes: Vec<_> = self.svg_rasters.keys()
+                .filter_map(|cache_key| svg_image::cached_size(cache_key, &key))
+                .collect();
+
+            for size in sizes {
+                let Some(pixmap) = svg.rasterize(size) else { continue };
+                if let Some((_, raster)) = self.svg_rasters.get_mut(&svg_image::cache_key(&key, size)) {
+                    *raster = pixmap;
+                }
+            }
+            self.svg_images.insert(key, svg);
+            return;
+        }
+
+        // A file can change between a still and an animated image
+        if let Some(frames) = load_animated_pixmaps(path) {
+            self.images.remove(&key);
+            self.animated_images.insert(key, frames);
+        } else if let Some(pixmap) = load_pixmap(path) {
+            self.animated_images.remove(&key);
+            self.images.insert(key, pixmap);
+        }
+    }
+}
+
+/// Draws an image stretched over `r`, multiplied by `tint`.
+fn draw_pixmap_rect(
+    target: &mut Pixmap, image: &Pixmap, r: &Rectangle, tint: Color, transform: Affine,
+    clip: Option<&Mask>
+) {
+    let source = Rectangle::new(Vec2::ZERO, Vec2::new(image.width() as f32, image.height() as f32));
+    draw_pixmap_region(target, image, &source, r, tint, transform, clip);
+}
+
+/// Draws the `source` part of an image, in image pixels, stretched over `r`
+/// and multiplied by `tint`.
+fn draw_pixmap_region(
+    target: &mut Pixmap, image: &Pixmap, source: &Rectangle, r: &Rectangle, tint: Color,
+    transform: Affine, clip: Option<&Mask>
+) {
+    let tinted;
+    let image = if tint.r() < 1. || tint.g() < 1. || tint.b() < 1. {
+        tinted = tint_pixmap(image, tint);
+        &tinted
+    } else {
+        image
+    };
+
+    let Some(rect) = to_skia_rect(r) else { return };
+    let sx = r.width() / source.width();
+    let sy = r.height() / source.height();
+
+    let mut paint = Paint::default();
+    paint.shader = Pattern::new(
+        image.as_ref(),
+        SpreadMode::Pad,
+        FilterQuality::Bilinear,
+        tint.a(),
+        Transform::from_row(
+            sx, 0., 0., sy,
+            r.top_left().x - source.top_left().x * sx,
+            r.top_left().y - source.top_left().y * sy,
+        ),
+    );
+
+    target.fill_rect(rect, &paint, transform.to_skia(), clip);
+}
+
+/// Converts a decoded video frame into a premultiplied pixmap.
+fn frame_to_pixmap(frame: &VideoFrame) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(frame.size.x, frame.size.y)?;
+
+    for (pixel, rgba) in pixmap.pixels_mut().iter_mut().zip(frame.pixels.chunks_exact(4)) {
+        *pixel = ColorU8::from_rgba(rgba[0], rgba[1], rgba[2], rgba[3]).premultiply();
+    }
+
+    Some(pixmap)
+}
+
+/// Loads every frame of an animated image, or returns `None` for still images.
+pub fn load_animated_pixmaps(path: &Path) -> Option<Vec<Pixmap>> {
+    animated_image::load_frames(path)?
+        .iter()
+        .map(|frame| {
+            let buffer = frame.buffer();
+            let mut pixmap = Pixmap::new(buffer.width(), buffer.height())?;
+            for (pixel, rgba) in pixmap.pixels_mut().iter_mut().zip(buffer.pixels()) {
+                *pixel = ColorU8::from_rgba(rgba[0], rgba[1], rgba[2], rgba[3]).premultiply();
+            }
+            Some(pixmap)
+        })
+        .collect()
+}
+
+/// Loads an image file into a premultiplied pixmap.
+pub fn load_pixmap(path: &Path) -> Option<Pixmap> {
+    let image = image::open(path).ok()?.to_rgba8();
+    let mut pixmap = Pixmap::new(image.width(), image.height())?;
+
+    for (pixel, rgba) in pixmap.pixels_mut().iter_mut().zip(image.pixels()) {
+        *pixel = ColorU8::from_rgba(rgba[0], rgba[1], rgba[2], rgba[3]).premultiply();
+    }
+
+    Some(pixmap)
+}
+
+fn tint_pixmap(image: &Pixmap, tint: Color) -> Pixmap {
+    let mut tinted = image.clone();
+
+    for pixel in tinted.pixels_mut() {
+        let c = pixel.demultiply();
+        *pixel = ColorU8::from_rgba(
+            (c.red() as f32 * tint.r()).round() as u8,
+            (c.green() as f32 * tint.g()).round() as u8,
+            (c.blue() as f32 * tint.b()).round() as u8,
+            c.alpha(),
+        ).premultiply();
+    }
+
+    tinted
+}
+
+pub fn to_skia_color(c: Color) -> tiny_skia::Color {
+    tiny_skia::Color::from_rgba(
+        c.r().clamp(0., 1.),
+        c.g().clamp(0., 1.),
+        c.b().clamp(0., 1.),
+        c.a().clamp(0., 1.),
+    ).unwrap_or(tiny_skia::Color::BLACK)
+}
+
+fn to_skia_rect(r: &Rectangle) -> Option<Rect> {
+    Rect::from_xywh(r.top_left().x, r.top_left().y, r.width(), r.height())
+}
+
+/// Runs the application with a fixed time step, saving the requested frames
+/// as PNG files in `out_dir`.
+pub fn run(app_path: &Path, frames: &[u32], out_dir: &Path, fps: f32, clock: SharedClock)
+    -> Result<(), HeadlessError>
+{
+    std::fs::create_dir_all(out_dir)?;
+
+    render_frames(app_path, frames, fps, clock, |frame, renderer| {
+        let mut path = out_dir.to_path_buf();
+        path.push(frame_file_name(frame));
+        renderer.save_png(&path)?;
+        info!("Wrote {}", path.display());
+        Ok(())
+    })
+}
+
+pub fn frame_file_name(frame: u32) -> String {
+    format!("frame_{:05}.png", frame)
+}
+
+/// Runs the application with a fixed time step of `1 / fps`, calling
+/// `on_frame` after each of the requested frames has been rendered.
+pub fn render_frames<F>(app_path: &Path, frames: &[u32], fps: f32, clock: SharedClock, mut on_frame: F)
+    -> Result<(), HeadlessError>
+where
+    F: FnMut(u32, &HeadlessRenderer) -> Result<(), HeadlessError>
+{
+    let last_frame = *frames.iter().max().ok_or(HeadlessError::NoFrames)?;
+
+    let mut script_env = JsEnv::new(app_path, clock);
+    script_env.wait_for_video_frames();
+    if let Err(err) = script_env.call_init() {
+        warn!("init failed: {}", err);
+    }
+
+    let mut renderer = HeadlessRenderer::new(app_path);
+    let dt = 1.0 / fps;
+
+    for frame in 0..=last_frame {
+        script_env.handle_file_changes();
+        if let Err(err) = script_env.run_frame(dt) {
+            warn!("Frame {} failed: {}", frame, err);
+        }
+
+        let graphics_calls = std::mem::take(&mut *script_env.graphics_calls().borrow_mut());
+        renderer.render(&graphics_calls);
+
+        if frames.contains(&frame) {
+            on_frame(frame, &renderer)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rectangle {
+        Rectangle::new(Vec2::new(x, y), Vec2::new(x + width, y + height))
+    }
+
+    fn nested_layers() -> (Vec<GraphicsCalls>, Vec<GraphicsCalls>) {
+        use GraphicsCalls::*;
+
+        let inner = vec![
+            BeginLayer(1, UVec2::new(10, 10)),
+            DrawRectangle(rect(0., 0., 10., 10.), Fill::Solid(Color::RED)),
+            EndLayer,
+        ];
+        let outer = vec![
+            BeginLayer(2, UVec2::new(20, 20)),
+            ClearScreen(Color::BLUE),
+            DrawLayer(rect(5., 5., 10., 10.), 1, Color::WHITE),
+            EndLayer,
+        ];
+        (inner, outer)
+    }
+
+    #[test]
+    fn nested_layers_match_when_rendered_scope_by_scope() {
+        let (inner, outer) = nested_layers();
+
+        // Headless draws every call in one render
+        let mut headless = HeadlessRenderer::new(".");
+        let mut calls = inner.clone();
+        calls.extend(outer.clone());
+        calls.push(GraphicsCalls::DrawLayer(rect(0., 0., 20., 20.), 2, Color::WHITE));
+        headless.render(&calls);
+
+        // The window renders each scope separately, then draws the layer
+        // textures itself
+        let mut layer_renderer = HeadlessRenderer::new(".");
+        layer_renderer.render(&inner);
+        assert!(layer_renderer.use_layer(1).is_some());
+        layer_renderer.render(&outer);
+        let window_layer = layer_renderer.use_layer(2).unwrap().clone();
+
+        for (x, y) in [(2, 2), (10, 10), (17, 17)] {
+            let expected = headless.pixmap().pixel(x, y).unwrap();
+            assert_eq!(window_layer.pixel(x, y).unwrap(), expected, "pixel {}, {}", x, y);
+        }
+        assert_eq!(window_layer.pixel(10, 10).unwrap().demultiply(), ColorU8::from_rgba(255, 0, 0, 255));
+    }
+
+    #[test]
+    fn used_layers_outlive_other_renders() {
+        let (inner, _) = nested_layers();
+        let mut renderer = HeadlessRenderer::new(".");
+        renderer.render(&inner);
+
+        for _ in 0..LAYER_LIFETIME * 2 {
+            renderer.render(&[]);
+            assert!(renderer.use_layer(1).is_some());
+        }
+    }
+
+    #[test]
+    fn svg_rasters_expire_when_no_longer_drawn() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("signrs-headless-svg-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("dot.svg"),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><circle cx="5" cy="5" r="5"/></svg>"#,
+        ).unwrap();
+
+        let image_ref = ImageRef { path: "dot.svg".to_owned(), frame: 0 };
+        let mut renderer = HeadlessRenderer::new(&dir);
+
+        // A growing SVG is rasterized at a new size every render
+        for size in 1..=LAYER_LIFETIME * 2 {
+            let r = rect(0., 0., size as f32, size as f32);
+            renderer.render(&[GraphicsCalls::DrawRectangleImageTinted(r, image_ref.clone(), Color::WHITE)]);
+            assert!(renderer.svg_rasters.len() as u64 <= LAYER_LIFETIME);
+        }
+
+        // Sizes still being drawn are kept
+        let r = rect(0., 0., 5., 5.);
+        for _ in 0..LAYER_LIFETIME * 2 {
+            renderer.render(&[GraphicsCalls::DrawRectangleImageTinted(r.clone(), image_ref.clone(), Color::WHITE)]);
+        }
+        assert_eq!(renderer.svg_rasters.len(), 1);
+    }
+}
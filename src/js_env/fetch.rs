@@ -0,0 +1,357 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use boa_engine::{Context, JsError, JsNativeError, JsResult, JsValue, NativeFunction, Source};
+use boa_engine::builtins::promise::ResolvingFunctions;
+use boa_engine::object::builtins::JsPromise;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::js_env::JsEnv;
+
+/// The directory in each app for player-written files, which isn't watched.
+pub const CACHE_ROOT: &str = ".cache";
+const CACHE_DIR: &str = "fetch";
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Normalizes the `fetch()` arguments before handing them to the native
+/// `__fetch`, and wraps the raw result in a minimal `Response`.
+const FETCH_SHIM: &str = r###"
+    class Response {
+        constructor(raw) {
+            this.url = raw.url;
+            this.status = raw.status;
+            this.statusText = raw.status_text;
+            this.headers = Object.fromEntries(raw.headers);
+            this.fromCache = raw.from_cache;
+            this._body = raw.body;
+        }
+
+        get ok() {
+            return this.status >= 200 && this.status < 300;
+        }
+
+        text() {
+            return Promise.resolve(this._body);
+        }
+
+        json() {
+            return Promise.resolve().then(() => JSON.parse(this._body));
+        }
+    }
+
+    globalThis.Response = Response;
+
+    globalThis.fetch = function(resource, options = {}) {
+        const headers = Object.entries(options.headers || {})
+            .map(([name, value]) => [String(name), String(value)]);
+        const body = (options.body === undefined || options.body === null)
+            ? null : String(options.body);
+
+        return __fetch(
+            String(resource),
+            String(options.method || "GET").toUpperCase(),
+            JSON.stringify(headers),
+            body
+        ).then(raw => new Response(raw));
+    };
+"###;
+
+#[derive(Debug)]
+pub struct FetchRequest {
+    pub url: String,
+    pub method: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FetchResponse {
+    pub url: String,
+    pub status: u16,
+    pub status_text: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    pub from_cache: bool,
+}
+
+struct FetchResult {
+    id: u32,
+    outcome: Result<FetchResponse, String>,
+}
+
+/// Runs `fetch()` requests on background threads and hands the results back to
+/// the JS thread, which settles the matching promises once per frame.
+pub struct Fetcher {
+    cache_dir: PathBuf,
+    next_id: u32,
+    pending: HashMap<u32, ResolvingFunctions>,
+    result_tx: mpsc::Sender<FetchResult>,
+    result_rx: mpsc::Receiver<FetchResult>,
+}
+
+impl Fetcher {
+    pub fn new(app_path: &Path) -> Self {
+        let (result_tx, result_rx) = mpsc::channel();
+        let mut cache_dir = app_path.to_path_buf();
+        cache_dir.push(CACHE_ROOT);
+        cache_dir.push(CACHE_DIR);
+
+        Fetcher {
+            cache_dir,
+            next_id: 0,
+            pending: HashMap::new(),
+            result_tx,
+            result_rx,
+        }
+    }
+
+    fn start(&mut self, request: FetchRequest, resolvers: ResolvingFunctions) {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.pending.insert(id, resolvers);
+
+        let result_tx = self.result_tx.clone();
+        let cache_dir = self.cache_dir.clone();
+
+        thread::spawn(move || {
+            let outcome = fetch_with_cache(&request, &cache_dir);
+            let _ = result_tx.send(FetchResult { id, outcome });
+        });
+    }
+}
+
+/// Performs a blocking request. Successful GET responses are saved to
+/// `cache_dir` and served from there when the server can't be reached or
+/// returns a server error.
+pub fn fetch_with_cache(request: &FetchRequest, cache_dir: &Path) -> Result<FetchResponse, String> {
+    let cacheable = request.method == "GET";
+    let cache_path = cache_path(cache_dir, &request.url);
+
+    let response = fetch_blocking(request);
+
+    match response {
+        Ok(response) if response.status < 500 => {
+            if cacheable && (200..300).contains(&response.status) {
+                if let Err(err) = save_cached(&cache_path, &response) {
+                    warn!("Could not cache {}: {}", request.url, err);
+                }
+            }
+            Ok(response)
+        },
+        _ if cacheable && cache_path.is_file() => {
+            info!("Serving {} from cache", request.url);
+            load_cached(&cache_path)
+        },
+        response => response,
+    }
+}
+
+fn fetch_blocking(request: &FetchRequest) -> Result<FetchResponse, String> {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    let mut http_request = agent.request(&request.method, &request.url);
+
+    for (name, value) in request.headers.iter() {
+        http_request = http_request.set(name, value);
+    }
+
+    let result = match &request.body {
+        Some(body) => http_request.send_string(body),
+        None => http_request.call(),
+    };
+
+    // Like fetch(), HTTP error statuses are responses rather than failures
+    let response = match result {
+        Ok(response) => response,
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(ureq::Error::Transport(err)) => return Err(err.to_string()),
+    };
+
+    let headers = response.headers_names()
+        .into_iter()
+        .filter_map(|name| {
+            let value = response.header(&name)?.to_owned();
+            Some((name, value))
+        })
+        .collect();
+
+    Ok(FetchResponse {
+        url: response.get_url().to_owned(),
+        status: response.status(),
+        status_text: response.status_text().to_owned(),
+        headers,
+        body: response.into_string().map_err(|err| err.to_string())?,
+        from_cache: false,
+    })
+}
+
+fn cache_path(cache_dir: &Path, url: &str) -> PathBuf {
+    // FNV-1a keeps file names stable across builds, unlike std's hasher
+    let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+
+    let mut path = cache_dir.to_path_buf();
+    path.push(format!("{:016x}.json", hash));
+    path
+}
+
+fn save_cached(path: &Path, response: &FetchResponse) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string(response)?)
+}
+
+fn load_cached(path: &Path) -> Result<FetchResponse, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut response: FetchResponse = serde_json::from_str(&text).map_err(|err| err.to_string())?;
+    response.from_cache = true;
+    Ok(response)
+}
+
+pub fn register_fns_and_types(context: &mut Context, fetcher: &Rc<RefCell<Fetcher>>) -> JsResult<()> {
+    let fetcher_ = fetcher.clone();
+    unsafe {
+        context.register_global_callable(
+            "__fetch", 4, NativeFunction::from_closure(move |this, args, context| {
+                fetch(&fetcher_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    context.eval(Source::from_bytes(FETCH_SHIM))?;
+    Ok(())
+}
+
+fn fetch(
+    fetcher: &Rc<RefCell<Fetcher>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 4 {
+        return Err(JsNativeError::typ().with_message("Not enough arguments").into());
+    }
+
+    let url = args[0].try_js_into::<String>(context)?;
+    let method = args[1].try_js_into::<String>(context)?;
+    let headers = args[2].try_js_into::<String>(context)?;
+    let headers = serde_json::from_str(&headers)
+        .map_err(|err| JsNativeError::typ().with_message(err.to_string()))?;
+    let body = match &args[3] {
+        JsValue::Null | JsValue::Undefined => None,
+        body => Some(body.try_js_into::<String>(context)?),
+    };
+
+    let (promise, resolvers) = JsPromise::new_pending(context);
+    fetcher.borrow_mut().start(FetchRequest { url, method, headers, body }, resolvers);
+    Ok(promise.into())
+}
+
+impl JsEnv {
+    /// Settles the promises of requests that finished since the last frame.
+    pub fn handle_fetch_results(&mut self) {
+        let results: Vec<FetchResult> = self.fetcher.borrow().result_rx.try_iter().collect();
+
+        for result in results {
+            let Some(resolvers) = self.fetcher.borrow_mut().pending.remove(&result.id) else {
+                continue
+            };
+
+            let call_result = match result.outcome {
+                Ok(response) => {
+                    let raw = serde_json::to_value(&response).unwrap();
+                    JsValue::from_json(&raw, &mut self.context).and_then(|raw| {
+                        resolvers.resolve.call(&JsValue::Undefined, &[raw], &mut self.context)
+                    })
+                },
+                Err(message) => {
+                    let error = JsError::from(
+                        JsNativeError::typ().with_message(format!("fetch failed: {}", message))
+                    ).to_opaque(&mut self.context);
+                    resolvers.reject.call(&JsValue::Undefined, &[error], &mut self.context)
+                },
+            };
+
+            if let Err(err) = call_result {
+                warn!("Could not settle fetch promise: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    /// Serves one canned response on a local port, returning its URL and the
+    /// server thread, which closes the port when it finishes.
+    fn serve_once(body: &'static str) -> (String, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/data.json", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(), body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        (url, server)
+    }
+
+    fn get(url: &str) -> FetchRequest {
+        FetchRequest {
+            url: url.to_owned(),
+            method: "GET".to_owned(),
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    #[test]
+    fn serves_cached_response_when_server_is_gone() {
+        let mut cache_dir = std::env::temp_dir();
+        cache_dir.push(format!("signrs-fetch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        let (url, server) = serve_once(r#"{"message": "hello"}"#);
+
+        let response = fetch_with_cache(&get(&url), &cache_dir).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, r#"{"message": "hello"}"#);
+        assert!(!response.from_cache);
+        assert!(response.headers.iter().any(|(name, value)| {
+            name.eq_ignore_ascii_case("content-type") && value == "application/json"
+        }));
+
+        server.join().unwrap();
+        let response = fetch_with_cache(&get(&url), &cache_dir).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, r#"{"message": "hello"}"#);
+        assert!(response.from_cache);
+
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+
+    #[test]
+    fn fails_without_server_or_cache() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/missing.json", listener.local_addr().unwrap());
+        drop(listener);
+
+        let cache_dir = std::env::temp_dir().join(format!("signrs-fetch-empty-{}", std::process::id()));
+        assert!(fetch_with_cache(&get(&url), &cache_dir).is_err());
+    }
+}
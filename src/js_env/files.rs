@@ -1,32 +1,119 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::UNIX_EPOCH;
 
-use boa_engine::{Context, JsNativeError, JsResult, JsValue, NativeFunction};
-use boa_engine::object::builtins::JsFunction;
+use boa_engine::{Context, JsError, JsNativeError, JsResult, JsValue, NativeFunction};
+use boa_engine::object::builtins::{JsArray, JsFunction};
+use serde_json::{Map, Value};
 
 use crate::js_env::JsEnv;
+use crate::js_env::timers::Timers;
+use crate::js_env::fetch::Fetcher;
+use crate::js_env::video::Videos;
+use crate::js_env::tweens::Tweens;
 use crate::iter_util::iter_unique;
 
 use super::GraphicsCalls;
 
+#[derive(Debug, Clone)]
+pub enum WatchKind {
+    Json,
+    Text,
+    Csv { header: Option<bool>, delimiter: u8 },
+    Xml,
+    Dir { patterns: Vec<String> },
+}
+
+#[derive(Clone)]
+pub struct Watch {
+    kind: WatchKind,
+    callback: JsFunction,
+}
+
+impl WatchKind {
+    /// Loads and parses a watched file, with parse failures as `SyntaxError`s.
+    fn load(&self, path: &Path, context: &mut Context) -> Result<JsValue, JsError> {
+        match self {
+            WatchKind::Json => JsEnv::load_json(path, context),
+            WatchKind::Text => load_text(path),
+            WatchKind::Csv { header, delimiter } => load_csv(path, *header, *delimiter, context),
+            WatchKind::Xml => load_xml(path, context),
+            WatchKind::Dir { patterns } => load_dir(path, patterns, context),
+        }
+    }
+    
+    /// Loads a watched file as the `(data, error)` arguments for its callback.
+    fn load_args(&self, path: &Path, context: &mut Context) -> [JsValue; 2] {
+        match self.load(path, context) {
+            Ok(data) => [data, JsValue::Undefined],
+            Err(err) => [JsValue::Undefined, err.to_opaque(context)],
+        }
+    }
+}
+
 pub fn register_fns_and_types(
     context: &mut Context,
-    watches: &Rc<RefCell<HashMap<PathBuf, JsFunction>>>
+    watches: &Rc<RefCell<HashMap<PathBuf, Watch>>>
 ) {
     let watches_ = watches.clone();
     unsafe {
         context.register_global_callable(
             "watch_json", 2, NativeFunction::from_closure(move |this, args, context| {
-                watch_json(&watches_, this, args, context)
+                let run_first = run_first_arg(args.get(2), context)?;
+                watch_file(&watches_, WatchKind::Json, run_first, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let watches_ = watches.clone();
+    unsafe {
+        context.register_global_callable(
+            "watch_text", 2, NativeFunction::from_closure(move |this, args, context| {
+                let run_first = run_first_arg(args.get(2), context)?;
+                watch_file(&watches_, WatchKind::Text, run_first, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let watches_ = watches.clone();
+    unsafe {
+        context.register_global_callable(
+            "watch_csv", 2, NativeFunction::from_closure(move |this, args, context| {
+                let run_first = run_first_arg(args.get(2), context)?;
+                let kind = csv_kind(args.get(3), context)?;
+                watch_file(&watches_, kind, run_first, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let watches_ = watches.clone();
+    unsafe {
+        context.register_global_callable(
+            "watch_xml", 2, NativeFunction::from_closure(move |this, args, context| {
+                let run_first = run_first_arg(args.get(2), context)?;
+                watch_file(&watches_, WatchKind::Xml, run_first, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let watches_ = watches.clone();
+    unsafe {
+        context.register_global_callable(
+            "watch_dir", 2, NativeFunction::from_closure(move |this, args, context| {
+                let (kind, run_first) = dir_kind(args.get(2), context)?;
+                watch_file(&watches_, kind, run_first, this, args, context)
             })
         ).unwrap();
     }
 }
 
-fn watch_json(
-    watches: &Rc<RefCell<HashMap<PathBuf, JsFunction>>>,
+fn watch_file(
+    watches: &Rc<RefCell<HashMap<PathBuf, Watch>>>,
+    kind: WatchKind,
+    run_first: bool,
     _this: &JsValue, args: &[JsValue], context: &mut Context
     ) -> JsResult<JsValue>
 {
@@ -43,37 +130,274 @@ fn watch_json(
     
     let callback = args[1].try_js_into::<JsFunction>(context)?;
     // todo Keeping the callback outside the JsEnv seems to cause core dump on quit
-    watches.borrow_mut().insert(full_path.clone(), callback.clone());
+    watches.borrow_mut().insert(
+        full_path.clone(), Watch { kind: kind.clone(), callback: callback.clone() }
+    );
     
-    let run_first = match args.get(2) {
-        Some(arg) => arg.try_js_into::<bool>(context)?,
-        None => true,
-    };
+    let load_args = kind.load_args(&full_path, context);
     
     if run_first {
-        let data = JsEnv::load_json(&full_path, context)?;
-        callback.call(&JsValue::Undefined, &[data], context)?;
+        callback.call(&JsValue::Undefined, &load_args, context)?;
+    }
+    
+    let [data, _] = load_args;
+    Ok(data)
+}
+
+fn run_first_arg(arg: Option<&JsValue>, context: &mut Context) -> JsResult<bool> {
+    match arg {
+        Some(arg) if !arg.is_undefined() => arg.try_js_into::<bool>(context),
+        _ => Ok(true),
+    }
+}
+
+/// Reads the `{pattern, runFirst}` options of `watch_dir`, where `pattern` is a
+/// glob or an array of globs.
+fn dir_kind(options: Option<&JsValue>, context: &mut Context) -> JsResult<(WatchKind, bool)> {
+    let mut patterns = vec![];
+    let mut run_first = true;
+    
+    if let Some(options) = options.and_then(|options| options.as_object()) {
+        let pattern = options.get("pattern", context)?;
+        
+        if let Some(object) = pattern.as_object().filter(|object| object.is_array()) {
+            let array = JsArray::from_object(object.clone())?;
+            let length = array.length(context)? as i64;
+            for i in 0..length {
+                patterns.push(array.at(i, context)?.try_js_into::<String>(context)?);
+            }
+        } else if !pattern.is_undefined() {
+            patterns.push(pattern.try_js_into::<String>(context)?);
+        }
+        
+        run_first = run_first_arg(Some(&options.get("runFirst", context)?), context)?;
+    }
+    
+    Ok((WatchKind::Dir { patterns }, run_first))
+}
+
+/// Reads the `{header, delimiter}` options of `watch_csv`.
+fn csv_kind(options: Option<&JsValue>, context: &mut Context) -> JsResult<WatchKind> {
+    let mut header = None;
+    let mut delimiter = b',';
+    
+    if let Some(options) = options.and_then(|options| options.as_object()) {
+        let header_value = options.get("header", context)?;
+        if !header_value.is_undefined() {
+            header = Some(header_value.to_boolean());
+        }
+        
+        let delimiter_value = options.get("delimiter", context)?;
+        if !delimiter_value.is_undefined() {
+            let delimiter_str = delimiter_value.try_js_into::<String>(context)?;
+            delimiter = match delimiter_str.as_bytes() {
+                [byte] => *byte,
+                _ => return Err(JsNativeError::typ()
+                    .with_message("delimiter must be a single character").into()),
+            };
+        }
+    }
+    
+    Ok(WatchKind::Csv { header, delimiter })
+}
+
+fn parse_error(kind: &str, err: impl std::fmt::Display) -> JsError {
+    JsNativeError::syntax().with_message(format!("Invalid {}: {}", kind, err)).into()
+}
+
+fn load_text(path: &Path) -> Result<JsValue, JsError> {
+    let text = read_to_string(path).map_err(|err| parse_error("text file", err))?;
+    Ok(JsValue::from(text))
+}
+
+/// Loads a CSV file as an array of row objects. Without a header row, or when
+/// `header` is `None` and the first row doesn't look like one, the keys are
+/// the column numbers.
+fn load_csv(path: &Path, header: Option<bool>, delimiter: u8, context: &mut Context)
+    -> Result<JsValue, JsError>
+{
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_path(path)
+        .map_err(|err| parse_error("CSV", err))?;
+    
+    let mut rows = reader.records()
+        .map(|record| record.map(|record| {
+            record.iter().map(|field| field.to_owned()).collect::<Vec<_>>()
+        }))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| parse_error("CSV", err))?;
+    
+    let has_header = match header {
+        Some(has_header) => has_header,
+        None => rows.first().map_or(false, |first| looks_like_header(first)),
+    };
+    
+    let keys = if has_header && !rows.is_empty() {
+        rows.remove(0)
+    } else {
+        let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        (0..columns).map(|i| i.to_string()).collect()
+    };
+    
+    let data = rows.into_iter()
+        .map(|row| {
+            let object: Map<String, Value> = keys.iter()
+                .cloned()
+                .zip(row.into_iter().map(Value::String))
+                .collect();
+            Value::Object(object)
+        })
+        .collect();
+    
+    JsValue::from_json(&Value::Array(data), context)
+}
+
+/// A header row has unique, non-empty, non-numeric fields.
+fn looks_like_header(row: &[String]) -> bool {
+    let mut seen = std::collections::HashSet::new();
+    
+    row.iter().all(|field| {
+        let field = field.trim();
+        !field.is_empty() && field.parse::<f64>().is_err() && seen.insert(field)
+    })
+}
+
+/// Loads an XML document, such as an RSS feed, as a tree of
+/// `{name, attributes, text, children}` objects.
+fn load_xml(path: &Path, context: &mut Context) -> Result<JsValue, JsError> {
+    let text = read_to_string(path).map_err(|err| parse_error("XML", err))?;
+    let document = roxmltree::Document::parse(&text).map_err(|err| parse_error("XML", err))?;
+    JsValue::from_json(&xml_to_json(document.root_element()), context)
+}
+
+/// Lists the files in a directory matching any of `patterns`, sorted by name.
+fn load_dir(path: &Path, patterns: &[String], context: &mut Context) -> Result<JsValue, JsError> {
+    let entries = std::fs::read_dir(path).map_err(|err| parse_error("directory", err))?;
+    
+    // Report paths relative to the app so they can be passed to `new Image()`
+    let app_path = context.global_object().get("app_path", context)?
+        .try_js_into::<String>(context)?;
+    let dir_path = path.strip_prefix(&app_path).unwrap_or(path);
+    
+    let mut files: Vec<(String, Value)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|metadata| metadata.is_file())?;
+            let name = entry.file_name().to_str()?.to_owned();
+            
+            if !patterns.is_empty() && !patterns.iter().any(|pattern| glob_match(pattern, &name)) {
+                return None;
+            }
+            
+            let mtime = metadata.modified().ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0., |duration| duration.as_millis() as f64);
+            let extension = entry.path().extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| ext.to_lowercase())
+                .unwrap_or_default();
+            
+            let mut relative_path = dir_path.to_path_buf();
+            relative_path.push(&name);
+            
+            let file = serde_json::json!({
+                "name": name,
+                "path": relative_path,
+                "size": metadata.len(),
+                "mtime": mtime,
+                "extension": extension,
+            });
+            Some((name, file))
+        })
+        .collect();
+    
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    let listing = files.into_iter().map(|(_, file)| file).collect();
+    JsValue::from_json(&Value::Array(listing), context)
+}
+
+/// Matches a file name against a glob with `*` and `?` wildcards, ignoring case.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            },
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            },
+            _ => match backtrack {
+                // Let the last * consume one more character and retry
+                Some((star_p, star_n)) => {
+                    backtrack = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                },
+                None => return false,
+            },
+        }
     }
     
-    JsEnv::load_json(&full_path, context)
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn xml_to_json(node: roxmltree::Node) -> Value {
+    let attributes: Map<String, Value> = node.attributes()
+        .map(|attribute| (attribute.name().to_owned(), Value::String(attribute.value().to_owned())))
+        .collect();
+    
+    let text: String = node.children()
+        .filter(|child| child.is_text())
+        .filter_map(|child| child.text())
+        .collect();
+    
+    let children: Vec<Value> = node.children()
+        .filter(|child| child.is_element())
+        .map(xml_to_json)
+        .collect();
+    
+    serde_json::json!({
+        "name": node.tag_name().name(),
+        "attributes": attributes,
+        "text": text.trim(),
+        "children": children,
+    })
 }
 
 impl JsEnv {
     pub fn handle_file_changes(&mut self) {
         let mut reload_script_env = false;
+        let mut changed_watches = vec![];
         
         for changed_path_buf in iter_unique(self.file_change_rx.try_iter()) {
-            // Check if it's a watched file with a callback
-            if let Some(js_fn) = self.watches.borrow().get(&changed_path_buf) {               
-                match JsEnv::load_json(&changed_path_buf, &mut self.context) {
-                    Ok(data) => {
-                        if let Err(err) = js_fn.call(&JsValue::Undefined, &[data], &mut self.context) {
-                            dbg!(&err);
-                        }
-                    },
-                    Err(err) => {dbg!(&err);},
+            // Check if it's a watched file, or a file in a watched directory
+            let watches = self.watches.borrow();
+            if watches.contains_key(&changed_path_buf) {
+                changed_watches.push(changed_path_buf.clone());
+            }
+            
+            if let Some(parent) = changed_path_buf.parent() {
+                if let Some(Watch { kind: WatchKind::Dir { .. }, .. }) = watches.get(parent) {
+                    changed_watches.push(parent.to_path_buf());
                 }
             }
+            drop(watches);
+            
+            // Removed files can't be reloaded
+            if !changed_path_buf.exists() {
+                continue;
+            }
             
             // If not explicitly watched, do other updates
             let extension = changed_path_buf.extension()
@@ -84,7 +408,7 @@ impl JsEnv {
                 Some(ext) if ext == "js" => {
                     reload_script_env = true;
                 },
-                Some(ext) if ["jpg", "png"].contains(&ext.as_ref()) => {
+                Some(ext) if ["jpg", "jpeg", "png", "apng", "gif", "svg", "svgz"].contains(&ext.as_ref()) => {
                     self.graphics_calls.borrow_mut().push(
                         GraphicsCalls::ImageFileUpdate(changed_path_buf)
                     );
@@ -93,18 +417,44 @@ impl JsEnv {
             }
         }
                
+        // Run each callback once, even if several files in its directory changed
+        for path in iter_unique(changed_watches) {
+            // Clone the watch so the callback can add watches of its own
+            let Some(watch) = self.watches.borrow().get(&path).cloned() else { continue };
+            let args = watch.kind.load_args(&path, &mut self.context);
+            if let Err(err) = watch.callback.call(&JsValue::Undefined, &args, &mut self.context) {
+                dbg!(&err);
+            }
+        }
+               
         if reload_script_env {
             self.try_before_reload();
         }
     }
     
     fn try_before_reload(&mut self) {
-        match JsEnv::create_context(&self.app_path, &self.graphics_calls, &self.watches) {
+        // Timers, fetches, videos and tweens hold callbacks or objects from their context, so the new
+        // context gets its own
+        let timers = Rc::new(RefCell::new(Timers::default()));
+        let fetcher = Rc::new(RefCell::new(Fetcher::new(&self.app_path)));
+        let mut videos = Videos::new(&self.app_path);
+        videos.wait_for_frames = self.videos.borrow().wait_for_frames;
+        let videos = Rc::new(RefCell::new(videos));
+        let tweens = Rc::new(RefCell::new(Tweens::default()));
+        
+        match JsEnv::create_context(
+            &self.app_path, &self.graphics_calls, &self.watches, &self.clock, &timers, &fetcher,
+            &videos, &tweens
+        ) {
             Ok((mut context, module)) => {
                 match JsEnv::call_module_init(&module, &mut context) {
                     Ok(_) => {
                         self.context = context;
                         self.module = module;
+                        self.timers = timers;
+                        self.fetcher = fetcher;
+                        self.videos = videos;
+                        self.tweens = tweens;
                         println!("Reloaded script environment.");
                     },
                     Err(err) => { dbg!(&err); },
@@ -113,4 +463,112 @@ impl JsEnv {
             Err(err) => { dbg!(&err); },
         };        
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|field| field.to_string()).collect()
+    }
+
+    #[test]
+    fn glob_stars_match_any_run_of_characters() {
+        assert!(glob_match("*", "photo.jpg"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.jpg", "photo.jpg"));
+        assert!(glob_match("*.jpg", ".jpg"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(glob_match("**.png", "logo.png"));
+    }
+
+    #[test]
+    fn glob_question_marks_match_one_character() {
+        assert!(glob_match("slide?.png", "slide1.png"));
+        assert!(!glob_match("slide?.png", "slide.png"));
+        assert!(!glob_match("slide?.png", "slide10.png"));
+    }
+
+    #[test]
+    fn glob_ignores_case() {
+        assert!(glob_match("*.JPG", "photo.jpg"));
+        assert!(glob_match("*.jpg", "PHOTO.JPG"));
+    }
+
+    #[test]
+    fn glob_rejects_names_that_dont_match() {
+        assert!(!glob_match("*.jpg", "photo.png"));
+        assert!(!glob_match("*.jpg", "photo.jpg.bak"));
+        assert!(!glob_match("photo", "photos"));
+        assert!(!glob_match("", "photo"));
+    }
+
+    #[test]
+    fn header_rows_have_unique_text_fields() {
+        assert!(looks_like_header(&row(&["name", "price", "stock"])));
+        assert!(!looks_like_header(&row(&["apple", "1.50", "12"])));
+        assert!(!looks_like_header(&row(&["name", "", "stock"])));
+        assert!(!looks_like_header(&row(&["name", "name"])));
+        assert!(!looks_like_header(&row(&["name", " name "])));
+    }
+
+    #[test]
+    fn xml_converts_to_nested_objects() {
+        let text = r#"<rss version="2.0">
+            <channel>
+                <title> News </title>
+                <item><title>First</title></item>
+            </channel>
+        </rss>"#;
+        let document = roxmltree::Document::parse(text).unwrap();
+
+        assert_eq!(xml_to_json(document.root_element()), serde_json::json!({
+            "name": "rss",
+            "attributes": { "version": "2.0" },
+            "text": "",
+            "children": [{
+                "name": "channel",
+                "attributes": {},
+                "text": "",
+                "children": [
+                    { "name": "title", "attributes": {}, "text": "News", "children": [] },
+                    {
+                        "name": "item",
+                        "attributes": {},
+                        "text": "",
+                        "children": [
+                            { "name": "title", "attributes": {}, "text": "First", "children": [] },
+                        ],
+                    },
+                ],
+            }],
+        }));
+    }
+
+    #[test]
+    fn xml_files_load_as_js_objects() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("signrs-files-{}.xml", std::process::id()));
+        std::fs::write(&path, r#"<feed><entry id="1">Hello</entry></feed>"#).unwrap();
+
+        let mut context = Context::default();
+        let feed = load_xml(&path, &mut context);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(feed.unwrap().to_json(&mut context).unwrap(), serde_json::json!({
+            "name": "feed",
+            "attributes": {},
+            "text": "",
+            "children": [
+                { "name": "entry", "attributes": { "id": "1" }, "text": "Hello", "children": [] },
+            ],
+        }));
+    }
+
+    #[test]
+    fn xml_text_joins_the_text_around_children() {
+        let document = roxmltree::Document::parse("<p>Hello <b>big</b> world</p>").unwrap();
+        assert_eq!(xml_to_json(document.root_element())["text"], "Hello  world");
+    }
+}
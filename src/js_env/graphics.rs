@@ -1,11 +1,14 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::str::FromStr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 
-use boa_engine::{Context, JsNativeError, JsResult, NativeFunction, JsError, JsValue};
+use boa_engine::{Context, JsNativeError, JsResult, NativeFunction, JsError, JsValue, Source};
 use boa_engine::class::{Class, ClassBuilder};
+use boa_engine::object::JsObject;
 use boa_engine::object::builtins::{JsFunction, JsArray};
 use boa_engine::property::Attribute;
 use boa_engine::value::TryFromJs;
@@ -16,20 +19,80 @@ use speedy2d::dimen::{Vec2, UVec2};
 use speedy2d::shape::Rectangle;
 use speedy2d::font::{Font, TextOptions, TextLayout, FormattedTextBlock};
 
+use crate::animated_image;
+use crate::blend::BlendMode;
+use crate::color_adjust::ColorAdjust;
+use crate::font_chain::FontChain;
+use crate::gradient::{Fill, Gradient, GradientKind};
+use crate::nine_slice::Insets;
+use crate::shape::Shape;
+use crate::shaping;
+use crate::text_effects::{Glow, Outline, Shadow, TextEffects};
+use crate::text_layout::{
+    self, Align, RichSpan, RichTextLayout, RichTextOptions, TextBoxLayout, TextBoxOptions, VAlign
+};
+use crate::transform::Affine;
+use crate::video::VideoFrame;
+
 #[derive(Clone)]
 pub enum GraphicsCalls {
     ClearScreenBlack,
     ClearScreen(Color),
-    DrawRectangle(Rectangle, Color),
-    DrawText(Vec2, Color, FormattedTextBlock),
-    DrawImage(Vec2, String),
-    DrawRectangleImageTinted(Rectangle, String, Color),
-    PushOffset(Vec2),
-    PopOffset,
+    DrawRectangle(Rectangle, Fill),
+    DrawText(Vec2, Fill, FormattedTextBlock, TextSource),
+    DrawTextEffects(Vec2, TextSource, TextEffects),
+    DrawImage(Vec2, ImageRef),
+    DrawRectangleImageTinted(Rectangle, ImageRef, Color),
+    /// Draws the part of the image inside the first rectangle, in image
+    /// pixels, over the second.
+    DrawImageRegion(Rectangle, Rectangle, ImageRef, Color),
+    DrawImageNineSlice(Rectangle, Insets, ImageRef, Color),
+    /// Draws the image with its colors adjusted, then tinted.
+    DrawImageAdjusted(Rectangle, ImageRef, Color, ColorAdjust),
+    DrawVideo(Rectangle, VideoFrame, Color),
+    FillShape(Shape, Fill),
+    StrokeShape(Shape, f32, Fill),
+    PushTransform(Affine),
+    PopTransform,
+    PushClip(Rectangle),
+    PopClip,
+    /// Draws the calls up to the matching `EndLayer` into the layer with this
+    /// id and size instead of the screen.
+    BeginLayer(u32, UVec2),
+    EndLayer,
+    DrawLayer(Rectangle, u32, Color),
+    /// Blends the calls up to the matching `PopBlendMode` onto what is below
+    /// them as one image.
+    PushBlendMode(BlendMode),
+    PopBlendMode,
     SetResolution(UVec2),
     ImageFileUpdate(PathBuf),
 }
 
+/// An image file relative to the app, and the frame to draw for animated
+/// images. Still images only have frame 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageRef {
+    pub path: String,
+    pub frame: usize,
+}
+
+/// The text, face and scale behind a `DrawText` call, so renderers without
+/// speedy2d's glyph cache can rasterize the same text.
+#[derive(Clone)]
+pub struct TextSource {
+    pub font: Arc<rusttype::Font<'static>>,
+    pub text: String,
+    pub scale: f32,
+}
+
+/// Faces are compared by identity, as a reloaded font is a new face.
+impl PartialEq for TextSource {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.font, &other.font) && self.text == other.text && self.scale == other.scale
+    }
+}
+
 use std::fmt;
 
 impl fmt::Debug for GraphicsCalls {
@@ -39,17 +102,75 @@ impl fmt::Debug for GraphicsCalls {
             ClearScreenBlack => write!(f, "ClearScreenBlack"),
             ClearScreen(_) => write!(f, "ClearScreenColor"),
             DrawRectangle(_, _) => write!(f, "DrawRectangle"),
-            DrawText(_, _, _) => write!(f, "DrawText"),
+            DrawText(_, _, _, _) => write!(f, "DrawText"),
+            DrawTextEffects(_, _, _) => write!(f, "DrawTextEffects"),
             DrawImage(_, _) => write!(f, "DrawImage"),
             DrawRectangleImageTinted(_, _, _) => write!(f, "DrawRectangleImageTinted"),
-            PushOffset(_) => write!(f, "PushOffset"),
-            PopOffset => write!(f, "PopOffset"),
+            DrawImageRegion(_, _, _, _) => write!(f, "DrawImageRegion"),
+            DrawImageNineSlice(_, _, _, _) => write!(f, "DrawImageNineSlice"),
+            DrawImageAdjusted(_, _, _, _) => write!(f, "DrawImageAdjusted"),
+            DrawVideo(_, _, _) => write!(f, "DrawVideo"),
+            FillShape(_, _) => write!(f, "FillShape"),
+            StrokeShape(_, _, _) => write!(f, "StrokeShape"),
+            PushTransform(_) => write!(f, "PushTransform"),
+            PopTransform => write!(f, "PopTransform"),
+            PushClip(_) => write!(f, "PushClip"),
+            PopClip => write!(f, "PopClip"),
+            BeginLayer(_, _) => write!(f, "BeginLayer"),
+            EndLayer => write!(f, "EndLayer"),
+            DrawLayer(_, _, _) => write!(f, "DrawLayer"),
+            PushBlendMode(_) => write!(f, "PushBlendMode"),
+            PopBlendMode => write!(f, "PopBlendMode"),
             SetResolution(_) => write!(f, "SetResolution"),
             ImageFileUpdate(_) => write!(f, "ImageFileUpdate"),
         }
     }
 }
 
+/// Calls are equal when they draw the same thing, so renderers can reuse what
+/// an unchanged list of calls drew. Text is compared by its source, which the
+/// laid out block is made from.
+impl PartialEq for GraphicsCalls {
+    fn eq(&self, other: &Self) -> bool {
+        use GraphicsCalls::*;
+        match (self, other) {
+            (ClearScreenBlack, ClearScreenBlack) => true,
+            (ClearScreen(a), ClearScreen(b)) => a == b,
+            (DrawRectangle(r1, f1), DrawRectangle(r2, f2)) => (r1, f1) == (r2, f2),
+            (DrawText(p1, f1, _, s1), DrawText(p2, f2, _, s2)) => (p1, f1, s1) == (p2, f2, s2),
+            (DrawTextEffects(p1, s1, e1), DrawTextEffects(p2, s2, e2)) => (p1, s1, e1) == (p2, s2, e2),
+            (DrawImage(p1, i1), DrawImage(p2, i2)) => (p1, i1) == (p2, i2),
+            (DrawRectangleImageTinted(r1, i1, c1), DrawRectangleImageTinted(r2, i2, c2)) => {
+                (r1, i1, c1) == (r2, i2, c2)
+            },
+            (DrawImageRegion(s1, r1, i1, c1), DrawImageRegion(s2, r2, i2, c2)) => {
+                (s1, r1, i1, c1) == (s2, r2, i2, c2)
+            },
+            (DrawImageNineSlice(r1, n1, i1, c1), DrawImageNineSlice(r2, n2, i2, c2)) => {
+                (r1, n1, i1, c1) == (r2, n2, i2, c2)
+            },
+            (DrawImageAdjusted(r1, i1, c1, a1), DrawImageAdjusted(r2, i2, c2, a2)) => {
+                (r1, i1, c1, a1) == (r2, i2, c2, a2)
+            },
+            (DrawVideo(r1, v1, c1), DrawVideo(r2, v2, c2)) => (r1, v1, c1) == (r2, v2, c2),
+            (FillShape(s1, f1), FillShape(s2, f2)) => (s1, f1) == (s2, f2),
+            (StrokeShape(s1, t1, f1), StrokeShape(s2, t2, f2)) => (s1, t1, f1) == (s2, t2, f2),
+            (PushTransform(a), PushTransform(b)) => a == b,
+            (PopTransform, PopTransform) => true,
+            (PushClip(a), PushClip(b)) => a == b,
+            (PopClip, PopClip) => true,
+            (BeginLayer(i1, s1), BeginLayer(i2, s2)) => (i1, s1) == (i2, s2),
+            (EndLayer, EndLayer) => true,
+            (DrawLayer(r1, i1, c1), DrawLayer(r2, i2, c2)) => (r1, i1, c1) == (r2, i2, c2),
+            (PushBlendMode(a), PushBlendMode(b)) => a == b,
+            (PopBlendMode, PopBlendMode) => true,
+            (SetResolution(a), SetResolution(b)) => a == b,
+            (ImageFileUpdate(a), ImageFileUpdate(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Trace, Finalize, TryFromJs, Clone)]
 struct JsColor {
     r: f64,
@@ -93,30 +214,161 @@ impl From<JsColor> for Color {
     }
 }
 
+/// Where to draw a piece of laid out text.
+type TextPlacement = (Vec2, FormattedTextBlock, TextSource);
+
+/// Pushes the text's effects before any of the text, so no piece's shadow or
+/// outline covers another piece.
+fn push_text(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    placements: Vec<TextPlacement>, fill: &Fill, effects: &TextEffects
+) {
+    let mut graphics_calls = graphics_calls.borrow_mut();
+    
+    if !effects.is_empty() {
+        for (pos, _, source) in placements.iter() {
+            graphics_calls.push(GraphicsCalls::DrawTextEffects(*pos, source.clone(), effects.clone()));
+        }
+    }
+    
+    for (pos, block, source) in placements {
+        graphics_calls.push(GraphicsCalls::DrawText(pos, fill.clone(), block, source));
+    }
+}
+
+#[derive(Debug, Trace, Finalize, Clone)]
+struct JsGradient {
+    #[unsafe_ignore_trace]
+    gradient: Gradient,
+}
+
+impl Class for JsGradient {
+    const NAME: &'static str = "Gradient";
+    const LENGTH: usize = 6;
+    
+    fn constructor(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<Self> {
+        let kind_name = args.get(0).cloned().unwrap_or_default().try_js_into::<String>(context)?;
+        
+        let coordinate_count = match kind_name.as_str() {
+            "linear" => 4,
+            "radial" => 3,
+            _ => return Err(
+                JsNativeError::typ().with_message("Gradient type must be \"linear\" or \"radial\"").into()
+            ),
+        };
+        
+        if args.len() < coordinate_count + 2 {
+            return Err(JsNativeError::typ().with_message("Too few arguments for Gradient").into());
+        }
+        
+        let mut coordinates = vec![];
+        for arg in args[1..=coordinate_count].iter() {
+            coordinates.push(arg.try_js_into::<f64>(context)? as f32);
+        }
+        
+        let kind = match coordinates[..] {
+            [x1, y1, x2, y2] => GradientKind::Linear { start: (x1, y1).into(), end: (x2, y2).into() },
+            [x, y, radius] => GradientKind::Radial { center: (x, y).into(), radius },
+            _ => unreachable!(),
+        };
+        
+        let stops = stops_arg(&args[coordinate_count + 1], context)?;
+        Ok(JsGradient { gradient: Gradient::new(kind, stops) })
+    }
+
+    fn init(_class: &mut ClassBuilder) -> JsResult<()> {
+        Ok(())
+    }
+}
+
+/// Reads stops given as `[[offset, color], ...]`, or as colors spaced evenly
+/// from 0 to 1.
+fn stops_arg(arg: &JsValue, context: &mut Context) -> JsResult<Vec<(f32, Color)>> {
+    let array = arg.try_js_into::<JsArray>(context)?;
+    let length = array.length(context)? as i64;
+    let mut stops = vec![];
+    
+    for i in 0..length {
+        let item = array.at(i, context)?;
+        let object = item.as_object()
+            .ok_or(JsNativeError::typ().with_message("Expected a Color or [offset, Color]"))?;
+        
+        let stop = if let Some(color) = object.downcast_ref::<JsColor>() {
+            let offset = if length > 1 { i as f32 / (length - 1) as f32 } else { 0. };
+            (offset, color.clone().into())
+        } else {
+            let pair = JsArray::from_object(object.clone())?;
+            let offset = pair.at(0, context)?.try_js_into::<f64>(context)? as f32;
+            let color = pair.at(1, context)?;
+            let color = color.as_object()
+                .and_then(|color| color.downcast_ref::<JsColor>().map(|color| color.clone()))
+                .ok_or(JsNativeError::typ().with_message("Expected a Color"))?;
+            (offset, color.into())
+        };
+        
+        stops.push(stop);
+    }
+    
+    Ok(stops)
+}
+
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 struct BlockCacheKey {
     text: String,
+    face: usize,
     scale: i32,
 }
 
 impl BlockCacheKey {
-    fn new(text: &str, scale: f32) -> Self {
+    fn new(text: &str, face: usize, scale: f32) -> Self {
         BlockCacheKey {
             text: text.to_owned(),
+            face,
             scale: (scale * 100.) as i32,
         }
     }
 }
 
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+struct TextBoxCacheKey {
+    text: String,
+    width: i32,
+    height: i32,
+    size: i32,
+    align: Align,
+    valign: VAlign,
+    line_height: i32,
+    ellipsis: Option<String>,
+    auto_shrink: bool,
+}
+
+impl TextBoxCacheKey {
+    fn new(text: &str, width: f32, height: f32, options: &TextBoxOptions) -> Self {
+        TextBoxCacheKey {
+            text: text.to_owned(),
+            width: (width * 100.) as i32,
+            height: (height * 100.) as i32,
+            size: (options.size * 100.) as i32,
+            align: options.align,
+            valign: options.valign,
+            line_height: (options.line_height * 100.) as i32,
+            ellipsis: options.ellipsis.clone(),
+            auto_shrink: options.auto_shrink,
+        }
+    }
+}
+
 #[derive(Clone)]
 struct FormattedTextBlockCache {
     cache: HashMap<BlockCacheKey, FormattedTextBlock>,
+    boxes: HashMap<TextBoxCacheKey, TextBoxLayout>,
 }
 
 impl FormattedTextBlockCache {
     fn new() -> Self {
         Self {
             cache: HashMap::new(),
+            boxes: HashMap::new(),
         }
     }
     
@@ -124,25 +376,38 @@ impl FormattedTextBlockCache {
         self.cache.len()
     }
     
-    fn get(&mut self, text: &str, font: &Font, scale: f32) -> FormattedTextBlock {
+    fn get(&mut self, text: &str, faces: &[Font], face: usize, scale: f32) -> FormattedTextBlock {
         // todo Cold cache items should be pruned eventually
-        let key = BlockCacheKey::new(text, scale);
+        let key = BlockCacheKey::new(text, face, scale);
         
         match self.cache.get(&key) {
             Some(block) => block.clone(),
             None => {
-                let block = font.layout_text(text, scale, TextOptions::new());
+                let block = faces[face].layout_text(text, scale, TextOptions::new());
                 self.cache.insert(key, block.clone());
                 block
             }
         }
     }
+    
+    fn get_box(
+        &mut self, text: &str, font: &FontChain, width: f32, height: f32, options: &TextBoxOptions
+    ) -> TextBoxLayout {
+        let key = TextBoxCacheKey::new(text, width, height, options);
+        
+        self.boxes.entry(key)
+            .or_insert_with(|| text_layout::layout_text_box(font, text, width, height, options))
+            .clone()
+    }
 }
 
 #[derive(Trace, Finalize, Clone)]
 struct JsFont {
+    // speedy2d faces in the same order as the chain's faces
     #[unsafe_ignore_trace]
-    font: Font,
+    faces: Vec<Font>,
+    #[unsafe_ignore_trace]
+    chain: FontChain,
     #[unsafe_ignore_trace]
     cache: FormattedTextBlockCache,
     test: i32,
@@ -154,18 +419,45 @@ impl Class for JsFont {
     const LENGTH: usize = 1;
 
     fn constructor(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<Self> {
-        let font_path = args[0].try_js_into::<String>(context)?;
-        let mut full_path = PathBuf::from_str(
+        // A single path, or a list of paths where later faces are fallbacks
+        let font_paths = match args.get(0).and_then(|arg| arg.as_object()) {
+            Some(object) if object.is_array() => {
+                let array = JsArray::from_object(object.clone())?;
+                let length = array.length(context)? as i64;
+                let mut font_paths = vec![];
+                for i in 0..length {
+                    font_paths.push(array.at(i, context)?.try_js_into::<String>(context)?);
+                }
+                font_paths
+            },
+            _ => vec![args[0].try_js_into::<String>(context)?],
+        };
+        
+        if font_paths.is_empty() {
+            return Err(JsNativeError::typ().with_message("Expected at least one font path").into());
+        }
+        
+        let app_path = PathBuf::from_str(
             &context.global_object().get("app_path", context).unwrap().try_js_into::<String>(context).unwrap()
         ).unwrap();
         
-        full_path.push(font_path.clone());
+        let mut faces = vec![];
+        let mut raster_faces = vec![];
         
-        let bytes = std::fs::read(full_path).unwrap();
-        let font = Font::new(&bytes).unwrap();
+        for font_path in font_paths.iter() {
+            let mut full_path = app_path.clone();
+            full_path.push(font_path);
+            
+            let load_error = || JsNativeError::typ().with_message(format!("Could not load font {}", font_path));
+            let bytes = std::fs::read(full_path).map_err(|_| load_error())?;
+            faces.push(Font::new(&bytes).map_err(|_| load_error())?);
+            raster_faces.push(Arc::new(rusttype::Font::try_from_vec(bytes).ok_or_else(load_error)?));
+        }
+        
+        let chain = FontChain::new(raster_faces);
         let cache = FormattedTextBlockCache::new();        
         
-        Ok(JsFont{font, cache, test: 10, path: font_path})
+        Ok(JsFont{faces, chain, cache, test: 10, path: font_paths[0].clone()})
     }
     
     fn init(class: &mut ClassBuilder) -> JsResult<()> {
@@ -175,11 +467,67 @@ impl Class for JsFont {
 }
 
 impl JsFont {   
-    fn layout_text(&mut self, text: &str, scale: f32) -> FormattedTextBlock {
-        self.cache.get(text, &self.font, scale)
+    fn layout_text(&mut self, text: &str, face: usize, scale: f32) -> FormattedTextBlock {
+        self.cache.get(text, &self.faces, face, scale)
 
     }
     
+    fn layout_text_box(
+        &mut self, text: &str, width: f32, height: f32, options: &TextBoxOptions
+    ) -> TextBoxLayout {
+        self.cache.get_box(text, &self.chain, width, height, options)
+    }
+    
+    fn text_source(&self, text: &str, face: usize, scale: f32) -> TextSource {
+        TextSource {
+            font: self.chain.face(face).clone(),
+            text: text.to_owned(),
+            scale,
+        }
+    }
+    
+    /// Lays out text at `pos` for drawing, after shaping and reordering
+    /// right-to-left text. Text the primary face can draw is a single
+    /// placement, and otherwise each run of a fallback face is placed after
+    /// the last on the primary face's baseline.
+    fn place_text(&mut self, text: &str, pos: Vec2, scale: f32) -> Vec<TextPlacement> {
+        let text = &*shaping::visual_text(text);
+        
+        if !self.chain.needs_fallback(text) {
+            let block = self.layout_text(text, 0, scale);
+            let source = self.text_source(text, 0, scale);
+            return vec![(pos, block, source)];
+        }
+        
+        let line_height = self.chain.line_height(scale);
+        let mut placements = vec![];
+        
+        for (i, line) in text.lines().enumerate() {
+            let mut x = 0.;
+            
+            for (face, run) in self.chain.runs(line) {
+                let offset = Vec2::new(x, i as f32 * line_height + self.chain.baseline_shift(face, scale));
+                let block = self.layout_text(run, face, scale);
+                let source = self.text_source(run, face, scale);
+                placements.push((pos + offset, block, source));
+                x += self.chain.measure_face(face, run, scale);
+            }
+        }
+        
+        placements
+    }
+    
+    fn size_text(&mut self, text: &str, scale: f32) -> Vec2 {
+        let text = &*shaping::visual_text(text);
+        
+        if !self.chain.needs_fallback(text) {
+            return self.layout_text(text, 0, scale).size();
+        }
+        
+        let width = text.lines().map(|line| self.chain.measure(line, scale)).fold(0., f32::max);
+        Vec2::new(width, text.lines().count() as f32 * self.chain.line_height(scale))
+    }
+    
     fn cache_length(this: &JsValue, _: &[JsValue], _: &mut Context<'_>) -> JsResult<JsValue> {
         if let Some(object) = this.as_object() {
             if let Some(js_font) = object.downcast_ref::<JsFont>() {
@@ -190,9 +538,149 @@ impl JsFont {
     }
 }
 
+/// Adds a `frame` property to images on top of the native methods.
+const IMAGE_SHIM: &str = r###"
+    Object.defineProperty(Image.prototype, "frame", {
+        get() { return this.getFrame(); },
+        set(frame) { this.setFrame(frame); },
+    });
+"###;
+
+/// Playback of an animated image, timed by the app's clock so it follows
+/// `--time-scale` and is deterministic in headless renders.
+#[derive(Debug, Clone)]
+struct ImageAnimation {
+    delays_ms: Vec<f64>,
+    playing: bool,
+    /// While playing, the clock time playback started from. While paused,
+    /// the position in the animation.
+    origin_ms: f64,
+}
+
+impl ImageAnimation {
+    fn position_ms(&self, now_ms: f64) -> f64 {
+        let total_ms: f64 = self.delays_ms.iter().sum();
+        let position = if self.playing { now_ms - self.origin_ms } else { self.origin_ms };
+        position.rem_euclid(total_ms)
+    }
+
+    fn frame_at(&self, now_ms: f64) -> usize {
+        let mut remaining = self.position_ms(now_ms);
+        for (frame, delay) in self.delays_ms.iter().enumerate() {
+            if remaining < *delay {
+                return frame;
+            }
+            remaining -= delay;
+        }
+        self.delays_ms.len() - 1
+    }
+
+    fn play(&mut self, now_ms: f64) {
+        if !self.playing {
+            self.origin_ms = now_ms - self.origin_ms;
+            self.playing = true;
+        }
+    }
+
+    fn pause(&mut self, now_ms: f64) {
+        if self.playing {
+            self.origin_ms = now_ms - self.origin_ms;
+            self.playing = false;
+        }
+    }
+
+    /// Moves to the start of `frame`, keeping playback running if it was.
+    fn set_frame(&mut self, frame: usize, now_ms: f64) {
+        let start: f64 = self.delays_ms.iter().take(frame).sum();
+        self.origin_ms = if self.playing { now_ms - start } else { start };
+    }
+}
+
 #[derive(Debug, Trace, Finalize, Clone)]
 struct JsImage {
     path: PathBuf,
+    #[unsafe_ignore_trace]
+    animation: Option<ImageAnimation>,
+}
+
+impl JsImage {
+    /// `path` is relative to the app.
+    fn load(path: PathBuf, context: &mut Context) -> JsResult<Self> {
+        // Animated images start playing as soon as they are loaded
+        let animation = match app_file_path(&path, context)? {
+            Some(full_path) => match animated_image::load_delays(&full_path) {
+                Some(delays_ms) => Some(ImageAnimation {
+                    delays_ms,
+                    playing: true,
+                    origin_ms: now_ms(context)?,
+                }),
+                None => None,
+            },
+            None => None,
+        };
+
+        Ok(Self{path, animation})
+    }
+
+    fn image_ref(&self, context: &mut Context) -> JsResult<ImageRef> {
+        let frame = match &self.animation {
+            Some(animation) => animation.frame_at(now_ms(context)?),
+            None => 0,
+        };
+
+        Ok(ImageRef { path: self.path.to_str().unwrap().to_owned(), frame })
+    }
+
+    /// Runs `f` on the animation of an animated image, returning its result
+    /// or `still` for still images.
+    fn with_animation<T>(
+        this: &JsValue, context: &mut Context, still: T,
+        f: impl FnOnce(&mut ImageAnimation, f64) -> T
+    ) -> JsResult<T> {
+        let now_ms = now_ms(context)?;
+        let object = this.as_object()
+            .ok_or(JsNativeError::typ().with_message("'this' is not an Image object"))?;
+        let mut js_image = object.downcast_mut::<JsImage>()
+            .ok_or(JsNativeError::typ().with_message("'this' is not an Image object"))?;
+
+        Ok(match js_image.animation.as_mut() {
+            Some(animation) => f(animation, now_ms),
+            None => still,
+        })
+    }
+
+    fn play(this: &JsValue, _: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
+        Self::with_animation(this, context, (), |animation, now_ms| animation.play(now_ms))?;
+        Ok(JsValue::Undefined)
+    }
+
+    fn pause(this: &JsValue, _: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
+        Self::with_animation(this, context, (), |animation, now_ms| animation.pause(now_ms))?;
+        Ok(JsValue::Undefined)
+    }
+
+    fn get_frame(this: &JsValue, _: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
+        let frame = Self::with_animation(this, context, 0, |animation, now_ms| {
+            animation.frame_at(now_ms)
+        })?;
+        Ok(JsValue::Integer(frame as i32))
+    }
+
+    fn set_frame(this: &JsValue, args: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
+        let frame = args.get(0).cloned().unwrap_or_default().to_number(context)?;
+        let frame = if frame.is_finite() { frame.max(0.) as usize } else { 0 };
+
+        Self::with_animation(this, context, (), |animation, now_ms| {
+            let frame = frame.min(animation.delays_ms.len() - 1);
+            animation.set_frame(frame, now_ms)
+        })?;
+        Ok(JsValue::Undefined)
+    }
+
+    fn frame_count(this: &JsValue, _: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
+        let count = Self::with_animation(this, context, 1, |animation, _| animation.delays_ms.len())?;
+        Ok(JsValue::Integer(count as i32))
+    }
 }
 
 impl Class for JsImage {
@@ -202,14 +690,205 @@ impl Class for JsImage {
     fn constructor(_this: &JsValue, args: &[JsValue], context: &mut Context<'_>) -> JsResult<Self> {
         let path = args[0].try_js_into::<String>(context)?;
         let path = PathBuf::from_str(&path).unwrap();
-        Ok(Self{path})
+        JsImage::load(path, context)
     }
     
-    fn init(_class: &mut ClassBuilder<'_, '_>) -> JsResult<()> {
+    fn init(class: &mut ClassBuilder<'_, '_>) -> JsResult<()> {
+        class.method("play", 0, NativeFunction::from_fn_ptr(Self::play));
+        class.method("pause", 0, NativeFunction::from_fn_ptr(Self::pause));
+        class.method("getFrame", 0, NativeFunction::from_fn_ptr(Self::get_frame));
+        class.method("setFrame", 1, NativeFunction::from_fn_ptr(Self::set_frame));
+        class.method("frameCount", 0, NativeFunction::from_fn_ptr(Self::frame_count));
+        Ok(())
+    }
+}
+
+/// A path relative to the app, or `None` in contexts without an app.
+fn app_file_path(path: &Path, context: &mut Context) -> JsResult<Option<PathBuf>> {
+    let app_path = context.global_object().get("app_path", context)?;
+    Ok(app_path.as_string().map(|app_path| {
+        let mut full_path = PathBuf::from(app_path.to_std_string_escaped());
+        full_path.push(path);
+        full_path
+    }))
+}
+
+/// Named regions of one image, loaded from a TexturePacker-style JSON file
+/// with a `frames` hash or array and `meta.image`.
+#[derive(Debug, Trace, Finalize, Clone)]
+struct JsAtlas {
+    image: JsImage,
+    #[unsafe_ignore_trace]
+    sprites: HashMap<String, Rectangle>,
+}
+
+impl JsAtlas {
+    fn sprites(json: &serde_json::Value) -> HashMap<String, Rectangle> {
+        let region = |frame: &serde_json::Value| {
+            let value = |name: &str| frame["frame"][name].as_f64().map(|value| value as f32);
+            let (x, y) = (value("x")?, value("y")?);
+            Some(Rectangle::new(Vec2::new(x, y), Vec2::new(x + value("w")?, y + value("h")?)))
+        };
+
+        match &json["frames"] {
+            serde_json::Value::Object(frames) => frames.iter()
+                .filter_map(|(name, frame)| Some((name.clone(), region(frame)?)))
+                .collect(),
+            serde_json::Value::Array(frames) => frames.iter()
+                .filter_map(|frame| Some((frame["filename"].as_str()?.to_owned(), region(frame)?)))
+                .collect(),
+            _ => HashMap::new(),
+        }
+    }
+
+    fn names(this: &JsValue, _: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
+        let object = this.as_object()
+            .ok_or(JsNativeError::typ().with_message("'this' is not an Atlas object"))?;
+        let atlas = object.downcast_ref::<JsAtlas>()
+            .ok_or(JsNativeError::typ().with_message("'this' is not an Atlas object"))?;
+
+        let mut names: Vec<&String> = atlas.sprites.keys().collect();
+        names.sort();
+
+        let array = JsArray::new(context);
+        for name in names {
+            array.push(JsValue::from(name.as_str()), context)?;
+        }
+        Ok(array.into())
+    }
+
+    fn region(this: &JsValue, args: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
+        let name = args.get(0).cloned().unwrap_or_default().try_js_into::<String>(context)?;
+        let object = this.as_object()
+            .ok_or(JsNativeError::typ().with_message("'this' is not an Atlas object"))?;
+        let Some(region) = object.downcast_ref::<JsAtlas>()
+            .ok_or(JsNativeError::typ().with_message("'this' is not an Atlas object"))?
+            .sprites.get(&name).cloned() else {
+            return Ok(JsValue::Undefined);
+        };
+
+        let array = JsArray::new(context);
+        array.push(region.top_left().x, context)?;
+        array.push(region.top_left().y, context)?;
+        array.push(region.width(), context)?;
+        array.push(region.height(), context)?;
+        Ok(array.into())
+    }
+}
+
+impl Class for JsAtlas {
+    const NAME: &'static str = "Atlas";
+    const LENGTH: usize = 1;
+
+    fn constructor(_this: &JsValue, args: &[JsValue], context: &mut Context<'_>) -> JsResult<Self> {
+        let json_path = args[0].try_js_into::<String>(context)?;
+        let json_path = PathBuf::from_str(&json_path).unwrap();
+        let load_error = |message: String| {
+            JsNativeError::typ().with_message(format!("Could not load atlas {}: {}", json_path.display(), message))
+        };
+
+        let full_path = app_file_path(&json_path, context)?
+            .ok_or_else(|| load_error("no app path".to_owned()))?;
+        let text = std::fs::read_to_string(&full_path).map_err(|err| load_error(err.to_string()))?;
+        let json: serde_json::Value = serde_json::from_str(&text).map_err(|err| load_error(err.to_string()))?;
+
+        // The image is next to the JSON file unless given
+        let image_path = match args.get(1) {
+            Some(arg) if !arg.is_undefined() => PathBuf::from_str(&arg.try_js_into::<String>(context)?).unwrap(),
+            _ => {
+                let image = json["meta"]["image"].as_str()
+                    .ok_or_else(|| load_error("no meta.image".to_owned()))?;
+                json_path.parent().unwrap_or(Path::new("")).join(image)
+            },
+        };
+
+        Ok(JsAtlas {
+            image: JsImage::load(image_path, context)?,
+            sprites: JsAtlas::sprites(&json),
+        })
+    }
+
+    fn init(class: &mut ClassBuilder<'_, '_>) -> JsResult<()> {
+        class.method("names", 0, NativeFunction::from_fn_ptr(Self::names));
+        class.method("region", 1, NativeFunction::from_fn_ptr(Self::region));
+        Ok(())
+    }
+}
+
+// Layer ids are unique across script reloads, so a new layer never shows a
+// texture left from an old one
+static NEXT_LAYER_ID: AtomicU32 = AtomicU32::new(0);
+
+/// An offscreen image that `with_layer` draws into and `draw_layer` draws.
+#[derive(Debug, Trace, Finalize, Clone)]
+struct JsLayer {
+    id: u32,
+    #[unsafe_ignore_trace]
+    size: UVec2,
+}
+
+impl JsLayer {
+    fn get_width(this: &JsValue, _: &[JsValue], _context: &mut Context<'_>) -> JsResult<JsValue> {
+        Ok(JsValue::Integer(layer_arg(this)?.size.x as i32))
+    }
+
+    fn get_height(this: &JsValue, _: &[JsValue], _context: &mut Context<'_>) -> JsResult<JsValue> {
+        Ok(JsValue::Integer(layer_arg(this)?.size.y as i32))
+    }
+}
+
+impl Class for JsLayer {
+    const NAME: &'static str = "Layer";
+    const LENGTH: usize = 2;
+
+    fn constructor(_this: &JsValue, args: &[JsValue], context: &mut Context<'_>) -> JsResult<Self> {
+        if args.len() < 2 {
+            return Err(JsNativeError::typ().with_message("Too few arguments for Layer").into());
+        }
+
+        let width = args[0].try_js_into::<f64>(context)?;
+        let height = args[1].try_js_into::<f64>(context)?;
+        if !(width >= 1. && height >= 1.) {
+            return Err(JsNativeError::range().with_message("Layer size must be at least 1x1").into());
+        }
+
+        Ok(JsLayer {
+            id: NEXT_LAYER_ID.fetch_add(1, Ordering::Relaxed),
+            size: UVec2::new(width.round() as u32, height.round() as u32),
+        })
+    }
+
+    fn init(class: &mut ClassBuilder<'_, '_>) -> JsResult<()> {
+        class.method("getWidth", 0, NativeFunction::from_fn_ptr(Self::get_width));
+        class.method("getHeight", 0, NativeFunction::from_fn_ptr(Self::get_height));
         Ok(())
     }
 }
 
+const LAYER_SHIM: &str = r###"
+    Object.defineProperty(Layer.prototype, "width", { get() { return this.getWidth(); } });
+    Object.defineProperty(Layer.prototype, "height", { get() { return this.getHeight(); } });
+"###;
+
+fn layer_arg(value: &JsValue) -> JsResult<JsLayer> {
+    Ok(value.as_object()
+        .ok_or(JsNativeError::typ().with_message("Expected a Layer"))?
+        .downcast_ref::<JsLayer>()
+        .ok_or(JsNativeError::typ().with_message("Expected a Layer"))?
+        .clone())
+}
+
+/// The app clock's current time, as `Date.now()` reports it.
+fn now_ms(context: &mut Context) -> JsResult<f64> {
+    let date = context.global_object().get("Date", context)?;
+    let date = date.as_object()
+        .ok_or(JsNativeError::typ().with_message("Date is not an object"))?;
+    let now = date.get("now", context)?;
+    let now = now.as_callable()
+        .ok_or(JsNativeError::typ().with_message("Date.now is not a function"))?;
+    now.call(&JsValue::Object(date.clone()), &[], context)?.to_number(context)
+}
+
 pub fn register_fns_and_types(
     context: &mut Context,
     graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>
@@ -219,8 +898,13 @@ pub fn register_fns_and_types(
         .expect("Unable to create console object");
     
     context.register_global_class::<JsColor>().expect("Could not register JsColor");
+    context.register_global_class::<JsGradient>().expect("Could not register JsGradient");
     context.register_global_class::<JsFont>().expect("Could not register JsFont");
     context.register_global_class::<JsImage>().expect("Could not register Image");
+    context.register_global_class::<JsAtlas>().expect("Could not register Atlas");
+    context.eval(Source::from_bytes(IMAGE_SHIM)).expect("Could not define Image.frame");
+    context.register_global_class::<JsLayer>().expect("Could not register Layer");
+    context.eval(Source::from_bytes(LAYER_SHIM)).expect("Could not define Layer size");
     
     let graphics_calls_ = graphics_calls.clone();
     unsafe {
@@ -249,26 +933,29 @@ pub fn register_fns_and_types(
         ).unwrap();
     }
 
-    context.register_global_callable(
-        "size_text", 1, NativeFunction::from_copy_closure(move |this, args, context| {
-            size_text(this, args, context)
-        })
-    ).unwrap();
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "stroke_rectangle", 1, NativeFunction::from_closure(move |this, args, context| {
+                stroke_rectangle(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
     
     let graphics_calls_ = graphics_calls.clone();
     unsafe {
         context.register_global_callable(
-            "draw_image", 1, NativeFunction::from_closure(move |this, args, context| {
-                draw_image(&graphics_calls_, this, args, context)
+            "draw_line", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_line(&graphics_calls_, this, args, context)
             })
         ).unwrap();
     }
-
+    
     let graphics_calls_ = graphics_calls.clone();
     unsafe {
         context.register_global_callable(
-            "with_offset", 1, NativeFunction::from_closure(move |this, args, context| {
-                with_offset(&graphics_calls_, this, args, context)
+            "draw_circle", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_circle(&graphics_calls_, false, this, args, context)
             })
         ).unwrap();
     }
@@ -276,37 +963,238 @@ pub fn register_fns_and_types(
     let graphics_calls_ = graphics_calls.clone();
     unsafe {
         context.register_global_callable(
-            "set_resolution", 1, NativeFunction::from_closure(move |this, args, context| {
-                set_resolution(&graphics_calls_, this, args, context)
+            "stroke_circle", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_circle(&graphics_calls_, true, this, args, context)
             })
         ).unwrap();
     }
-}
-
-fn clear_screen(
-    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
-    _this: &JsValue, args: &[JsValue], _context: &mut Context
-    ) -> JsResult<JsValue>
-{
-    if args.is_empty() {
-        graphics_calls.borrow_mut().push(GraphicsCalls::ClearScreenBlack);
-    } else {
-        let c = args[0].as_object()
-            .ok_or(JsNativeError::typ().with_message("Expected a Color"))?
-            .downcast_ref::<JsColor>()
-            .ok_or(JsNativeError::typ().with_message("Expected a Color"))?
-            .clone();
-
-        graphics_calls.borrow_mut().push(GraphicsCalls::ClearScreen(c.into()));        
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "draw_ellipse", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_ellipse(&graphics_calls_, false, this, args, context)
+            })
+        ).unwrap();
     }
     
-    Ok(JsValue::Undefined)    
-}
-
-fn draw_rectangle(
-    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
-    _this: &JsValue, args: &[JsValue], context: &mut Context
-    ) -> JsResult<JsValue>
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "stroke_ellipse", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_ellipse(&graphics_calls_, true, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "draw_polygon", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_polygon(&graphics_calls_, false, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "stroke_polygon", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_polygon(&graphics_calls_, true, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "draw_rounded_rectangle", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_rounded_rectangle(&graphics_calls_, false, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "stroke_rounded_rectangle", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_rounded_rectangle(&graphics_calls_, true, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "draw_text_box", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_text_box(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    context.register_global_callable(
+        "size_text", 1, NativeFunction::from_copy_closure(move |this, args, context| {
+            size_text(this, args, context)
+        })
+    ).unwrap();
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "draw_rich_text", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_rich_text(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    context.register_global_callable(
+        "size_rich_text", 1, NativeFunction::from_copy_closure(move |this, args, context| {
+            size_rich_text(this, args, context)
+        })
+    ).unwrap();
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "draw_image", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_image(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "draw_image_region", 9, NativeFunction::from_closure(move |this, args, context| {
+                draw_image_region(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "draw_image_nine_slice", 6, NativeFunction::from_closure(move |this, args, context| {
+                draw_image_nine_slice(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "draw_sprite", 4, NativeFunction::from_closure(move |this, args, context| {
+                draw_sprite(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "with_offset", 1, NativeFunction::from_closure(move |this, args, context| {
+                with_offset(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "with_transform", 1, NativeFunction::from_closure(move |this, args, context| {
+                with_transform(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "with_rotation", 1, NativeFunction::from_closure(move |this, args, context| {
+                with_rotation(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "with_scale", 1, NativeFunction::from_closure(move |this, args, context| {
+                with_scale(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "with_clip", 1, NativeFunction::from_closure(move |this, args, context| {
+                with_clip(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "with_layer", 2, NativeFunction::from_closure(move |this, args, context| {
+                with_layer(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "draw_layer", 3, NativeFunction::from_closure(move |this, args, context| {
+                draw_layer(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "with_blend_mode", 2, NativeFunction::from_closure(move |this, args, context| {
+                with_blend_mode(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+    
+    let graphics_calls_ = graphics_calls.clone();
+    unsafe {
+        context.register_global_callable(
+            "set_resolution", 1, NativeFunction::from_closure(move |this, args, context| {
+                set_resolution(&graphics_calls_, this, args, context)
+            })
+        ).unwrap();
+    }
+}
+
+fn clear_screen(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    _this: &JsValue, args: &[JsValue], _context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.is_empty() {
+        graphics_calls.borrow_mut().push(GraphicsCalls::ClearScreenBlack);
+    } else {
+        let c = args[0].as_object()
+            .ok_or(JsNativeError::typ().with_message("Expected a Color"))?
+            .downcast_ref::<JsColor>()
+            .ok_or(JsNativeError::typ().with_message("Expected a Color"))?
+            .clone();
+
+        graphics_calls.borrow_mut().push(GraphicsCalls::ClearScreen(c.into()));        
+    }
+    
+    Ok(JsValue::Undefined)    
+}
+
+fn draw_rectangle(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
 {
     if args.len() < 5 {
         return Err(JsNativeError::typ().with_message("Too few arguments for draw_rectangle").into());
@@ -317,24 +1205,218 @@ fn draw_rectangle(
     let w = args[2].try_js_into::<f64>(context)? as f32;
     let h = args[3].try_js_into::<f64>(context)? as f32;
 
-    let c = args[4].as_object()
-        .ok_or(JsNativeError::typ().with_message("Expected a Color"))?
-        .downcast_ref::<JsColor>()
-        .ok_or(JsNativeError::typ().with_message("Expected a Color"))?
-        .clone();
+    let fill = fill_arg(&args[4])?;
 
     let r = Rectangle::from_tuples((x, y), (x + w, y + h));
-    graphics_calls.borrow_mut().push(GraphicsCalls::DrawRectangle(r, c.into()));
+    graphics_calls.borrow_mut().push(GraphicsCalls::DrawRectangle(r, fill));
+    Ok(JsValue::Undefined)
+}
+
+fn stroke_rectangle(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 6 {
+        return Err(JsNativeError::typ().with_message("Too few arguments for stroke_rectangle").into());
+    }
+
+    let x = args[0].try_js_into::<f64>(context)? as f32;
+    let y = args[1].try_js_into::<f64>(context)? as f32;
+    let w = args[2].try_js_into::<f64>(context)? as f32;
+    let h = args[3].try_js_into::<f64>(context)? as f32;
+    let thickness = args[4].try_js_into::<f64>(context)? as f32;
+    let fill = fill_arg(&args[5])?;
+
+    let points = vec![(x, y).into(), (x + w, y).into(), (x + w, y + h).into(), (x, y + h).into()];
+    graphics_calls.borrow_mut().push(
+        GraphicsCalls::StrokeShape(Shape::Polygon(points), thickness, fill)
+    );
+    Ok(JsValue::Undefined)
+}
+
+fn draw_line(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 6 {
+        return Err(JsNativeError::typ().with_message("Too few arguments for draw_line").into());
+    }
+
+    let x1 = args[0].try_js_into::<f64>(context)? as f32;
+    let y1 = args[1].try_js_into::<f64>(context)? as f32;
+    let x2 = args[2].try_js_into::<f64>(context)? as f32;
+    let y2 = args[3].try_js_into::<f64>(context)? as f32;
+    let thickness = args[4].try_js_into::<f64>(context)? as f32;
+    let fill = fill_arg(&args[5])?;
+
+    let line = Shape::Line((x1, y1).into(), (x2, y2).into());
+    graphics_calls.borrow_mut().push(GraphicsCalls::StrokeShape(line, thickness, fill));
+    Ok(JsValue::Undefined)
+}
+
+/// Pushes a fill, or a stroke whose thickness is the argument before the color.
+fn push_shape(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    shape: Shape, stroke: bool, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    let call = if stroke {
+        let thickness = args[0].try_js_into::<f64>(context)? as f32;
+        GraphicsCalls::StrokeShape(shape, thickness, fill_arg(&args[1])?)
+    } else {
+        GraphicsCalls::FillShape(shape, fill_arg(&args[0])?)
+    };
+    
+    graphics_calls.borrow_mut().push(call);
+    Ok(JsValue::Undefined)
+}
+
+fn draw_circle(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>, stroke: bool,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 4 + stroke as usize {
+        return Err(JsNativeError::typ().with_message("Too few arguments for circle").into());
+    }
+
+    let x = args[0].try_js_into::<f64>(context)? as f32;
+    let y = args[1].try_js_into::<f64>(context)? as f32;
+    let r = args[2].try_js_into::<f64>(context)? as f32;
+
+    let circle = Shape::Ellipse((x, y).into(), (r, r).into());
+    push_shape(graphics_calls, circle, stroke, &args[3..], context)
+}
+
+fn draw_ellipse(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>, stroke: bool,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 5 + stroke as usize {
+        return Err(JsNativeError::typ().with_message("Too few arguments for ellipse").into());
+    }
+
+    let x = args[0].try_js_into::<f64>(context)? as f32;
+    let y = args[1].try_js_into::<f64>(context)? as f32;
+    let rx = args[2].try_js_into::<f64>(context)? as f32;
+    let ry = args[3].try_js_into::<f64>(context)? as f32;
+
+    let ellipse = Shape::Ellipse((x, y).into(), (rx, ry).into());
+    push_shape(graphics_calls, ellipse, stroke, &args[4..], context)
+}
+
+fn draw_polygon(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>, stroke: bool,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 2 + stroke as usize {
+        return Err(JsNativeError::typ().with_message("Too few arguments for polygon").into());
+    }
+
+    let points = points_arg(&args[0], context)?;
+    push_shape(graphics_calls, Shape::Polygon(points), stroke, &args[1..], context)
+}
+
+fn draw_rounded_rectangle(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>, stroke: bool,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 6 + stroke as usize {
+        return Err(JsNativeError::typ().with_message("Too few arguments for rounded rectangle").into());
+    }
+
+    let x = args[0].try_js_into::<f64>(context)? as f32;
+    let y = args[1].try_js_into::<f64>(context)? as f32;
+    let w = args[2].try_js_into::<f64>(context)? as f32;
+    let h = args[3].try_js_into::<f64>(context)? as f32;
+    let radius = args[4].try_js_into::<f64>(context)? as f32;
+
+    let r = Rectangle::from_tuples((x, y), (x + w, y + h));
+    push_shape(graphics_calls, Shape::RoundedRectangle(r, radius), stroke, &args[5..], context)
+}
+
+/// Reads a `Color` or a `Gradient`.
+fn fill_arg(arg: &JsValue) -> JsResult<Fill> {
+    let object = arg.as_object()
+        .ok_or(JsNativeError::typ().with_message("Expected a Color or Gradient"))?;
+    
+    if let Some(color) = object.downcast_ref::<JsColor>() {
+        return Ok(Fill::Solid(color.clone().into()));
+    }
+    
+    if let Some(gradient) = object.downcast_ref::<JsGradient>() {
+        return Ok(Fill::Gradient(gradient.gradient.clone()));
+    }
+    
+    Err(JsNativeError::typ().with_message("Expected a Color or Gradient").into())
+}
+
+/// Reads points given as `[[x, y], ...]` or as a flat `[x, y, ...]` array.
+fn points_arg(arg: &JsValue, context: &mut Context) -> JsResult<Vec<Vec2>> {
+    let array = arg.try_js_into::<JsArray>(context)?;
+    let length = array.length(context)? as i64;
+    let mut values = vec![];
+    
+    for i in 0..length {
+        let item = array.at(i, context)?;
+        
+        if let Some(object) = item.as_object().filter(|object| object.is_array()) {
+            let point = JsArray::from_object(object.clone())?;
+            values.push(point.at(0, context)?.try_js_into::<f64>(context)? as f32);
+            values.push(point.at(1, context)?.try_js_into::<f64>(context)? as f32);
+        } else {
+            values.push(item.try_js_into::<f64>(context)? as f32);
+        }
+    }
+    
+    if values.len() % 2 != 0 {
+        return Err(JsNativeError::typ().with_message("Expected pairs of coordinates").into());
+    }
+    
+    Ok(values.chunks_exact(2).map(|xy| Vec2::new(xy[0], xy[1])).collect())
+}
+
+fn draw_text(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 6 {
+        return Err(JsNativeError::typ().with_message("Too few arguments for draw_text").into());
+    }
+
+    let mut js_font = args[0].as_object()
+        .ok_or(JsNativeError::typ().with_message("Expected a Font"))?
+        .downcast_mut::<JsFont>()
+        .ok_or(JsNativeError::typ().with_message("Expected a Font"))?;
+        
+    let text = args[1].try_js_into::<String>(context)?;   
+    let x = args[2].try_js_into::<f64>(context)? as f32;
+    let y = args[3].try_js_into::<f64>(context)? as f32;
+    let s = args[4].try_js_into::<f64>(context)? as f32;
+
+    let fill = fill_arg(&args[5])?;
+                                            
+    let effects = text_effects_arg(args.get(6), context)?;
+                                            
+    let placements = js_font.place_text(&text, (x, y).into(), s);
+    push_text(graphics_calls, placements, &fill, &effects);
+
     Ok(JsValue::Undefined)
 }
 
-fn draw_text(
+fn draw_text_box(
     graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
     _this: &JsValue, args: &[JsValue], context: &mut Context
     ) -> JsResult<JsValue>
 {
-    if args.len() < 6 {
-        return Err(JsNativeError::typ().with_message("Too few arguments for draw_text").into());
+    if args.len() < 7 {
+        return Err(JsNativeError::typ().with_message("Too few arguments for draw_text_box").into());
     }
 
     let mut js_font = args[0].as_object()
@@ -342,25 +1424,283 @@ fn draw_text(
         .downcast_mut::<JsFont>()
         .ok_or(JsNativeError::typ().with_message("Expected a Font"))?;
         
-    let text = args[1].try_js_into::<String>(context)?;   
+    let text = args[1].try_js_into::<String>(context)?;
     let x = args[2].try_js_into::<f64>(context)? as f32;
     let y = args[3].try_js_into::<f64>(context)? as f32;
-    let s = args[4].try_js_into::<f64>(context)? as f32;
+    let w = args[4].try_js_into::<f64>(context)? as f32;
+    let h = args[5].try_js_into::<f64>(context)? as f32;
+    let fill = fill_arg(&args[6])?;
+    let options = text_box_options(args.get(7), context)?;
+    
+    let layout = js_font.layout_text_box(&text, w, h, &options);
+    
+    let effects = text_effects_arg(args.get(7), context)?;
+    
+    let placements = layout.pieces.iter()
+        .flat_map(|piece| js_font.place_text(&piece.text, Vec2::new(x, y) + piece.offset, layout.size))
+        .collect();
+    push_text(graphics_calls, placements, &fill, &effects);
+    
+    let result = serde_json::json!({
+        "size": layout.size,
+        "lines": layout.line_count,
+        "truncated": layout.truncated,
+    });
+    JsValue::from_json(&result, context)
+}
 
-    let c = args[5].as_object()
-        .ok_or(JsNativeError::typ().with_message("Expected a Color"))?
-        .downcast_ref::<JsColor>()
-        .ok_or(JsNativeError::typ().with_message("Expected a Color"))?
-        .clone();
-                                            
-    let block = js_font.layout_text(&text, s);
-    graphics_calls.borrow_mut().push(
-        GraphicsCalls::DrawText((x, y).into(), c.into(), block)
-    );
+/// Reads `{outline: {width, color}, shadow: {x, y, blur, color}, glow: {radius, color}}`
+/// from an options object, where each effect is off unless given.
+fn text_effects_arg(options: Option<&JsValue>, context: &mut Context) -> JsResult<TextEffects> {
+    let mut effects = TextEffects::default();
+    
+    let Some(options) = options.and_then(|options| options.as_object()) else {
+        return Ok(effects);
+    };
+    
+    let number = |object: &JsObject, name: &str, default: f32, context: &mut Context| -> JsResult<f32> {
+        let value = object.get(name, context)?;
+        if value.is_undefined() {
+            Ok(default)
+        } else {
+            Ok(value.try_js_into::<f64>(context)? as f32)
+        }
+    };
+    
+    let color = |object: &JsObject, default: Color, context: &mut Context| -> JsResult<Color> {
+        let value = object.get("color", context)?;
+        if value.is_undefined() {
+            return Ok(default);
+        }
+        let color = value.as_object()
+            .and_then(|color| color.downcast_ref::<JsColor>().map(|color| color.clone()))
+            .ok_or(JsNativeError::typ().with_message("Expected a Color"))?;
+        Ok(color.into())
+    };
+    
+    if let Some(outline) = options.get("outline", context)?.as_object() {
+        effects.outline = Some(Outline {
+            width: number(outline, "width", 2., context)?,
+            color: color(outline, Color::BLACK, context)?,
+        });
+    }
+    
+    if let Some(shadow) = options.get("shadow", context)?.as_object() {
+        effects.shadow = Some(Shadow {
+            offset: Vec2::new(number(shadow, "x", 2., context)?, number(shadow, "y", 2., context)?),
+            blur: number(shadow, "blur", 4., context)?,
+            color: color(shadow, Color::from_rgba(0., 0., 0., 0.6), context)?,
+        });
+    }
+    
+    if let Some(glow) = options.get("glow", context)?.as_object() {
+        effects.glow = Some(Glow {
+            radius: number(glow, "radius", 8., context)?,
+            color: color(glow, Color::WHITE, context)?,
+        });
+    }
+    
+    Ok(effects)
+}
+
+fn text_box_options(options: Option<&JsValue>, context: &mut Context) -> JsResult<TextBoxOptions> {
+    let mut text_box_options = TextBoxOptions {
+        size: 16.,
+        align: Align::Left,
+        valign: VAlign::Top,
+        line_height: 1.,
+        ellipsis: None,
+        auto_shrink: false,
+    };
+    
+    let Some(options) = options.and_then(|options| options.as_object()) else {
+        return Ok(text_box_options);
+    };
+    
+    let size = options.get("size", context)?;
+    if !size.is_undefined() {
+        text_box_options.size = size.try_js_into::<f64>(context)? as f32;
+    }
+    
+    let align = options.get("align", context)?;
+    if !align.is_undefined() {
+        text_box_options.align = match align.try_js_into::<String>(context)?.as_str() {
+            "left" => Align::Left,
+            "center" => Align::Center,
+            "right" => Align::Right,
+            "justify" => Align::Justify,
+            _ => return Err(JsNativeError::typ()
+                .with_message("align must be left, center, right or justify").into()),
+        };
+    }
+    
+    let valign = options.get("valign", context)?;
+    if !valign.is_undefined() {
+        text_box_options.valign = match valign.try_js_into::<String>(context)?.as_str() {
+            "top" => VAlign::Top,
+            "middle" => VAlign::Middle,
+            "bottom" => VAlign::Bottom,
+            _ => return Err(JsNativeError::typ()
+                .with_message("valign must be top, middle or bottom").into()),
+        };
+    }
+    
+    let line_height = options.get("lineHeight", context)?;
+    if !line_height.is_undefined() {
+        text_box_options.line_height = line_height.try_js_into::<f64>(context)? as f32;
+    }
+    
+    // true uses the ellipsis character, and a string replaces it
+    let ellipsis = options.get("ellipsis", context)?;
+    if ellipsis.is_string() {
+        text_box_options.ellipsis = Some(ellipsis.try_js_into::<String>(context)?);
+    } else if ellipsis.to_boolean() {
+        text_box_options.ellipsis = Some("\u{2026}".to_owned());
+    }
+    
+    text_box_options.auto_shrink = options.get("autoShrink", context)?.to_boolean();
+    
+    Ok(text_box_options)
+}
+
+/// A rich text span with the defaults from the options filled in.
+struct SpanArg {
+    font: JsObject,
+    text: String,
+    size: f32,
+    fill: Fill,
+}
+
+/// Reads spans given as strings or `{text, font, size, color}` objects, where
+/// missing properties come from the options.
+fn rich_text_args(
+    spans: &JsValue, options: Option<&JsValue>, context: &mut Context
+) -> JsResult<(Vec<SpanArg>, RichTextOptions)> {
+    let options = options.and_then(|options| options.as_object()).cloned();
+    let option = |name: &str, context: &mut Context| -> JsResult<JsValue> {
+        match &options {
+            Some(options) => options.get(name, context),
+            None => Ok(JsValue::Undefined),
+        }
+    };
+    
+    let default_font = option("font", context)?.as_object().cloned();
+    let default_size = match option("size", context)? {
+        size if size.is_undefined() => 16.,
+        size => size.try_js_into::<f64>(context)? as f32,
+    };
+    let default_fill = match option("color", context)? {
+        color if color.is_undefined() => Fill::Solid(Color::WHITE),
+        color => fill_arg(&color)?,
+    };
+    
+    let array = spans.try_js_into::<JsArray>(context)?;
+    let length = array.length(context)? as i64;
+    let mut span_args = vec![];
+    
+    for i in 0..length {
+        let span = array.at(i, context)?;
+        
+        let (text, font, size, fill) = match span.as_object() {
+            Some(span) => {
+                let text = span.get("text", context)?.try_js_into::<String>(context)?;
+                let font = span.get("font", context)?.as_object().cloned();
+                let size = span.get("size", context)?;
+                let size = if size.is_undefined() { None } else { Some(size.try_js_into::<f64>(context)? as f32) };
+                let color = span.get("color", context)?;
+                let fill = if color.is_undefined() { None } else { Some(fill_arg(&color)?) };
+                (text, font, size, fill)
+            },
+            None => (span.try_js_into::<String>(context)?, None, None, None),
+        };
+        
+        let font = font.or(default_font.clone())
+            .filter(|font| font.is::<JsFont>())
+            .ok_or(JsNativeError::typ().with_message("Expected a Font for each span"))?;
+        
+        span_args.push(SpanArg {
+            font,
+            text,
+            size: size.unwrap_or(default_size),
+            fill: fill.unwrap_or(default_fill.clone()),
+        });
+    }
+    
+    let width = option("width", context)?;
+    let width = if width.is_undefined() { None } else { Some(width.try_js_into::<f64>(context)? as f32) };
+    
+    let align = match option("align", context)? {
+        align if align.is_undefined() => Align::Left,
+        align => match align.try_js_into::<String>(context)?.as_str() {
+            "left" => Align::Left,
+            "center" => Align::Center,
+            "right" => Align::Right,
+            _ => return Err(JsNativeError::typ()
+                .with_message("align must be left, center or right").into()),
+        },
+    };
+    
+    let line_height = match option("lineHeight", context)? {
+        line_height if line_height.is_undefined() => 1.,
+        line_height => line_height.try_js_into::<f64>(context)? as f32,
+    };
+    
+    Ok((span_args, RichTextOptions { width, align, line_height }))
+}
 
+fn layout_rich_text(spans: &[SpanArg], options: &RichTextOptions) -> RichTextLayout {
+    let chains: Vec<FontChain> = spans.iter()
+        .map(|span| span.font.downcast_ref::<JsFont>().unwrap().chain.clone())
+        .collect();
+    
+    let rich_spans: Vec<RichSpan> = spans.iter().zip(chains.iter())
+        .map(|(span, font)| RichSpan { font, text: &span.text, size: span.size })
+        .collect();
+    
+    text_layout::layout_rich_text(&rich_spans, options)
+}
+
+fn draw_rich_text(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 3 {
+        return Err(JsNativeError::typ().with_message("Too few arguments for draw_rich_text").into());
+    }
+    
+    let (spans, options) = rich_text_args(&args[0], args.get(3), context)?;
+    let x = args[1].try_js_into::<f64>(context)? as f32;
+    let y = args[2].try_js_into::<f64>(context)? as f32;
+    
+    let layout = layout_rich_text(&spans, &options);
+    
+    for piece in layout.pieces.iter() {
+        let span = &spans[piece.span];
+        let mut js_font = span.font.downcast_mut::<JsFont>().unwrap();
+        let placements = js_font.place_text(&piece.text, Vec2::new(x, y) + piece.offset, span.size);
+        push_text(graphics_calls, placements, &span.fill, &TextEffects::default());
+    }
+    
     Ok(JsValue::Undefined)
 }
 
+fn size_rich_text(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue>
+{
+    if args.is_empty() {
+        return Err(JsNativeError::typ().with_message("Too few arguments for size_rich_text").into());
+    }
+    
+    let (spans, options) = rich_text_args(&args[0], args.get(1), context)?;
+    let size = layout_rich_text(&spans, &options).size;
+    
+    let array = JsArray::new(context);
+    array.push(size.x, context)?;
+    array.push(size.y, context)?;
+    
+    Ok(JsValue::Object(array.into()))
+}
+
 fn size_text(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue>
 {
     if args.len() < 3 {
@@ -375,8 +1715,7 @@ fn size_text(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResu
     let text = args[1].try_js_into::<String>(context)?;
     let s = args[2].try_js_into::<f64>(context)? as f32;
     
-    let block = js_font.layout_text(&text, s);
-    let size = block.size();
+    let size = js_font.size_text(&text, s);
     
     let array = JsArray::new(context);
     array.push(size.x, context)?;
@@ -399,10 +1738,9 @@ fn draw_image(
         
         let x = args[1].try_js_into::<f64>(context)? as f32;
         let y = args[2].try_js_into::<f64>(context)? as f32;
+        let image_ref = js_image.image_ref(context)?;
                                                 
-        graphics_calls.borrow_mut().push(
-            GraphicsCalls::DrawImage((x, y).into(), js_image.path.to_str().unwrap().to_owned())
-        );
+        graphics_calls.borrow_mut().push(GraphicsCalls::DrawImage((x, y).into(), image_ref));
     } else if args.len() == 5 {
         let js_image = args[0].as_object()
             .ok_or(JsNativeError::typ().with_message("Expected an Image"))?
@@ -414,11 +1752,12 @@ fn draw_image(
         let y = args[2].try_js_into::<f64>(context)? as f32;
         let w = args[3].try_js_into::<f64>(context)? as f32;
         let h = args[4].try_js_into::<f64>(context)? as f32;                                                            
+        let image_ref = js_image.image_ref(context)?;
                                                 
         graphics_calls.borrow_mut().push(
             GraphicsCalls::DrawRectangleImageTinted(
                 Rectangle::new((x, y).into(), (x + w, y + h).into()),
-                js_image.path.to_str().unwrap().to_owned(),
+                image_ref,
                 Color::WHITE,
             )
         );
@@ -433,21 +1772,223 @@ fn draw_image(
         let y = args[2].try_js_into::<f64>(context)? as f32;
         let w = args[3].try_js_into::<f64>(context)? as f32;
         let h = args[4].try_js_into::<f64>(context)? as f32;
-        let a = args[5].try_js_into::<f64>(context)? as f32;
+        let (tint, adjust) = image_style_arg(&args[5], context)?;
+        let image_ref = js_image.image_ref(context)?;
+        let r = Rectangle::new((x, y).into(), (x + w, y + h).into());
 
-        graphics_calls.borrow_mut().push(
-            GraphicsCalls::DrawRectangleImageTinted(
-                Rectangle::new((x, y).into(), (x + w, y + h).into()),
-                js_image.path.to_str().unwrap().to_owned(),
-                Color::from_rgba(1., 1., 1., a),
-            )
-        );
+        // Tinting alone is done by the renderers without copying the image
+        let call = if adjust.is_identity() {
+            GraphicsCalls::DrawRectangleImageTinted(r, image_ref, tint)
+        } else {
+            GraphicsCalls::DrawImageAdjusted(r, image_ref, tint, adjust)
+        };
+        graphics_calls.borrow_mut().push(call);
     } else {
         return Err(JsNativeError::typ().with_message("Unexpected number of arguments for draw_image").into());
     }
     Ok(JsValue::Undefined)
 }
 
+/// Reads how to color an image from an alpha, a tint `Color`, or an object
+/// with `tint`, `alpha`, `brightness`, `contrast`, `saturation` and
+/// `grayscale`.
+fn image_style_arg(arg: &JsValue, context: &mut Context) -> JsResult<(Color, ColorAdjust)> {
+    let Some(object) = arg.as_object() else {
+        let a = arg.try_js_into::<f64>(context)? as f32;
+        return Ok((Color::from_rgba(1., 1., 1., a), ColorAdjust::default()));
+    };
+
+    if let Some(color) = object.downcast_ref::<JsColor>() {
+        return Ok((color.clone().into(), ColorAdjust::default()));
+    }
+
+    let tint = object.get("tint", context)?;
+    let tint = if tint.is_undefined() {
+        Color::WHITE
+    } else {
+        tint.as_object()
+            .and_then(|tint| tint.downcast_ref::<JsColor>().map(|color| color.clone().into()))
+            .ok_or(JsNativeError::typ().with_message("tint must be a Color"))?
+    };
+
+    let mut number = |name: &str, default: f32| -> JsResult<f32> {
+        let value = object.get(name, context)?;
+        if value.is_undefined() {
+            Ok(default)
+        } else {
+            Ok(value.try_js_into::<f64>(context)? as f32)
+        }
+    };
+
+    let alpha = number("alpha", 1.)?;
+    let defaults = ColorAdjust::default();
+    let adjust = ColorAdjust {
+        brightness: number("brightness", defaults.brightness)?.max(0.),
+        contrast: number("contrast", defaults.contrast)?.max(0.),
+        saturation: number("saturation", defaults.saturation)?.max(0.),
+        grayscale: number("grayscale", defaults.grayscale)?.clamp(0., 1.),
+    };
+
+    Ok((Color::from_rgba(tint.r(), tint.g(), tint.b(), tint.a() * alpha), adjust))
+}
+
+fn draw_image_region(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() != 9 && args.len() != 10 {
+        return Err(JsNativeError::typ().with_message("Unexpected number of arguments for draw_image_region").into());
+    }
+
+    let js_image = args[0].as_object()
+        .ok_or(JsNativeError::typ().with_message("Expected an Image"))?
+        .downcast_ref::<JsImage>()
+        .ok_or(JsNativeError::typ().with_message("Expected an Image"))?
+        .clone();
+
+    let mut values = [0.; 8];
+    for (value, arg) in values.iter_mut().zip(&args[1..9]) {
+        *value = arg.try_js_into::<f64>(context)? as f32;
+    }
+    let [sx, sy, sw, sh, dx, dy, dw, dh] = values;
+    let a = match args.get(9) {
+        Some(arg) => arg.try_js_into::<f64>(context)? as f32,
+        None => 1.,
+    };
+    let image_ref = js_image.image_ref(context)?;
+
+    graphics_calls.borrow_mut().push(
+        GraphicsCalls::DrawImageRegion(
+            Rectangle::new((sx, sy).into(), (sx + sw, sy + sh).into()),
+            Rectangle::new((dx, dy).into(), (dx + dw, dy + dh).into()),
+            image_ref,
+            Color::from_rgba(1., 1., 1., a),
+        )
+    );
+    Ok(JsValue::Undefined)
+}
+
+/// Reads insets given as one number for every side, an array in CSS order
+/// (`[top, right, bottom, left]`, `[vertical, horizontal]` or
+/// `[top, horizontal, bottom]`) or an object with `top`, `right`, `bottom`
+/// and `left`.
+fn insets_arg(arg: &JsValue, context: &mut Context) -> JsResult<Insets> {
+    if let Some(object) = arg.as_object() {
+        if object.is_array() {
+            let array = JsArray::from_object(object.clone())?;
+            let mut values = vec![];
+            let length = array.length(context)? as i64;
+            for i in 0..length {
+                values.push(array.at(i, context)?.try_js_into::<f64>(context)? as f32);
+            }
+
+            let (top, right, bottom, left) = match values[..] {
+                [all] => (all, all, all, all),
+                [vertical, horizontal] => (vertical, horizontal, vertical, horizontal),
+                [top, horizontal, bottom] => (top, horizontal, bottom, horizontal),
+                [top, right, bottom, left] => (top, right, bottom, left),
+                _ => return Err(JsNativeError::typ().with_message("Insets must have 1 to 4 values").into()),
+            };
+            return Ok(Insets { top, right, bottom, left });
+        }
+
+        let mut side = |name: &str| -> JsResult<f32> {
+            let value = object.get(name, context)?;
+            if value.is_undefined() {
+                Ok(0.)
+            } else {
+                Ok(value.try_js_into::<f64>(context)? as f32)
+            }
+        };
+        return Ok(Insets { top: side("top")?, right: side("right")?, bottom: side("bottom")?, left: side("left")? });
+    }
+
+    let all = arg.try_js_into::<f64>(context)? as f32;
+    Ok(Insets { top: all, right: all, bottom: all, left: all })
+}
+
+fn draw_image_nine_slice(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() != 6 && args.len() != 7 {
+        return Err(JsNativeError::typ().with_message("Unexpected number of arguments for draw_image_nine_slice").into());
+    }
+
+    let js_image = args[0].as_object()
+        .ok_or(JsNativeError::typ().with_message("Expected an Image"))?
+        .downcast_ref::<JsImage>()
+        .ok_or(JsNativeError::typ().with_message("Expected an Image"))?
+        .clone();
+
+    let x = args[1].try_js_into::<f64>(context)? as f32;
+    let y = args[2].try_js_into::<f64>(context)? as f32;
+    let w = args[3].try_js_into::<f64>(context)? as f32;
+    let h = args[4].try_js_into::<f64>(context)? as f32;
+    let insets = insets_arg(&args[5], context)?;
+    let a = match args.get(6) {
+        Some(arg) => arg.try_js_into::<f64>(context)? as f32,
+        None => 1.,
+    };
+    let image_ref = js_image.image_ref(context)?;
+
+    graphics_calls.borrow_mut().push(
+        GraphicsCalls::DrawImageNineSlice(
+            Rectangle::new((x, y).into(), (x + w, y + h).into()),
+            insets,
+            image_ref,
+            Color::from_rgba(1., 1., 1., a),
+        )
+    );
+    Ok(JsValue::Undefined)
+}
+
+fn draw_sprite(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() != 4 && args.len() != 6 && args.len() != 7 {
+        return Err(JsNativeError::typ().with_message("Unexpected number of arguments for draw_sprite").into());
+    }
+
+    let atlas = args[0].as_object()
+        .ok_or(JsNativeError::typ().with_message("Expected an Atlas"))?
+        .downcast_ref::<JsAtlas>()
+        .ok_or(JsNativeError::typ().with_message("Expected an Atlas"))?
+        .clone();
+
+    let name = args[1].try_js_into::<String>(context)?;
+    let source = atlas.sprites.get(&name)
+        .ok_or(JsNativeError::typ().with_message(format!("Unknown sprite {}", name)))?
+        .clone();
+
+    let x = args[2].try_js_into::<f64>(context)? as f32;
+    let y = args[3].try_js_into::<f64>(context)? as f32;
+    let (w, h) = if args.len() >= 6 {
+        (args[4].try_js_into::<f64>(context)? as f32, args[5].try_js_into::<f64>(context)? as f32)
+    } else {
+        (source.width(), source.height())
+    };
+    let a = match args.get(6) {
+        Some(arg) => arg.try_js_into::<f64>(context)? as f32,
+        None => 1.,
+    };
+    let image_ref = atlas.image.image_ref(context)?;
+
+    graphics_calls.borrow_mut().push(
+        GraphicsCalls::DrawImageRegion(
+            source,
+            Rectangle::new((x, y).into(), (x + w, y + h).into()),
+            image_ref,
+            Color::from_rgba(1., 1., 1., a),
+        )
+    );
+    Ok(JsValue::Undefined)
+}
+
 fn with_offset(
     graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
     this: &JsValue, args: &[JsValue], context: &mut Context
@@ -462,11 +2003,197 @@ fn with_offset(
     let func = args[2].try_js_into::<JsFunction>(context)?;
     
     graphics_calls.borrow_mut().push(
-        GraphicsCalls::PushOffset((x, y).into())
+        GraphicsCalls::PushTransform(Affine::translate(x, y))
     );
 
     let call_result = func.call(this, args, context);
-    graphics_calls.borrow_mut().push(GraphicsCalls::PopOffset);
+    graphics_calls.borrow_mut().push(GraphicsCalls::PopTransform);
+    call_result
+}
+
+fn call_with_transform(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    transform: Affine, func: &JsFunction,
+    this: &JsValue, context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    graphics_calls.borrow_mut().push(GraphicsCalls::PushTransform(transform));
+    let call_result = func.call(this, &[], context);
+    graphics_calls.borrow_mut().push(GraphicsCalls::PopTransform);
+    call_result
+}
+
+/// Applies `transform` about the pivot `(x, y)` rather than the origin.
+fn about_pivot(transform: Affine, x: f32, y: f32) -> Affine {
+    Affine::translate(x, y)
+        .then_apply(&transform)
+        .then_apply(&Affine::translate(-x, -y))
+}
+
+fn with_transform(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 7 {
+        return Err(JsNativeError::typ().with_message("Too few arguments for with_transform").into());
+    }
+    
+    let mut m = [0f32; 6];
+    for (i, value) in m.iter_mut().enumerate() {
+        *value = args[i].try_js_into::<f64>(context)? as f32;
+    }
+    let func = args[6].try_js_into::<JsFunction>(context)?;
+    
+    let transform = Affine::new(m[0], m[1], m[2], m[3], m[4], m[5]);
+    call_with_transform(graphics_calls, transform, &func, this, context)
+}
+
+fn with_rotation(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    let (angle, pivot, func) = match args.len() {
+        2 => (&args[0], None, &args[1]),
+        4 => (&args[0], Some((&args[1], &args[2])), &args[3]),
+        _ => return Err(JsNativeError::typ()
+            .with_message("Unexpected number of arguments for with_rotation").into()),
+    };
+    
+    let angle = angle.try_js_into::<f64>(context)? as f32;
+    let func = func.try_js_into::<JsFunction>(context)?;
+    let mut transform = Affine::rotate(angle);
+    
+    if let Some((x, y)) = pivot {
+        let x = x.try_js_into::<f64>(context)? as f32;
+        let y = y.try_js_into::<f64>(context)? as f32;
+        transform = about_pivot(transform, x, y);
+    }
+    
+    call_with_transform(graphics_calls, transform, &func, this, context)
+}
+
+fn with_scale(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    let (sx, sy, pivot, func) = match args.len() {
+        2 => (&args[0], &args[0], None, &args[1]),
+        3 => (&args[0], &args[1], None, &args[2]),
+        5 => (&args[0], &args[1], Some((&args[2], &args[3])), &args[4]),
+        _ => return Err(JsNativeError::typ()
+            .with_message("Unexpected number of arguments for with_scale").into()),
+    };
+    
+    let sx = sx.try_js_into::<f64>(context)? as f32;
+    let sy = sy.try_js_into::<f64>(context)? as f32;
+    let func = func.try_js_into::<JsFunction>(context)?;
+    let mut transform = Affine::scale(sx, sy);
+    
+    if let Some((x, y)) = pivot {
+        let x = x.try_js_into::<f64>(context)? as f32;
+        let y = y.try_js_into::<f64>(context)? as f32;
+        transform = about_pivot(transform, x, y);
+    }
+    
+    call_with_transform(graphics_calls, transform, &func, this, context)
+}
+
+fn with_clip(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 5 {
+        return Err(JsNativeError::typ().with_message("Too few arguments for with_clip").into());
+    }
+    
+    let x = args[0].try_js_into::<f64>(context)? as f32;
+    let y = args[1].try_js_into::<f64>(context)? as f32;
+    let w = args[2].try_js_into::<f64>(context)? as f32;
+    let h = args[3].try_js_into::<f64>(context)? as f32;
+    let func = args[4].try_js_into::<JsFunction>(context)?;
+    
+    let r = Rectangle::from_tuples((x, y), (x + w, y + h));
+    graphics_calls.borrow_mut().push(GraphicsCalls::PushClip(r));
+    let call_result = func.call(this, &[], context);
+    graphics_calls.borrow_mut().push(GraphicsCalls::PopClip);
+    call_result
+}
+
+/// Draws everything `func` draws into the layer, replacing what it held.
+fn with_layer(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 2 {
+        return Err(JsNativeError::typ().with_message("Too few arguments for with_layer").into());
+    }
+
+    let layer = layer_arg(&args[0])?;
+    let func = args[1].try_js_into::<JsFunction>(context)?;
+
+    graphics_calls.borrow_mut().push(GraphicsCalls::BeginLayer(layer.id, layer.size));
+    let call_result = func.call(this, &[], context);
+    graphics_calls.borrow_mut().push(GraphicsCalls::EndLayer);
+    call_result
+}
+
+fn draw_layer(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() != 3 && args.len() != 5 && args.len() != 6 {
+        return Err(JsNativeError::typ().with_message("Unexpected number of arguments for draw_layer").into());
+    }
+
+    let layer = layer_arg(&args[0])?;
+    let x = args[1].try_js_into::<f64>(context)? as f32;
+    let y = args[2].try_js_into::<f64>(context)? as f32;
+    let (w, h) = if args.len() >= 5 {
+        (args[3].try_js_into::<f64>(context)? as f32, args[4].try_js_into::<f64>(context)? as f32)
+    } else {
+        (layer.size.x as f32, layer.size.y as f32)
+    };
+    let a = match args.get(5) {
+        Some(arg) => arg.try_js_into::<f64>(context)? as f32,
+        None => 1.,
+    };
+
+    graphics_calls.borrow_mut().push(
+        GraphicsCalls::DrawLayer(
+            Rectangle::new((x, y).into(), (x + w, y + h).into()),
+            layer.id,
+            Color::from_rgba(1., 1., 1., a),
+        )
+    );
+    Ok(JsValue::Undefined)
+}
+
+fn with_blend_mode(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 2 {
+        return Err(JsNativeError::typ().with_message("Too few arguments for with_blend_mode").into());
+    }
+
+    let name = args[0].try_js_into::<String>(context)?;
+    let mode = BlendMode::from_str(&name).map_err(|_| {
+        JsNativeError::typ().with_message(
+            "Blend mode must be \"normal\", \"additive\", \"multiply\" or \"screen\""
+        )
+    })?;
+    let func = args[1].try_js_into::<JsFunction>(context)?;
+
+    graphics_calls.borrow_mut().push(GraphicsCalls::PushBlendMode(mode));
+    let call_result = func.call(this, &[], context);
+    graphics_calls.borrow_mut().push(GraphicsCalls::PopBlendMode);
     call_result
 }
 
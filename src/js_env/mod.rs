@@ -9,7 +9,7 @@ use boa_engine::{JsNativeError, JsResult, JsValue, JsError};
 use boa_engine::{Context, Module, Source};
 use boa_engine::builtins::promise::PromiseState;
 use boa_engine::module::{ModuleLoader, SimpleModuleLoader};
-use boa_engine::object::builtins::{JsArray, JsFunction};
+use boa_engine::object::builtins::JsArray;
 use boa_engine::property::{Attribute, PropertyKey};
 use boa_engine::value::TryFromJs;
 use boa_runtime::Console;
@@ -17,18 +17,30 @@ use local_ip_address::local_ip;
 use notify::{Watcher, RecursiveMode};
 use tracing::warn;
 
+use crate::clock::SharedClock;
+
 mod graphics;
 mod files;
-pub use graphics::GraphicsCalls;
+mod time;
+mod timers;
+mod fetch;
+mod video;
+mod tweens;
+pub use graphics::{GraphicsCalls, ImageRef, TextSource};
 
 pub struct JsEnv {
     app_path: PathBuf,
     context: Context<'static>,
     module: Module,
     graphics_calls: Rc<RefCell<Vec<GraphicsCalls>>>,
+    clock: SharedClock,
+    timers: Rc<RefCell<timers::Timers>>,
+    fetcher: Rc<RefCell<fetch::Fetcher>>,
+    videos: Rc<RefCell<video::Videos>>,
+    tweens: Rc<RefCell<tweens::Tweens>>,
     
     #[allow(deprecated)]
-    watches: Rc<RefCell<HashMap<PathBuf, JsFunction>>>,
+    watches: Rc<RefCell<HashMap<PathBuf, files::Watch>>>,
     #[allow(dead_code)] // Required to keep watcher in scope
     watcher: Box<dyn Watcher>,
     file_change_rx: mpsc::Receiver<PathBuf>,
@@ -44,17 +56,27 @@ const FALLBACK_SCRIPT: &str = r###"
 "###;
 
 impl JsEnv {
-    pub fn new(app_path: &Path) -> Self
+    pub fn new(app_path: &Path, clock: SharedClock) -> Self
     {
         let (tx, rx) = mpsc::channel();
         let tx_for_watcher = tx.clone();
+        let cache_root = std::fs::canonicalize(app_path)
+            .unwrap_or_else(|_| app_path.to_path_buf())
+            .join(fetch::CACHE_ROOT);
         
         let mut watcher = notify::recommended_watcher(move |res: Result<notify::Event, notify::Error>| {           
             match res {
-                Ok(event) if event.kind.is_modify() => {
+                // Creation and removal are needed to keep directory listings current
+                Ok(event) if event.kind.is_modify() || event.kind.is_create() || event.kind.is_remove() => {
                     for path_buf in event.paths {
+                        // Skip files the player writes itself, like cached responses
+                        if path_buf.starts_with(&cache_root) {
+                            continue;
+                        }
+                        // Apps outside the working directory are watched by
+                        // their absolute paths
                         let cwd = std::env::current_dir().unwrap();
-                        let path = path_buf.strip_prefix(&cwd).unwrap();
+                        let path = path_buf.strip_prefix(&cwd).unwrap_or(&path_buf);
                         let _ = tx_for_watcher.send(path.to_owned());
                     }
                 },
@@ -68,7 +90,13 @@ impl JsEnv {
         let watches = Rc::new(RefCell::new(HashMap::new()));
         
         let graphics_calls = Rc::new(RefCell::new(vec![]));
-        let (context, module) = JsEnv::create_context(app_path, &graphics_calls, &watches)
+        let timers = Rc::new(RefCell::new(timers::Timers::default()));
+        let fetcher = Rc::new(RefCell::new(fetch::Fetcher::new(app_path)));
+        let videos = Rc::new(RefCell::new(video::Videos::new(app_path)));
+        let tweens = Rc::new(RefCell::new(tweens::Tweens::default()));
+        let (context, module) = JsEnv::create_context(
+            app_path, &graphics_calls, &watches, &clock, &timers, &fetcher, &videos, &tweens
+        )
             .unwrap_or_else(|err| {
                 dbg!(err);
                 JsEnv::create_fallback_context(&graphics_calls)
@@ -80,6 +108,11 @@ impl JsEnv {
             context,
             module,
             graphics_calls,
+            clock,
+            timers,
+            fetcher,
+            videos,
+            tweens,
             watches,
             watcher: Box::new(watcher),
             _file_change_tx: tx,
@@ -114,10 +147,16 @@ impl JsEnv {
         self.graphics_calls.borrow_mut().clear();
     }
     
+    #[allow(clippy::too_many_arguments)]
     pub fn create_context(
         app_path: &Path,
         graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
-        watches: &Rc<RefCell<HashMap<PathBuf, JsFunction>>>
+        watches: &Rc<RefCell<HashMap<PathBuf, files::Watch>>>,
+        clock: &SharedClock,
+        timers: &Rc<RefCell<timers::Timers>>,
+        fetcher: &Rc<RefCell<fetch::Fetcher>>,
+        videos: &Rc<RefCell<video::Videos>>,
+        tweens: &Rc<RefCell<tweens::Tweens>>,
         ) -> JsResult<(Context<'static>, Module)>
     {
         let loader = Rc::new(SimpleModuleLoader::new(Path::new(&app_path))?);
@@ -141,6 +180,11 @@ impl JsEnv {
         
         graphics::register_fns_and_types(&mut context, graphics_calls);
         files::register_fns_and_types(&mut context, watches);
+        time::register_fns_and_types(&mut context, clock)?;
+        timers::register_fns_and_types(&mut context, timers);
+        fetch::register_fns_and_types(&mut context, fetcher)?;
+        video::register_fns_and_types(&mut context, graphics_calls, videos)?;
+        tweens::register_fns_and_types(&mut context, tweens)?;
         
         let console = Console::init(&mut context);
         context.register_global_property(Console::NAME, console, Attribute::all())?;
@@ -177,6 +221,22 @@ impl JsEnv {
         JsEnv::call_module_init(&self.module, &mut self.context)
     }
 
+    /// Advances the virtual clock by the real frame time, settles finished
+    /// fetches, runs due timers, advances videos and tweens and draws the
+    /// frame with the scaled `dt`, draining promise jobs after each.
+    pub fn run_frame(&mut self, real_dt: f32) -> Result<(), JsError> {
+        let dt = self.clock.lock().unwrap().advance(real_dt);
+        self.handle_fetch_results();
+        self.run_timers(dt);
+        self.advance_videos(dt);
+        self.advance_tweens(dt);
+        self.context.run_jobs();
+        
+        let result = self.call_draw(dt);
+        self.context.run_jobs();
+        result
+    }
+
     pub fn call_draw(&mut self, dt: f32) -> Result<(), JsError> {
         let namespace = self.module.namespace(&mut self.context);
         let draw = namespace
@@ -227,7 +287,9 @@ impl JsEnv {
             
     pub fn load_json(path: impl AsRef<Path>, context: &mut Context) -> Result<JsValue, JsError> {
         let json_text = read_to_string(path).unwrap_or("{}".to_owned());
-        let Ok(json_data) = serde_json::from_str(&json_text) else {return Ok(JsValue::Undefined)};
+        let json_data = serde_json::from_str(&json_text).map_err(|err| {
+            JsNativeError::syntax().with_message(format!("Invalid JSON: {}", err))
+        })?;
         JsValue::from_json(&json_data, context)
     }
 }
@@ -0,0 +1,106 @@
+use boa_engine::{Context, JsResult, JsValue, NativeFunction, Source};
+
+use crate::clock::SharedClock;
+
+/// Replaces the global `Date` so that `new Date()`, `Date()` and `Date.now()`
+/// report the player's virtual clock. Dates built from explicit values are
+/// unaffected.
+const DATE_SHIM: &str = r###"
+    (() => {
+        // Read from the global object, as the declaration below shadows
+        // `Date` throughout this function
+        const SystemDate = globalThis.Date;
+
+        function Date(...args) {
+            if (new.target === undefined) {
+                return new SystemDate(__clock_now()).toString();
+            }
+
+            if (args.length === 0) {
+                return Reflect.construct(SystemDate, [__clock_now()], new.target);
+            }
+            return Reflect.construct(SystemDate, args, new.target);
+        }
+
+        for (const key of Object.getOwnPropertyNames(SystemDate)) {
+            if (key !== "prototype" && key !== "name") {
+                Object.defineProperty(Date, key, Object.getOwnPropertyDescriptor(SystemDate, key));
+            }
+        }
+
+        Date.prototype = SystemDate.prototype;
+        Object.defineProperty(SystemDate.prototype, "constructor", {
+            value: Date, writable: true, configurable: true,
+        });
+        Date.now = () => __clock_now();
+
+        globalThis.Date = Date;
+    })();
+"###;
+
+pub fn register_fns_and_types(context: &mut Context, clock: &SharedClock) -> JsResult<()> {
+    let clock_ = clock.clone();
+    unsafe {
+        context.register_global_callable(
+            "__clock_now", 0, NativeFunction::from_closure(move |this, args, context| {
+                clock_now(&clock_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    context.eval(Source::from_bytes(DATE_SHIM))?;
+    Ok(())
+}
+
+fn clock_now(
+    clock: &SharedClock,
+    _this: &JsValue, _args: &[JsValue], _context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    let now = clock.lock().unwrap().now();
+    Ok(JsValue::Rational(now.timestamp_millis() as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{self, VirtualClock};
+
+    fn context() -> Context {
+        let mut context = Context::default();
+        let clock = VirtualClock::shared(clock::parse_time("2023-03-25T18:00:00Z").ok(), 1.);
+        register_fns_and_types(&mut context, &clock).unwrap();
+        context
+    }
+
+    fn eval(context: &mut Context, code: &str) -> JsValue {
+        context.eval(Source::from_bytes(code)).unwrap()
+    }
+
+    #[test]
+    fn dates_report_the_virtual_clock() {
+        let mut context = context();
+
+        assert_eq!(eval(&mut context, "new Date().getTime()").as_number(), Some(1679767200000.));
+        assert_eq!(eval(&mut context, "Date.now()").as_number(), Some(1679767200000.));
+        assert_eq!(eval(&mut context, "Date() === new Date().toString()").as_boolean(), Some(true));
+    }
+
+    #[test]
+    fn dates_from_values_are_unaffected() {
+        let mut context = context();
+
+        assert_eq!(eval(&mut context, "new Date(0).getTime()").as_number(), Some(0.));
+        assert_eq!(eval(&mut context, "Date.UTC(2020, 0, 1)").as_number(), Some(1577836800000.));
+    }
+
+    #[test]
+    fn dates_are_still_dates() {
+        let mut context = context();
+
+        assert_eq!(eval(&mut context, "new Date() instanceof Date").as_boolean(), Some(true));
+        assert_eq!(eval(&mut context, "new Date().constructor === Date").as_boolean(), Some(true));
+        assert_eq!(eval(&mut context, "class Later extends Date {}; new Later() instanceof Later")
+            .as_boolean(), Some(true));
+    }
+}
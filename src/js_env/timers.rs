@@ -0,0 +1,240 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use boa_engine::{Context, JsNativeError, JsResult, JsValue, NativeFunction};
+use boa_engine::object::builtins::JsFunction;
+use tracing::warn;
+
+use crate::js_env::JsEnv;
+
+// Keeps a zero-delay setInterval from firing more than once per frame
+const MIN_INTERVAL_MS: f64 = 1.;
+
+struct Timer {
+    id: u32,
+    callback: JsFunction,
+    args: Vec<JsValue>,
+    remaining_ms: f64,
+    interval_ms: Option<f64>,
+}
+
+/// Pending `setTimeout` and `setInterval` callbacks, advanced once per frame
+/// by the app's `dt`.
+#[derive(Default)]
+pub struct Timers {
+    next_id: u32,
+    timers: Vec<Timer>,
+}
+
+impl Timers {
+    fn add(&mut self, callback: JsFunction, args: Vec<JsValue>, delay_ms: f64, repeat: bool) -> u32 {
+        // Ids start at 1 so they are always truthy in JS
+        self.next_id += 1;
+        let id = self.next_id;
+
+        self.timers.push(Timer {
+            id,
+            callback,
+            args,
+            remaining_ms: delay_ms,
+            interval_ms: repeat.then_some(delay_ms.max(MIN_INTERVAL_MS)),
+        });
+
+        id
+    }
+
+    fn remove(&mut self, id: u32) {
+        self.timers.retain(|timer| timer.id != id);
+    }
+
+    /// Advances every timer and returns the ids of those now due, earliest first.
+    fn advance(&mut self, dt_ms: f64) -> Vec<u32> {
+        for timer in self.timers.iter_mut() {
+            timer.remaining_ms -= dt_ms;
+        }
+
+        let mut due: Vec<&Timer> = self.timers.iter()
+            .filter(|timer| timer.remaining_ms <= 0.)
+            .collect();
+        due.sort_by(|a, b| a.remaining_ms.total_cmp(&b.remaining_ms));
+        due.iter().map(|timer| timer.id).collect()
+    }
+
+    /// Takes the callback of a due timer, rescheduling intervals and removing
+    /// timeouts. Returns `None` if the timer was cleared in the meantime.
+    fn fire(&mut self, id: u32) -> Option<(JsFunction, Vec<JsValue>)> {
+        let index = self.timers.iter().position(|timer| timer.id == id)?;
+        let timer = &mut self.timers[index];
+        let fired = (timer.callback.clone(), timer.args.clone());
+
+        match timer.interval_ms {
+            Some(interval_ms) => timer.remaining_ms = (timer.remaining_ms + interval_ms).max(0.),
+            None => { self.timers.remove(index); },
+        }
+
+        Some(fired)
+    }
+}
+
+pub fn register_fns_and_types(context: &mut Context, timers: &Rc<RefCell<Timers>>) {
+    let timers_ = timers.clone();
+    unsafe {
+        context.register_global_callable(
+            "setTimeout", 2, NativeFunction::from_closure(move |this, args, context| {
+                set_timer(&timers_, false, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let timers_ = timers.clone();
+    unsafe {
+        context.register_global_callable(
+            "setInterval", 2, NativeFunction::from_closure(move |this, args, context| {
+                set_timer(&timers_, true, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let timers_ = timers.clone();
+    unsafe {
+        context.register_global_callable(
+            "clearTimeout", 1, NativeFunction::from_closure(move |this, args, context| {
+                clear_timer(&timers_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let timers_ = timers.clone();
+    unsafe {
+        context.register_global_callable(
+            "clearInterval", 1, NativeFunction::from_closure(move |this, args, context| {
+                clear_timer(&timers_, this, args, context)
+            })
+        ).unwrap();
+    }
+}
+
+fn set_timer(
+    timers: &Rc<RefCell<Timers>>, repeat: bool,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.is_empty() {
+        return Err(JsNativeError::typ().with_message("Not enough arguments").into());
+    }
+    
+    let callback = args[0].try_js_into::<JsFunction>(context)?;
+
+    let delay_ms = match args.get(1) {
+        Some(arg) => arg.to_number(context)?,
+        None => 0.,
+    };
+    let delay_ms = if delay_ms.is_finite() { delay_ms.max(0.) } else { 0. };
+
+    let callback_args = args.iter().skip(2).cloned().collect();
+    let id = timers.borrow_mut().add(callback, callback_args, delay_ms, repeat);
+    Ok(JsValue::Integer(id as i32))
+}
+
+fn clear_timer(
+    timers: &Rc<RefCell<Timers>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if let Some(arg) = args.get(0) {
+        let id = arg.to_number(context)?;
+        if id.is_finite() && id >= 0. {
+            timers.borrow_mut().remove(id as u32);
+        }
+    }
+
+    Ok(JsValue::Undefined)
+}
+
+impl JsEnv {
+    /// Runs the timer callbacks that became due during the last `dt` seconds.
+    pub fn run_timers(&mut self, dt: f32) {
+        let due = self.timers.borrow_mut().advance(dt as f64 * 1000.);
+
+        for id in due {
+            // Bind first so the borrow ends before the callback can add or clear timers
+            let fired = self.timers.borrow_mut().fire(id);
+
+            if let Some((callback, args)) = fired {
+                if let Err(err) = callback.call(&JsValue::Undefined, &args, &mut self.context) {
+                    warn!("Timer callback failed: {}", err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+
+    fn callback(context: &mut Context) -> JsFunction {
+        let value = context.eval(Source::from_bytes("() => {}")).unwrap();
+        JsFunction::from_object(value.as_object().unwrap().clone()).unwrap()
+    }
+
+    #[test]
+    fn timeouts_fire_once_when_due() {
+        let mut context = Context::default();
+        let mut timers = Timers::default();
+        let id = timers.add(callback(&mut context), vec![], 100., false);
+
+        assert!(timers.advance(50.).is_empty());
+        assert_eq!(timers.advance(50.), vec![id]);
+        assert!(timers.fire(id).is_some());
+        assert!(timers.advance(1000.).is_empty());
+        assert!(timers.fire(id).is_none());
+    }
+
+    #[test]
+    fn due_timers_are_ordered_earliest_first() {
+        let mut context = Context::default();
+        let mut timers = Timers::default();
+        let late = timers.add(callback(&mut context), vec![], 30., false);
+        let early = timers.add(callback(&mut context), vec![], 10., false);
+
+        assert_eq!(timers.advance(50.), vec![early, late]);
+    }
+
+    #[test]
+    fn intervals_reschedule_from_when_they_were_due() {
+        let mut context = Context::default();
+        let mut timers = Timers::default();
+        let id = timers.add(callback(&mut context), vec![], 100., true);
+
+        assert_eq!(timers.advance(130.), vec![id]);
+        timers.fire(id);
+        // Due again 70ms later, keeping the original schedule
+        assert!(timers.advance(60.).is_empty());
+        assert_eq!(timers.advance(10.), vec![id]);
+    }
+
+    #[test]
+    fn zero_intervals_fire_at_most_once_per_frame() {
+        let mut context = Context::default();
+        let mut timers = Timers::default();
+        let id = timers.add(callback(&mut context), vec![], 0., true);
+
+        assert_eq!(timers.advance(16.), vec![id]);
+        timers.fire(id);
+        assert_eq!(timers.advance(16.), vec![id]);
+    }
+
+    #[test]
+    fn cleared_timers_do_not_fire() {
+        let mut context = Context::default();
+        let mut timers = Timers::default();
+        let id = timers.add(callback(&mut context), vec![], 10., true);
+
+        let due = timers.advance(20.);
+        timers.remove(id);
+        assert_eq!(due, vec![id]);
+        assert!(timers.fire(id).is_none());
+    }
+}
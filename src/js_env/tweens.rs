@@ -0,0 +1,585 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use boa_engine::{Context, JsNativeError, JsResult, JsValue, NativeFunction, Source};
+use boa_engine::object::JsObject;
+use boa_engine::object::builtins::{JsArray, JsFunction};
+use tracing::warn;
+
+use crate::easing::Easing;
+use crate::js_env::JsEnv;
+
+const DEFAULT_DURATION_MS: f64 = 500.;
+
+/// Wraps native tweens in a `Tween` class, with `animate` to start one and
+/// `chain` to start another when it completes.
+const TWEEN_SHIM: &str = r###"
+    (() => {
+        class Tween {
+            constructor(id) {
+                this._id = id;
+            }
+
+            chain(target, props, options) {
+                return create(target, props, options, this._id);
+            }
+
+            pause() { __tween_set(this._id, "paused", true); }
+            resume() { __tween_set(this._id, "paused", false); }
+            stop() { __tween_stop(this._id); }
+
+            get paused() { return __tween_get(this._id, "paused"); }
+            get active() { return __tween_get(this._id, "active"); }
+        }
+
+        function create(target, props, options, after) {
+            const keys = Object.keys(props);
+            const values = keys.map(key => Number(props[key]));
+            return new Tween(__tween_create(target, keys, values, options || {}, after));
+        }
+
+        globalThis.Tween = Tween;
+        globalThis.animate = (target, props, options) => create(target, props, options, 0);
+    })();
+"###;
+
+struct Tween {
+    id: u32,
+    target: JsObject,
+    keys: Vec<String>,
+    to: Vec<f64>,
+    /// Read from the target when the tween's delay ends.
+    from: Option<Vec<f64>>,
+    duration_ms: f64,
+    delay_ms: f64,
+    /// Times to play again after the first, which may be infinite.
+    repeat: f64,
+    yoyo: bool,
+    easing: Easing,
+    on_update: Option<JsFunction>,
+    on_complete: Option<JsFunction>,
+    /// Time since the tween started, including its delay.
+    elapsed_ms: f64,
+    paused: bool,
+    /// Set for chained tweens until the tween before them completes.
+    waiting: bool,
+    /// Tweens chained to start when this one completes.
+    chained: Vec<u32>,
+}
+
+/// A tween's values for this frame, to set on its target.
+struct TweenStep {
+    target: JsObject,
+    values: Vec<(String, f64)>,
+    on_update: Option<JsFunction>,
+    /// The `onComplete` callback, if the tween just completed.
+    completed: Option<Option<JsFunction>>,
+}
+
+/// Running `animate` tweens, advanced once per frame by the app's `dt`.
+#[derive(Default)]
+pub struct Tweens {
+    next_id: u32,
+    tweens: Vec<Tween>,
+}
+
+impl Tweens {
+    fn add(&mut self, mut tween: Tween, after: u32) -> u32 {
+        // Ids start at 1 so 0 can mean a tween isn't chained
+        self.next_id += 1;
+        let id = self.next_id;
+        tween.id = id;
+
+        // Chaining to a tween that already completed starts right away
+        if let Some(previous) = self.get_mut(after) {
+            previous.chained.push(id);
+            tween.waiting = true;
+        }
+
+        self.tweens.push(tween);
+        id
+    }
+
+    fn get(&self, id: u32) -> Option<&Tween> {
+        self.tweens.iter().find(|tween| tween.id == id)
+    }
+
+    fn get_mut(&mut self, id: u32) -> Option<&mut Tween> {
+        self.tweens.iter_mut().find(|tween| tween.id == id)
+    }
+
+    /// Removes a tween and the tweens chained after it.
+    fn remove(&mut self, id: u32) {
+        let Some(index) = self.tweens.iter().position(|tween| tween.id == id) else { return };
+        let tween = self.tweens.remove(index);
+        for chained in tween.chained {
+            self.remove(chained);
+        }
+    }
+
+    /// Advances every running tween and returns the ids of those past their
+    /// delay, in the order they were created.
+    fn advance(&mut self, dt_ms: f64) -> Vec<u32> {
+        let running = self.tweens.iter_mut().filter(|tween| !tween.paused && !tween.waiting);
+        for tween in running {
+            tween.elapsed_ms += dt_ms;
+        }
+
+        self.tweens.iter()
+            .filter(|tween| !tween.paused && !tween.waiting && tween.elapsed_ms >= tween.delay_ms)
+            .map(|tween| tween.id)
+            .collect()
+    }
+
+    /// The target and property names of a tween that needs its start values.
+    fn unstarted(&self, id: u32) -> Option<(JsObject, Vec<String>)> {
+        let tween = self.get(id)?;
+        tween.from.is_none().then(|| (tween.target.clone(), tween.keys.clone()))
+    }
+
+    fn start(&mut self, id: u32, from: Vec<f64>) {
+        if let Some(tween) = self.get_mut(id) {
+            tween.from = Some(from);
+        }
+    }
+
+    /// Computes a started tween's values. A tween that completed is removed
+    /// and the tweens chained after it start with the time left over.
+    fn step(&mut self, id: u32) -> Option<TweenStep> {
+        let index = self.tweens.iter().position(|tween| tween.id == id)?;
+        let tween = &self.tweens[index];
+        let from = tween.from.as_ref()?;
+
+        let time = tween.elapsed_ms - tween.delay_ms;
+        let iterations = tween.repeat + 1.;
+        // Zero-length tweens complete at once, even when repeating forever
+        let total_ms = if tween.duration_ms > 0. { tween.duration_ms * iterations } else { 0. };
+        let completed = time >= total_ms;
+
+        let (iteration, x) = if completed {
+            (if iterations.is_finite() { iterations - 1. } else { 0. }, 1.)
+        } else {
+            let iteration = (time / tween.duration_ms).floor();
+            (iteration, time / tween.duration_ms - iteration)
+        };
+        let x = if tween.yoyo && iteration % 2. == 1. { 1. - x } else { x };
+        let eased = tween.easing.ease(x);
+
+        let values = tween.keys.iter()
+            .zip(from.iter().zip(tween.to.iter()))
+            .map(|(key, (from, to))| (key.clone(), from + (to - from) * eased))
+            .collect();
+
+        let mut step = TweenStep {
+            target: tween.target.clone(),
+            values,
+            on_update: tween.on_update.clone(),
+            completed: None,
+        };
+
+        if completed {
+            let left_over_ms = time - total_ms;
+            let tween = self.tweens.remove(index);
+            for chained in tween.chained {
+                if let Some(chained) = self.get_mut(chained) {
+                    chained.waiting = false;
+                    chained.elapsed_ms = left_over_ms;
+                }
+            }
+            step.completed = Some(tween.on_complete);
+        }
+
+        Some(step)
+    }
+}
+
+pub fn register_fns_and_types(context: &mut Context, tweens: &Rc<RefCell<Tweens>>) -> JsResult<()> {
+    let tweens_ = tweens.clone();
+    unsafe {
+        context.register_global_callable(
+            "__tween_create", 5, NativeFunction::from_closure(move |this, args, context| {
+                tween_create(&tweens_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let tweens_ = tweens.clone();
+    unsafe {
+        context.register_global_callable(
+            "__tween_get", 2, NativeFunction::from_closure(move |this, args, context| {
+                tween_get(&tweens_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let tweens_ = tweens.clone();
+    unsafe {
+        context.register_global_callable(
+            "__tween_set", 3, NativeFunction::from_closure(move |this, args, context| {
+                tween_set(&tweens_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let tweens_ = tweens.clone();
+    unsafe {
+        context.register_global_callable(
+            "__tween_stop", 1, NativeFunction::from_closure(move |this, args, context| {
+                tween_stop(&tweens_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    context.register_global_callable("ease", 2, NativeFunction::from_fn_ptr(ease))?;
+
+    context.eval(Source::from_bytes(TWEEN_SHIM))?;
+    Ok(())
+}
+
+/// Reads an easing name, a `cubic-bezier(...)` string or an `[x1, y1, x2, y2]`
+/// array, defaulting to linear.
+fn easing_arg(arg: &JsValue, context: &mut Context) -> JsResult<Easing> {
+    if arg.is_undefined() {
+        return Ok(Easing::Linear);
+    }
+
+    if let Some(object) = arg.as_object().filter(|object| object.is_array()) {
+        let array = JsArray::from_object(object.clone())?;
+        let mut values = vec![];
+        let length = array.length(context)? as i64;
+        for i in 0..length {
+            values.push(array.at(i, context)?.to_number(context)?);
+        }
+        return match values[..] {
+            [x1, y1, x2, y2] => Ok(Easing::cubic_bezier(x1, y1, x2, y2)),
+            _ => Err(JsNativeError::typ().with_message("A cubic bezier easing needs 4 values").into()),
+        };
+    }
+
+    let name = arg.try_js_into::<String>(context)?;
+    let easing = Easing::from_str(&name).map_err(|_| {
+        JsNativeError::typ().with_message(format!("Unknown easing {}", name))
+    })?;
+    Ok(easing)
+}
+
+fn tween_id(arg: Option<&JsValue>, context: &mut Context) -> JsResult<u32> {
+    let id = arg.cloned().unwrap_or_default().to_number(context)?;
+    if id.is_finite() && id >= 0. {
+        Ok(id as u32)
+    } else {
+        Err(JsNativeError::typ().with_message("Expected a tween id").into())
+    }
+}
+
+fn tween_create(
+    tweens: &Rc<RefCell<Tweens>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() < 5 {
+        return Err(JsNativeError::typ().with_message("Not enough arguments").into());
+    }
+
+    let target = args[0].as_object()
+        .ok_or(JsNativeError::typ().with_message("Can only animate the properties of an object"))?
+        .clone();
+    let keys = args[1].try_js_into::<JsArray>(context)?;
+    let values = args[2].try_js_into::<JsArray>(context)?;
+    let options = args[3].as_object()
+        .ok_or(JsNativeError::typ().with_message("Tween options must be an object"))?
+        .clone();
+    let after = tween_id(args.get(4), context)?;
+
+    let mut to_keys = vec![];
+    let mut to = vec![];
+    let length = keys.length(context)? as i64;
+    for i in 0..length {
+        to_keys.push(keys.at(i, context)?.try_js_into::<String>(context)?);
+        to.push(values.at(i, context)?.to_number(context)?);
+    }
+
+    let mut number = |name: &str, default: f64| -> JsResult<f64> {
+        let value = options.get(name, context)?;
+        if value.is_undefined() {
+            Ok(default)
+        } else {
+            let value = value.to_number(context)?;
+            Ok(if value.is_nan() { default } else { value })
+        }
+    };
+    let duration_ms = number("duration", DEFAULT_DURATION_MS)?.max(0.);
+    let delay_ms = number("delay", 0.)?.max(0.);
+    let repeat = number("repeat", 0.)?.max(0.).floor();
+
+    let yoyo = options.get("yoyo", context)?.to_boolean();
+    let easing = easing_arg(&options.get("easing", context)?, context)?;
+
+    let mut callback = |name: &str| -> JsResult<Option<JsFunction>> {
+        let value = options.get(name, context)?;
+        if value.is_undefined() || value.is_null() {
+            Ok(None)
+        } else {
+            Ok(Some(value.try_js_into::<JsFunction>(context)?))
+        }
+    };
+    let on_update = callback("onUpdate")?;
+    let on_complete = callback("onComplete")?;
+
+    let id = tweens.borrow_mut().add(Tween {
+        id: 0,
+        target,
+        keys: to_keys,
+        to,
+        from: None,
+        duration_ms,
+        delay_ms,
+        repeat,
+        yoyo,
+        easing,
+        on_update,
+        on_complete,
+        elapsed_ms: 0.,
+        paused: false,
+        waiting: false,
+        chained: vec![],
+    }, after);
+    Ok(JsValue::Integer(id as i32))
+}
+
+fn tween_get(
+    tweens: &Rc<RefCell<Tweens>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    let id = tween_id(args.get(0), context)?;
+    let property = args.get(1).cloned().unwrap_or_default().try_js_into::<String>(context)?;
+
+    let tweens = tweens.borrow();
+    let tween = tweens.get(id);
+
+    let value = match property.as_str() {
+        // Completed and stopped tweens are removed
+        "active" => JsValue::Boolean(tween.is_some()),
+        "paused" => JsValue::Boolean(tween.map_or(false, |tween| tween.paused)),
+        _ => JsValue::Undefined,
+    };
+    Ok(value)
+}
+
+fn tween_set(
+    tweens: &Rc<RefCell<Tweens>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    let id = tween_id(args.get(0), context)?;
+    let property = args.get(1).cloned().unwrap_or_default().try_js_into::<String>(context)?;
+    let value = args.get(2).cloned().unwrap_or_default();
+
+    if let Some(tween) = tweens.borrow_mut().get_mut(id) {
+        if property == "paused" {
+            tween.paused = value.to_boolean();
+        }
+    }
+    Ok(JsValue::Undefined)
+}
+
+fn tween_stop(
+    tweens: &Rc<RefCell<Tweens>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    let id = tween_id(args.get(0), context)?;
+    tweens.borrow_mut().remove(id);
+    Ok(JsValue::Undefined)
+}
+
+/// `ease(easing, t)` eases progress for apps that time their own animations.
+fn ease(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let easing = easing_arg(&args.get(0).cloned().unwrap_or_default(), context)?;
+    let t = args.get(1).cloned().unwrap_or_default().to_number(context)?;
+    Ok(JsValue::Rational(easing.ease(t)))
+}
+
+impl JsEnv {
+    /// Advances tweens by `dt` seconds, setting their targets' properties and
+    /// calling their callbacks.
+    pub fn advance_tweens(&mut self, dt: f32) {
+        let due = self.tweens.borrow_mut().advance(dt as f64 * 1000.);
+
+        for id in due {
+            // Tweens start from the target's values when their delay ends
+            let unstarted = self.tweens.borrow().unstarted(id);
+            if let Some((target, keys)) = unstarted {
+                let mut from = vec![];
+                for key in keys.iter() {
+                    let value = target.get(key.as_str(), &mut self.context)
+                        .and_then(|value| value.to_number(&mut self.context))
+                        .unwrap_or(f64::NAN);
+                    // Missing properties start from 0
+                    from.push(if value.is_finite() { value } else { 0. });
+                }
+                self.tweens.borrow_mut().start(id, from);
+            }
+
+            // Bind first so the borrow ends before callbacks can add or stop tweens
+            let step = self.tweens.borrow_mut().step(id);
+            let Some(step) = step else { continue };
+
+            for (key, value) in step.values {
+                if let Err(err) = step.target.set(key.as_str(), value, false, &mut self.context) {
+                    warn!("Could not set tweened property {}: {}", key, err);
+                }
+            }
+
+            let target = JsValue::Object(step.target);
+            if let Some(on_update) = step.on_update {
+                if let Err(err) = on_update.call(&JsValue::Undefined, &[target.clone()], &mut self.context) {
+                    warn!("Tween onUpdate failed: {}", err);
+                }
+            }
+            if let Some(Some(on_complete)) = step.completed {
+                if let Err(err) = on_complete.call(&JsValue::Undefined, &[target], &mut self.context) {
+                    warn!("Tween onComplete failed: {}", err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tween(context: &mut Context, duration_ms: f64, repeat: f64, yoyo: bool) -> Tween {
+        let target = context.eval(Source::from_bytes("({ x: 0 })")).unwrap();
+        Tween {
+            id: 0,
+            target: target.as_object().unwrap().clone(),
+            keys: vec!["x".to_string()],
+            to: vec![100.],
+            from: Some(vec![0.]),
+            duration_ms,
+            delay_ms: 0.,
+            repeat,
+            yoyo,
+            easing: Easing::Linear,
+            on_update: None,
+            on_complete: None,
+            elapsed_ms: 0.,
+            paused: false,
+            waiting: false,
+            chained: vec![],
+        }
+    }
+
+    /// Advances the tweens and returns each due tween's rounded value and
+    /// whether it completed.
+    fn advance(tweens: &mut Tweens, dt_ms: f64) -> Vec<(u32, f64, bool)> {
+        tweens.advance(dt_ms).into_iter()
+            .map(|id| {
+                let step = tweens.step(id).unwrap();
+                (id, step.values[0].1.round(), step.completed.is_some())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tweens_complete_after_their_duration() {
+        let mut context = Context::default();
+        let mut tweens = Tweens::default();
+        let id = tweens.add(tween(&mut context, 100., 0., false), 0);
+
+        assert_eq!(advance(&mut tweens, 50.), vec![(id, 50., false)]);
+        assert_eq!(advance(&mut tweens, 60.), vec![(id, 100., true)]);
+        assert!(tweens.get(id).is_none());
+    }
+
+    #[test]
+    fn tweens_wait_for_their_delay() {
+        let mut context = Context::default();
+        let mut tweens = Tweens::default();
+        let mut delayed = tween(&mut context, 100., 0., false);
+        delayed.delay_ms = 50.;
+        delayed.from = None;
+        let id = tweens.add(delayed, 0);
+
+        assert!(tweens.advance(40.).is_empty());
+        assert_eq!(tweens.advance(20.), vec![id]);
+        assert!(tweens.unstarted(id).is_some());
+        tweens.start(id, vec![0.]);
+        assert_eq!(tweens.step(id).unwrap().values, vec![("x".to_string(), 10.)]);
+    }
+
+    #[test]
+    fn paused_tweens_stay_put() {
+        let mut context = Context::default();
+        let mut tweens = Tweens::default();
+        let id = tweens.add(tween(&mut context, 100., 0., false), 0);
+
+        tweens.get_mut(id).unwrap().paused = true;
+        assert!(advance(&mut tweens, 50.).is_empty());
+        tweens.get_mut(id).unwrap().paused = false;
+        assert_eq!(advance(&mut tweens, 25.), vec![(id, 25., false)]);
+    }
+
+    #[test]
+    fn repeats_restart_from_the_beginning() {
+        let mut context = Context::default();
+        let mut tweens = Tweens::default();
+        let id = tweens.add(tween(&mut context, 100., 1., false), 0);
+
+        assert_eq!(advance(&mut tweens, 150.), vec![(id, 50., false)]);
+        assert_eq!(advance(&mut tweens, 40.), vec![(id, 90., false)]);
+        assert_eq!(advance(&mut tweens, 10.), vec![(id, 100., true)]);
+    }
+
+    #[test]
+    fn yoyos_play_back_on_odd_iterations() {
+        let mut context = Context::default();
+        let mut tweens = Tweens::default();
+        let id = tweens.add(tween(&mut context, 100., 1., true), 0);
+
+        assert_eq!(advance(&mut tweens, 25.), vec![(id, 25., false)]);
+        assert_eq!(advance(&mut tweens, 100.), vec![(id, 75., false)]);
+        // Ends back where it started after playing forwards then back
+        assert_eq!(advance(&mut tweens, 100.), vec![(id, 0., true)]);
+    }
+
+    #[test]
+    fn infinite_repeats_never_complete() {
+        let mut context = Context::default();
+        let mut tweens = Tweens::default();
+        let id = tweens.add(tween(&mut context, 100., f64::INFINITY, true), 0);
+
+        assert_eq!(advance(&mut tweens, 10_000. + 30.), vec![(id, 30., false)]);
+        assert_eq!(advance(&mut tweens, 100.), vec![(id, 70., false)]);
+    }
+
+    #[test]
+    fn chained_tweens_start_with_the_time_left_over() {
+        let mut context = Context::default();
+        let mut tweens = Tweens::default();
+        let first = tweens.add(tween(&mut context, 100., 0., false), 0);
+        let second = tweens.add(tween(&mut context, 100., 0., false), first);
+        let third = tweens.add(tween(&mut context, 100., 0., false), second);
+
+        assert_eq!(advance(&mut tweens, 50.), vec![(first, 50., false)]);
+        assert_eq!(advance(&mut tweens, 70.), vec![(first, 100., true)]);
+        assert_eq!(advance(&mut tweens, 10.), vec![(second, 30., false)]);
+
+        // Stopping a tween stops the tweens chained after it
+        tweens.remove(second);
+        assert!(tweens.get(third).is_none());
+    }
+
+    #[test]
+    fn chaining_to_a_completed_tween_starts_at_once() {
+        let mut context = Context::default();
+        let mut tweens = Tweens::default();
+        let id = tweens.add(tween(&mut context, 100., 0., false), 42);
+
+        assert_eq!(advance(&mut tweens, 50.), vec![(id, 50., false)]);
+    }
+}
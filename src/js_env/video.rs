@@ -0,0 +1,323 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use boa_engine::{Context, JsNativeError, JsResult, JsValue, NativeFunction, Source};
+use speedy2d::color::Color;
+use speedy2d::shape::Rectangle;
+use tracing::warn;
+
+use crate::js_env::{GraphicsCalls, JsEnv};
+use crate::video::VideoPlayer;
+
+/// Wraps the native player in a `Video` class with media-element style
+/// properties, and keeps each video reachable by id so `onended` can be
+/// called from Rust until it is closed.
+const VIDEO_SHIM: &str = r###"
+    (() => {
+        const videos = new Map();
+
+        class Video {
+            constructor(path) {
+                this._id = __video_open(String(path));
+                this.onended = null;
+                videos.set(this._id, this);
+            }
+
+            play() { __video_set(this._id, "playing", true); }
+            pause() { __video_set(this._id, "playing", false); }
+            seek(time) { __video_set(this._id, "currentTime", Number(time)); }
+
+            close() {
+                __video_close(this._id);
+                videos.delete(this._id);
+            }
+            dispose() { this.close(); }
+
+            get currentTime() { return __video_get(this._id, "currentTime"); }
+            set currentTime(time) { this.seek(time); }
+            get duration() { return __video_get(this._id, "duration"); }
+            get paused() { return !__video_get(this._id, "playing"); }
+            get ended() { return __video_get(this._id, "ended"); }
+            get loop() { return __video_get(this._id, "loop"); }
+            set loop(value) { __video_set(this._id, "loop", Boolean(value)); }
+            get width() { return __video_get(this._id, "width"); }
+            get height() { return __video_get(this._id, "height"); }
+        }
+
+        globalThis.Video = Video;
+
+        globalThis.__video_ended = function(id) {
+            const video = videos.get(id);
+            if (video && typeof video.onended === "function") {
+                video.onended(video);
+            }
+        };
+    })();
+"###;
+
+/// The videos opened by the app, advanced once per frame by the app's `dt`.
+pub struct Videos {
+    app_path: PathBuf,
+    players: HashMap<u32, VideoPlayer>,
+    /// Set for offline renders, which wait for each frame to be decoded.
+    pub wait_for_frames: bool,
+}
+
+impl Videos {
+    pub fn new(app_path: &Path) -> Self {
+        Videos {
+            app_path: app_path.to_path_buf(),
+            players: HashMap::new(),
+            wait_for_frames: false,
+        }
+    }
+
+    /// Returns the ids of the videos that ended during this frame.
+    fn advance(&mut self, dt: f64) -> Vec<u32> {
+        let wait = self.wait_for_frames;
+        let mut ended: Vec<u32> = self.players.iter_mut()
+            .filter_map(|(id, player)| player.advance(dt, wait).then_some(*id))
+            .collect();
+        ended.sort();
+        ended
+    }
+}
+
+pub fn register_fns_and_types(
+    context: &mut Context,
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    videos: &Rc<RefCell<Videos>>,
+) -> JsResult<()> {
+    let videos_ = videos.clone();
+    unsafe {
+        context.register_global_callable(
+            "__video_open", 1, NativeFunction::from_closure(move |this, args, context| {
+                video_open(&videos_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let videos_ = videos.clone();
+    unsafe {
+        context.register_global_callable(
+            "__video_get", 2, NativeFunction::from_closure(move |this, args, context| {
+                video_get(&videos_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let videos_ = videos.clone();
+    unsafe {
+        context.register_global_callable(
+            "__video_set", 3, NativeFunction::from_closure(move |this, args, context| {
+                video_set(&videos_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let videos_ = videos.clone();
+    unsafe {
+        context.register_global_callable(
+            "__video_close", 1, NativeFunction::from_closure(move |this, args, context| {
+                video_close(&videos_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    let graphics_calls_ = graphics_calls.clone();
+    let videos_ = videos.clone();
+    unsafe {
+        context.register_global_callable(
+            "draw_video", 1, NativeFunction::from_closure(move |this, args, context| {
+                draw_video(&graphics_calls_, &videos_, this, args, context)
+            })
+        ).unwrap();
+    }
+
+    context.eval(Source::from_bytes(VIDEO_SHIM))?;
+    Ok(())
+}
+
+fn video_open(
+    videos: &Rc<RefCell<Videos>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.is_empty() {
+        return Err(JsNativeError::typ().with_message("Not enough arguments").into());
+    }
+
+    let path_string = args[0].try_js_into::<String>(context)?;
+    let mut videos = videos.borrow_mut();
+    let mut path = videos.app_path.clone();
+    path.push(&path_string);
+
+    let player = VideoPlayer::open(&path).map_err(|err| {
+        JsNativeError::typ().with_message(format!("Could not open video {}: {}", path_string, err))
+    })?;
+
+    let id = player.id();
+    videos.players.insert(id, player);
+    Ok(JsValue::Integer(id as i32))
+}
+
+fn video_id(arg: Option<&JsValue>, context: &mut Context) -> JsResult<u32> {
+    let id = arg.cloned().unwrap_or_default().to_number(context)?;
+    if id.is_finite() && id >= 0. {
+        Ok(id as u32)
+    } else {
+        Err(JsNativeError::typ().with_message("Expected a video id").into())
+    }
+}
+
+fn video_get(
+    videos: &Rc<RefCell<Videos>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    let id = video_id(args.get(0), context)?;
+    let property = args.get(1).cloned().unwrap_or_default().try_js_into::<String>(context)?;
+
+    let videos = videos.borrow();
+    let Some(player) = videos.players.get(&id) else {
+        return Err(JsNativeError::typ().with_message("Unknown video").into());
+    };
+
+    let value = match property.as_str() {
+        "currentTime" => JsValue::Rational(player.current_time),
+        "duration" => JsValue::Rational(player.duration()),
+        "playing" => JsValue::Boolean(player.playing),
+        "ended" => JsValue::Boolean(player.ended),
+        "loop" => JsValue::Boolean(player.looping),
+        "width" => JsValue::Integer(player.info().size.x as i32),
+        "height" => JsValue::Integer(player.info().size.y as i32),
+        _ => JsValue::Undefined,
+    };
+    Ok(value)
+}
+
+fn video_set(
+    videos: &Rc<RefCell<Videos>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    let id = video_id(args.get(0), context)?;
+    let property = args.get(1).cloned().unwrap_or_default().try_js_into::<String>(context)?;
+    let value = args.get(2).cloned().unwrap_or_default();
+
+    let mut videos = videos.borrow_mut();
+    let Some(player) = videos.players.get_mut(&id) else {
+        return Err(JsNativeError::typ().with_message("Unknown video").into());
+    };
+
+    match property.as_str() {
+        "playing" if value.to_boolean() => player.play(),
+        "playing" => player.pause(),
+        "currentTime" => {
+            let time = value.to_number(context)?;
+            if time.is_finite() {
+                player.seek(time);
+            }
+        },
+        "loop" => player.looping = value.to_boolean(),
+        _ => {},
+    }
+    Ok(JsValue::Undefined)
+}
+
+/// Drops the player, which stops its decode thread. Closing a closed video
+/// does nothing.
+fn video_close(
+    videos: &Rc<RefCell<Videos>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    let id = video_id(args.get(0), context)?;
+    videos.borrow_mut().players.remove(&id);
+    Ok(JsValue::Undefined)
+}
+
+fn draw_video(
+    graphics_calls: &Rc<RefCell<Vec<GraphicsCalls>>>,
+    videos: &Rc<RefCell<Videos>>,
+    _this: &JsValue, args: &[JsValue], context: &mut Context
+    ) -> JsResult<JsValue>
+{
+    if args.len() != 3 && args.len() != 5 && args.len() != 6 {
+        return Err(JsNativeError::typ().with_message("Unexpected number of arguments for draw_video").into());
+    }
+
+    let id = args[0].as_object()
+        .ok_or(JsNativeError::typ().with_message("Expected a Video"))?
+        .get("_id", context)?;
+    let id = video_id(Some(&id), context)?;
+
+    let x = args[1].try_js_into::<f64>(context)? as f32;
+    let y = args[2].try_js_into::<f64>(context)? as f32;
+
+    let size = if args.len() >= 5 {
+        Some((args[3].try_js_into::<f64>(context)? as f32, args[4].try_js_into::<f64>(context)? as f32))
+    } else {
+        None
+    };
+    let a = match args.get(5) {
+        Some(arg) => arg.try_js_into::<f64>(context)? as f32,
+        None => 1.,
+    };
+
+    let videos = videos.borrow();
+    let Some(player) = videos.players.get(&id) else {
+        return Err(JsNativeError::typ().with_message("Expected a Video").into());
+    };
+    let (w, h) = size.unwrap_or_else(|| {
+        let size = player.info().size;
+        (size.x as f32, size.y as f32)
+    });
+
+    // Nothing is drawn until the first frame has been decoded
+    if let Some(frame) = player.frame() {
+        graphics_calls.borrow_mut().push(
+            GraphicsCalls::DrawVideo(
+                Rectangle::new((x, y).into(), (x + w, y + h).into()),
+                frame.clone(),
+                Color::from_rgba(1., 1., 1., a),
+            )
+        );
+    }
+    Ok(JsValue::Undefined)
+}
+
+impl JsEnv {
+    /// Makes videos wait for each frame to be decoded rather than dropping
+    /// behind, for renders that must not depend on decoding speed.
+    pub fn wait_for_video_frames(&mut self) {
+        self.videos.borrow_mut().wait_for_frames = true;
+    }
+
+    /// Advances playback by `dt` seconds and calls `onended` for videos that
+    /// finished.
+    pub fn advance_videos(&mut self, dt: f32) {
+        let ended = self.videos.borrow_mut().advance(dt as f64);
+        if ended.is_empty() {
+            return;
+        }
+
+        let global = self.context.global_object();
+        let dispatch = match global.get("__video_ended", &mut self.context) {
+            Ok(dispatch) => dispatch,
+            Err(err) => {
+                warn!("Could not find the video onended dispatcher: {}", err);
+                return;
+            },
+        };
+        let Some(dispatch) = dispatch.as_callable() else { return };
+
+        for id in ended {
+            if let Err(err) = dispatch.call(&JsValue::Undefined, &[JsValue::Integer(id as i32)], &mut self.context) {
+                warn!("Video onended failed: {}", err);
+            }
+        }
+    }
+}
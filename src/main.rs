@@ -1,3 +1,7 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
 use speedy2d::Window;
 use speedy2d::window::{WindowCreationOptions, WindowSize};
 use tracing::info;
@@ -7,6 +11,25 @@ mod iter_util;
 mod window_handler;
 mod js_env;
 mod perf;
+mod headless;
+mod text_raster;
+mod golden;
+mod clock;
+mod transform;
+mod shape;
+mod gradient;
+mod text_layout;
+mod font_chain;
+mod shaping;
+mod text_effects;
+mod video;
+mod animated_image;
+mod svg_image;
+mod nine_slice;
+mod blend;
+mod color_adjust;
+mod easing;
+use clock::VirtualClock;
 use window_handler::SignWindowHandler;
 
 const HELP: &str = "\
@@ -14,13 +37,25 @@ signrs digital signage application player
 
 USAGE:
   signrs [APPLICATION]
+  signrs test [APPLICATION]
+
+SUBCOMMANDS:
+  test             Compares headless frames against the PNGs in APPLICATION/golden
 
 FLAGS:
   -h, --help       Prints help information
+  --headless       Renders frames to PNG files without opening a window
+  --update         Writes the rendered frames as the new references (test only)
 
 OPTIONS:
   --multisampling  Sets the multisampling level [default: 1]
   -p, --port       Sets the server port [default: 3000]
+  --frames         Comma-separated frames to render [default: 0, or the references for test]
+  --out            Output directory for headless frames or test diffs [default: frames]
+  --fps            Frame rate used for dt in headless and test modes [default: 60]
+  --tolerance      Maximum per-channel difference allowed by test [default: 2]
+  --start-time     Starts the app clock at a time, e.g. 2024-06-04T09:00:00 [default: now]
+  --time-scale     Multiplies the rate of the app clock and dt [default: 1]
 ";
 
 #[derive(Debug)]
@@ -28,6 +63,19 @@ struct SignArgs {
     app_path: String,
     multisampling: u16,
     port: u16,
+    headless: bool,
+    test: bool,
+    update: bool,
+    frames: Option<Vec<u32>>,
+    out: Option<PathBuf>,
+    fps: f32,
+    tolerance: u8,
+    start_time: Option<DateTime<Utc>>,
+    time_scale: f64,
+}
+
+fn parse_frames(s: &str) -> Result<Vec<u32>, std::num::ParseIntError> {
+    s.split(',').map(|frame| frame.trim().parse()).collect()
 }
 
 fn parse_args() -> Result<SignArgs, pico_args::Error> {
@@ -38,10 +86,32 @@ fn parse_args() -> Result<SignArgs, pico_args::Error> {
         std::process::exit(0);
     }
     
+    // The first free argument is either the "test" subcommand or the app path
+    let (test, app_path) = match pargs.subcommand()? {
+        Some(subcommand) if subcommand == "test" => (true, None),
+        Some(app_path) => (false, Some(app_path)),
+        None => (false, None),
+    };
+    
+    let headless = pargs.contains("--headless");
+    let update = pargs.contains("--update");
+    
     let args = SignArgs {
-        app_path: pargs.free_from_str()?,
         multisampling: pargs.opt_value_from_str("--multisampling")?.unwrap_or(1),
         port: pargs.opt_value_from_str(["-p", "--port"])?.unwrap_or(3000),
+        headless,
+        test,
+        update,
+        frames: pargs.opt_value_from_fn("--frames", parse_frames)?,
+        out: pargs.opt_value_from_str("--out")?,
+        fps: pargs.opt_value_from_str("--fps")?.unwrap_or(60.),
+        tolerance: pargs.opt_value_from_str("--tolerance")?.unwrap_or(2),
+        start_time: pargs.opt_value_from_fn("--start-time", clock::parse_time)?,
+        time_scale: pargs.opt_value_from_str("--time-scale")?.unwrap_or(1.),
+        app_path: match app_path {
+            Some(app_path) => app_path,
+            None => pargs.free_from_str()?,
+        },
     };
     
     Ok(args)
@@ -59,7 +129,39 @@ fn main() {
     };
     
     let app_path = args.app_path;
-    let handler = SignWindowHandler::new(&app_path, args.port);
+    let clock = VirtualClock::shared(args.start_time, args.time_scale);
+    
+    if args.test {
+        let options = golden::TestOptions {
+            frames: args.frames,
+            fps: args.fps,
+            tolerance: args.tolerance,
+            update: args.update,
+            diff_dir: args.out,
+        };
+        
+        match golden::run(Path::new(&app_path), &options, clock) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    
+    if args.headless {
+        info!("Rendering {} headless...", &app_path);
+        let frames = args.frames.unwrap_or(vec![0]);
+        let out = args.out.unwrap_or("frames".into());
+        if let Err(e) = headless::run(Path::new(&app_path), &frames, &out, args.fps, clock) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    
+    let handler = SignWindowHandler::new(&app_path, args.port, clock);
 
     let options = WindowCreationOptions::new_windowed(WindowSize::PhysicalPixels((640, 480).into()), None)
                     .with_multisampling(args.multisampling)
@@ -0,0 +1,120 @@
+use speedy2d::dimen::Vec2;
+use speedy2d::shape::Rectangle;
+
+/// Border widths, in image pixels, that stay unstretched when an image is
+/// drawn as a nine-slice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Insets {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+/// Splits an image of `image_size` into corners that keep their size, edges
+/// that stretch along one axis and a center that stretches along both, and
+/// pairs each part with where it is drawn in `r`. Borders are shrunk evenly
+/// when `r` is too small to fit them.
+pub fn regions(image_size: Vec2, insets: &Insets, r: &Rectangle) -> Vec<(Rectangle, Rectangle)> {
+    // Insets can't overlap in the image either
+    let source_x = clamp_pair(insets.left.max(0.), insets.right.max(0.), image_size.x);
+    let source_y = clamp_pair(insets.top.max(0.), insets.bottom.max(0.), image_size.y);
+    let dest_x = clamp_pair(source_x.0, source_x.1, r.width());
+    let dest_y = clamp_pair(source_y.0, source_y.1, r.height());
+
+    let source_columns = [0., source_x.0, image_size.x - source_x.1, image_size.x];
+    let source_rows = [0., source_y.0, image_size.y - source_y.1, image_size.y];
+    let (left, top) = (r.top_left().x, r.top_left().y);
+    let (right, bottom) = (r.bottom_right().x, r.bottom_right().y);
+    let dest_columns = [left, left + dest_x.0, right - dest_x.1, right];
+    let dest_rows = [top, top + dest_y.0, bottom - dest_y.1, bottom];
+
+    let mut regions = vec![];
+    for row in 0..3 {
+        for column in 0..3 {
+            let source = Rectangle::new(
+                Vec2::new(source_columns[column], source_rows[row]),
+                Vec2::new(source_columns[column + 1], source_rows[row + 1]),
+            );
+            let dest = Rectangle::new(
+                Vec2::new(dest_columns[column], dest_rows[row]),
+                Vec2::new(dest_columns[column + 1], dest_rows[row + 1]),
+            );
+
+            // Zero-width borders and centers have nothing to draw
+            if source.width() > 0. && source.height() > 0. && dest.width() > 0. && dest.height() > 0. {
+                regions.push((source, dest));
+            }
+        }
+    }
+
+    regions
+}
+
+/// Scales two borders down proportionally so they fit in `length`.
+fn clamp_pair(start: f32, end: f32, length: f32) -> (f32, f32) {
+    let total = start + end;
+    if total <= length || total <= 0. {
+        (start, end)
+    } else {
+        let scale = length.max(0.) / total;
+        (start * scale, end * scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rectangle {
+        Rectangle::new(Vec2::new(x, y), Vec2::new(x + width, y + height))
+    }
+
+    fn insets(size: f32) -> Insets {
+        Insets { top: size, right: size, bottom: size, left: size }
+    }
+
+    #[test]
+    fn corners_keep_their_size_and_center_stretches() {
+        let parts = regions(Vec2::new(30., 30.), &insets(10.), &rect(100., 50., 200., 80.));
+        assert_eq!(parts.len(), 9);
+
+        let (source, dest) = &parts[0];
+        assert_eq!(*source, rect(0., 0., 10., 10.));
+        assert_eq!(*dest, rect(100., 50., 10., 10.));
+
+        let (source, dest) = &parts[4];
+        assert_eq!(*source, rect(10., 10., 10., 10.));
+        assert_eq!(*dest, rect(110., 60., 180., 60.));
+
+        let (source, dest) = &parts[8];
+        assert_eq!(*source, rect(20., 20., 10., 10.));
+        assert_eq!(*dest, rect(290., 120., 10., 10.));
+    }
+
+    #[test]
+    fn zero_insets_draw_only_the_center() {
+        let parts = regions(Vec2::new(30., 30.), &insets(0.), &rect(0., 0., 60., 60.));
+        assert_eq!(parts, vec![(rect(0., 0., 30., 30.), rect(0., 0., 60., 60.))]);
+    }
+
+    #[test]
+    fn borders_shrink_to_fit_a_small_destination() {
+        let insets = Insets { top: 10., right: 30., bottom: 10., left: 10. };
+        let parts = regions(Vec2::new(60., 60.), &insets, &rect(0., 0., 20., 100.));
+
+        // The 10 and 30 wide borders scale to 5 and 15, leaving no center column
+        let widths: Vec<f32> = parts.iter().take(2).map(|(_, dest)| dest.width()).collect();
+        assert_eq!(widths, vec![5., 15.]);
+        assert_eq!(parts.len(), 6);
+    }
+
+    #[test]
+    fn overlapping_insets_are_clamped_to_the_image() {
+        let parts = regions(Vec2::new(20., 20.), &insets(20.), &rect(0., 0., 100., 100.));
+
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0].0, rect(0., 0., 10., 10.));
+        assert_eq!(parts[3].0, rect(10., 10., 10., 10.));
+    }
+}
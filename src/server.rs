@@ -5,11 +5,12 @@ use std::sync::Mutex;
 use std::thread;
 
 use rouille::{Response, router, Request};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use speedy2d::window::UserEventSender;
 use tracing::info;
 use walkdir::WalkDir;
 
+use crate::clock::{parse_time, SharedClock};
 use crate::window_handler::SignWindowHandler;
 
 #[derive(Serialize)]
@@ -18,6 +19,51 @@ struct StatusResponse {
     is_fullscreen: bool,
 }
 
+#[derive(Serialize)]
+struct ClockResponse {
+    now: String,
+    time_scale: f64,
+}
+
+#[derive(Deserialize)]
+struct ClockUpdate {
+    now: Option<String>,
+    time_scale: Option<f64>,
+}
+
+fn make_clock_response(clock: &SharedClock) -> Response {
+    let clock = clock.lock().unwrap();
+    Response::json(&ClockResponse {
+        now: clock.now().to_rfc3339(),
+        time_scale: clock.time_scale(),
+    })
+}
+
+fn make_clock_put_response(clock: &SharedClock, request: &Request) -> Response {
+    let update: ClockUpdate = match rouille::input::json_input(request) {
+        Ok(update) => update,
+        Err(err) => return Response::text(err.to_string()).with_status_code(400),
+    };
+    
+    let now = match update.now.as_deref().map(parse_time) {
+        Some(Ok(now)) => Some(now),
+        Some(Err(err)) => return Response::text(err).with_status_code(400),
+        None => None,
+    };
+    
+    {
+        let mut clock = clock.lock().unwrap();
+        if let Some(now) = now {
+            clock.set_now(now);
+        }
+        if let Some(time_scale) = update.time_scale {
+            clock.set_time_scale(time_scale);
+        }
+    }
+    
+    make_clock_response(clock)
+}
+
 trait ResponseHelpers {
     fn allow_cors(self) -> Self;
 }
@@ -141,6 +187,7 @@ pub fn start_server(
 ) {
     let path = handler.root_path.clone();
     let is_fullscreen = handler.is_fullscreen.clone();
+    let clock = handler.clock.clone();
     
     thread::spawn(move || {          
         rouille::start_server(("127.0.0.1", port), move |request| {
@@ -163,6 +210,16 @@ pub fn start_server(
                     
                     Response::json(&data)
                 },
+                (GET) (/api/clock) => {
+                    make_clock_response(&clock)
+                },
+                (PUT) (/api/clock) => {
+                    make_clock_put_response(&clock, request)
+                },
+                (OPTIONS) (/api/clock) => {
+                    Response::text("OPTIONS response")
+                        .with_additional_header("Access-Control-Allow-Methods", "OPTIONS, GET, PUT")
+                },
                 (GET) (/api/test_sender) => {
                     sender.lock().unwrap().send_event("Test".to_owned()).unwrap();
                     Response::text("Sending test")
@@ -0,0 +1,220 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use speedy2d::dimen::Vec2;
+use speedy2d::shape::Rectangle;
+
+// Maximum distance between a curve and its flattened segments, in pixels
+const CURVE_TOLERANCE: f32 = 0.25;
+const MIN_CURVE_SEGMENTS: usize = 8;
+const MAX_CURVE_SEGMENTS: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Line(Vec2, Vec2),
+    Ellipse(Vec2, Vec2),
+    Polygon(Vec<Vec2>),
+    RoundedRectangle(Rectangle, f32),
+}
+
+impl Shape {
+    /// Lines are open; every other shape's outline joins back to its start.
+    pub fn is_closed(&self) -> bool {
+        !matches!(self, Shape::Line(_, _))
+    }
+
+    /// The outline of the shape with curves flattened into line segments.
+    pub fn outline(&self) -> Vec<Vec2> {
+        match self {
+            Shape::Line(start, end) => vec![*start, *end],
+            Shape::Ellipse(center, radii) => {
+                let segments = curve_segments(radii.x.max(radii.y), TAU);
+                (0..segments)
+                    .map(|i| {
+                        let angle = TAU * i as f32 / segments as f32;
+                        Vec2::new(center.x + radii.x * angle.cos(), center.y + radii.y * angle.sin())
+                    })
+                    .collect()
+            },
+            Shape::Polygon(points) => points.clone(),
+            Shape::RoundedRectangle(r, radius) => {
+                let radius = radius.min(r.width() / 2.).min(r.height() / 2.).max(0.);
+                let tl = *r.top_left();
+                let br = *r.bottom_right();
+
+                // Corner centers clockwise from the top right, each with its start angle
+                let corners = [
+                    (Vec2::new(br.x - radius, tl.y + radius), -FRAC_PI_2),
+                    (Vec2::new(br.x - radius, br.y - radius), 0.),
+                    (Vec2::new(tl.x + radius, br.y - radius), FRAC_PI_2),
+                    (Vec2::new(tl.x + radius, tl.y + radius), PI),
+                ];
+
+                let segments = curve_segments(radius, FRAC_PI_2);
+                corners.iter()
+                    .flat_map(|(center, start)| {
+                        (0..=segments).map(move |i| {
+                            let angle = start + FRAC_PI_2 * i as f32 / segments as f32;
+                            Vec2::new(center.x + radius * angle.cos(), center.y + radius * angle.sin())
+                        })
+                    })
+                    .collect()
+            },
+        }
+    }
+
+    /// Triangulates the filled shape, including concave polygons.
+    pub fn fill_triangles(&self) -> Vec<[Vec2; 3]> {
+        if !self.is_closed() {
+            return vec![];
+        }
+
+        triangulate(&self.outline())
+    }
+
+    /// Triangulates the outline stroked with a line of `thickness`, centered on
+    /// the outline, with beveled joins.
+    pub fn stroke_triangles(&self, thickness: f32) -> Vec<[Vec2; 3]> {
+        stroke(&self.outline(), self.is_closed(), thickness)
+    }
+}
+
+fn curve_segments(radius: f32, sweep: f32) -> usize {
+    if radius <= 0. {
+        return 1;
+    }
+
+    // The angle per segment that keeps the chord within the tolerance
+    let step = 2. * (1. - CURVE_TOLERANCE / radius.max(CURVE_TOLERANCE)).acos();
+    let segments = (sweep / step.max(f32::EPSILON)).ceil() as usize;
+    let min = (MIN_CURVE_SEGMENTS as f32 * sweep / TAU).ceil() as usize;
+    segments.clamp(min.max(1), MAX_CURVE_SEGMENTS)
+}
+
+fn triangulate(points: &[Vec2]) -> Vec<[Vec2; 3]> {
+    if points.len() < 3 {
+        return vec![];
+    }
+
+    let vertices: Vec<f32> = points.iter().flat_map(|p| [p.x, p.y]).collect();
+    let Ok(indices) = earcutr::earcut(&vertices, &[], 2) else { return vec![] };
+
+    indices.chunks_exact(3)
+        .map(|triangle| [points[triangle[0]], points[triangle[1]], points[triangle[2]]])
+        .collect()
+}
+
+fn stroke(points: &[Vec2], closed: bool, thickness: f32) -> Vec<[Vec2; 3]> {
+    let half = thickness / 2.;
+    let mut triangles = vec![];
+
+    let segment_count = if closed { points.len() } else { points.len().saturating_sub(1) };
+    let segments: Vec<(Vec2, Vec2)> = (0..segment_count)
+        .map(|i| (points[i], points[(i + 1) % points.len()]))
+        .filter(|(start, end)| start != end)
+        .collect();
+
+    let direction = |start: Vec2, end: Vec2| (end - start).normalize().unwrap_or(Vec2::ZERO);
+    let normal = |start: Vec2, end: Vec2| {
+        let direction = direction(start, end);
+        Vec2::new(-direction.y, direction.x) * half
+    };
+
+    // The corners of each segment's quad on the left (+normal) and right
+    // sides, which joins move so that neighbouring quads don't overlap
+    let mut starts: Vec<[Vec2; 2]> = segments.iter()
+        .map(|(start, end)| [*start + normal(*start, *end), *start - normal(*start, *end)])
+        .collect();
+    let mut ends: Vec<[Vec2; 2]> = segments.iter()
+        .map(|(start, end)| [*end + normal(*start, *end), *end - normal(*start, *end)])
+        .collect();
+
+    let join_count = if closed { segments.len() } else { segments.len().saturating_sub(1) };
+    for i in 0..join_count {
+        let next = (i + 1) % segments.len();
+        let (start, corner) = segments[i];
+        let (_, end) = segments[next];
+        let (d1, d2) = (direction(start, corner), direction(corner, end));
+        let (n1, n2) = (normal(start, corner), normal(corner, end));
+        let turn = d1.x * d2.y - d1.y * d2.x;
+        let cos = dot(d1, d2);
+
+        if turn.abs() < f32::EPSILON && cos > 0. {
+            continue;
+        }
+
+        // Both quads meet at the point where their inner edges cross, leaving
+        // a bevel wedge on the outer side
+        let inner_side = if turn > 0. { 0 } else { 1 };
+        let sign = if turn > 0. { 1. } else { -1. };
+        let reach = if cos > -1. + f32::EPSILON {
+            let miter = (n1 + n2) * (1. / (1. + cos));
+            Some((miter, dot(miter, d1).abs()))
+        } else {
+            None
+        };
+        let shortest = (corner - start).magnitude().min((end - corner).magnitude());
+
+        match reach {
+            Some((miter, reach)) if reach <= shortest / 2. => {
+                let inner = corner + miter * sign;
+                ends[i][inner_side] = inner;
+                starts[next][inner_side] = inner;
+                triangles.push([inner, corner - n1 * sign, corner - n2 * sign]);
+            },
+            _ => {
+                // Sharp turns on short segments fall back to overlapping wedges
+                triangles.push([corner, corner + n1, corner + n2]);
+                triangles.push([corner, corner - n1, corner - n2]);
+            },
+        }
+    }
+
+    for ([start_left, start_right], [end_left, end_right]) in starts.into_iter().zip(ends) {
+        triangles.push([start_left, end_left, end_right]);
+        triangles.push([start_left, end_right, start_right]);
+    }
+
+    triangles
+}
+
+fn dot(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.x + a.y * b.y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(triangles: &[[Vec2; 3]]) -> f32 {
+        triangles.iter()
+            .map(|[a, b, c]| ((b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)).abs() / 2.)
+            .sum()
+    }
+
+    #[test]
+    fn stroke_triangles_do_not_overlap_at_joins() {
+        let corner = Shape::Polygon(vec![
+            Vec2::new(0., 0.),
+            Vec2::new(100., 0.),
+            Vec2::new(100., 100.),
+        ]);
+        let line = stroke(&corner.outline(), false, 10.);
+
+        // Two 100x10 bars sharing a 5x5 square, plus the 5x5 bevel triangle
+        assert!((area(&line) - 1987.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn closed_stroke_area_matches_outline_ring() {
+        let square = Shape::Polygon(vec![
+            Vec2::new(0., 0.),
+            Vec2::new(100., 0.),
+            Vec2::new(100., 100.),
+            Vec2::new(0., 100.),
+        ]);
+
+        // A 110x110 square with beveled corners, less the 90x90 inside
+        let expected = 110. * 110. - 4. * 12.5 - 90. * 90.;
+        assert!((area(&square.stroke_triangles(10.)) - expected).abs() < 0.01);
+    }
+}
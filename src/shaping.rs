@@ -0,0 +1,315 @@
+use std::borrow::Cow;
+
+use unicode_bidi::{bidi_class, BidiClass, BidiInfo};
+
+const LAM: char = '\u{0644}';
+const TATWEEL: char = '\u{0640}';
+
+/// Arabic letters with their isolated, final, initial and medial presentation
+/// forms. Right-joining letters have no initial or medial forms.
+const ARABIC_FORMS: &[(char, [u32; 4])] = &[
+    ('\u{0621}', [0xFE80, 0, 0, 0]),
+    ('\u{0622}', [0xFE81, 0xFE82, 0, 0]),
+    ('\u{0623}', [0xFE83, 0xFE84, 0, 0]),
+    ('\u{0624}', [0xFE85, 0xFE86, 0, 0]),
+    ('\u{0625}', [0xFE87, 0xFE88, 0, 0]),
+    ('\u{0626}', [0xFE89, 0xFE8A, 0xFE8B, 0xFE8C]),
+    ('\u{0627}', [0xFE8D, 0xFE8E, 0, 0]),
+    ('\u{0628}', [0xFE8F, 0xFE90, 0xFE91, 0xFE92]),
+    ('\u{0629}', [0xFE93, 0xFE94, 0, 0]),
+    ('\u{062A}', [0xFE95, 0xFE96, 0xFE97, 0xFE98]),
+    ('\u{062B}', [0xFE99, 0xFE9A, 0xFE9B, 0xFE9C]),
+    ('\u{062C}', [0xFE9D, 0xFE9E, 0xFE9F, 0xFEA0]),
+    ('\u{062D}', [0xFEA1, 0xFEA2, 0xFEA3, 0xFEA4]),
+    ('\u{062E}', [0xFEA5, 0xFEA6, 0xFEA7, 0xFEA8]),
+    ('\u{062F}', [0xFEA9, 0xFEAA, 0, 0]),
+    ('\u{0630}', [0xFEAB, 0xFEAC, 0, 0]),
+    ('\u{0631}', [0xFEAD, 0xFEAE, 0, 0]),
+    ('\u{0632}', [0xFEAF, 0xFEB0, 0, 0]),
+    ('\u{0633}', [0xFEB1, 0xFEB2, 0xFEB3, 0xFEB4]),
+    ('\u{0634}', [0xFEB5, 0xFEB6, 0xFEB7, 0xFEB8]),
+    ('\u{0635}', [0xFEB9, 0xFEBA, 0xFEBB, 0xFEBC]),
+    ('\u{0636}', [0xFEBD, 0xFEBE, 0xFEBF, 0xFEC0]),
+    ('\u{0637}', [0xFEC1, 0xFEC2, 0xFEC3, 0xFEC4]),
+    ('\u{0638}', [0xFEC5, 0xFEC6, 0xFEC7, 0xFEC8]),
+    ('\u{0639}', [0xFEC9, 0xFECA, 0xFECB, 0xFECC]),
+    ('\u{063A}', [0xFECD, 0xFECE, 0xFECF, 0xFED0]),
+    ('\u{0641}', [0xFED1, 0xFED2, 0xFED3, 0xFED4]),
+    ('\u{0642}', [0xFED5, 0xFED6, 0xFED7, 0xFED8]),
+    ('\u{0643}', [0xFED9, 0xFEDA, 0xFEDB, 0xFEDC]),
+    ('\u{0644}', [0xFEDD, 0xFEDE, 0xFEDF, 0xFEE0]),
+    ('\u{0645}', [0xFEE1, 0xFEE2, 0xFEE3, 0xFEE4]),
+    ('\u{0646}', [0xFEE5, 0xFEE6, 0xFEE7, 0xFEE8]),
+    ('\u{0647}', [0xFEE9, 0xFEEA, 0xFEEB, 0xFEEC]),
+    ('\u{0648}', [0xFEED, 0xFEEE, 0, 0]),
+    ('\u{0649}', [0xFEEF, 0xFEF0, 0, 0]),
+    ('\u{064A}', [0xFEF1, 0xFEF2, 0xFEF3, 0xFEF4]),
+    ('\u{0671}', [0xFB50, 0xFB51, 0, 0]),
+    ('\u{067E}', [0xFB56, 0xFB57, 0xFB58, 0xFB59]),
+    ('\u{0686}', [0xFB7A, 0xFB7B, 0xFB7C, 0xFB7D]),
+    ('\u{0698}', [0xFB8A, 0xFB8B, 0, 0]),
+    ('\u{06A9}', [0xFB8E, 0xFB8F, 0xFB90, 0xFB91]),
+    ('\u{06AF}', [0xFB92, 0xFB93, 0xFB94, 0xFB95]),
+    ('\u{06CC}', [0xFBFC, 0xFBFD, 0xFBFE, 0xFBFF]),
+];
+
+/// Lam followed by these alefs becomes a ligature, with isolated and final forms.
+const LAM_ALEF_FORMS: &[(char, [u32; 2])] = &[
+    ('\u{0622}', [0xFEF5, 0xFEF6]),
+    ('\u{0623}', [0xFEF7, 0xFEF8]),
+    ('\u{0625}', [0xFEF9, 0xFEFA]),
+    ('\u{0627}', [0xFEFB, 0xFEFC]),
+];
+
+const MIRRORED: &[(char, char)] = &[
+    ('(', ')'), (')', '('), ('[', ']'), (']', '['), ('{', '}'), ('}', '{'),
+    ('<', '>'), ('>', '<'), ('«', '»'), ('»', '«'),
+];
+
+#[derive(PartialEq)]
+enum Joining {
+    None,
+    Right,
+    Dual,
+}
+
+fn forms(c: char) -> Option<&'static [u32; 4]> {
+    ARABIC_FORMS.iter().find(|(letter, _)| *letter == c).map(|(_, forms)| forms)
+}
+
+fn joining(c: char) -> Joining {
+    if c == TATWEEL {
+        return Joining::Dual;
+    }
+
+    match forms(c) {
+        Some(forms) if forms[2] != 0 => Joining::Dual,
+        Some(forms) if forms[1] != 0 => Joining::Right,
+        _ => Joining::None,
+    }
+}
+
+/// Marks like harakat sit on a letter without breaking its joins.
+fn is_transparent(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}' | '\u{0591}'..='\u{05C7}' | '\u{0610}'..='\u{061A}'
+        | '\u{064B}'..='\u{065F}' | '\u{0670}' | '\u{06D6}'..='\u{06ED}'
+    )
+}
+
+fn is_rtl(c: char) -> bool {
+    matches!(
+        bidi_class(c),
+        BidiClass::R | BidiClass::AL | BidiClass::RLE | BidiClass::RLO | BidiClass::RLI
+    )
+}
+
+/// Converts text to the order and forms it is drawn in, line by line. Arabic
+/// letters take the presentation form for how they join their neighbours,
+/// and mixed-direction lines are reordered by the Unicode bidi algorithm.
+///
+/// Text without right-to-left characters is returned unchanged.
+pub fn visual_text(text: &str) -> Cow<str> {
+    if !text.chars().any(is_rtl) {
+        return Cow::Borrowed(text);
+    }
+
+    Cow::Owned(text.split('\n').map(visual_line).collect::<Vec<_>>().join("\n"))
+}
+
+/// The order to draw the pieces of a line in, left to right, when each piece
+/// is converted by `visual_text` on its own. Pieces take the bidi level of
+/// their first non-space character and are reordered as whole units.
+pub fn visual_order<S: AsRef<str>>(pieces: &[S], separator: &str) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..pieces.len()).collect();
+    if !pieces.iter().any(|piece| piece.as_ref().chars().any(is_rtl)) {
+        return order;
+    }
+
+    let mut text = String::new();
+    let mut starts = vec![];
+    for (i, piece) in pieces.iter().enumerate() {
+        if i > 0 {
+            text.push_str(separator);
+        }
+        let piece = piece.as_ref();
+        starts.push(text.len() + piece.find(|c: char| !c.is_whitespace()).unwrap_or(0));
+        text.push_str(piece);
+    }
+
+    let bidi_info = BidiInfo::new(&text, None);
+    let paragraph_level = bidi_info.paragraphs.first().map_or(0, |paragraph| paragraph.level.number());
+    let levels: Vec<u8> = starts.iter()
+        .map(|start| bidi_info.levels.get(*start).map_or(paragraph_level, |level| level.number()))
+        .collect();
+
+    // Rule L2: from the highest level down to the lowest odd one, reverse
+    // every run of pieces at that level or above
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    let lowest_odd = levels.iter().copied().min().unwrap_or(0) | 1;
+
+    for level in (lowest_odd..=highest).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+            i = i.max(start + 1);
+        }
+    }
+
+    order
+}
+
+fn visual_line(line: &str) -> String {
+    let shaped = shape_arabic(line);
+    let bidi_info = BidiInfo::new(&shaped, None);
+    let mut visual = String::with_capacity(shaped.len());
+
+    for paragraph in bidi_info.paragraphs.iter() {
+        let (levels, runs) = bidi_info.visual_runs(paragraph, paragraph.range.clone());
+
+        for run in runs {
+            let text = &shaped[run.clone()];
+            if levels[run.start].is_rtl() {
+                push_reversed(&mut visual, text);
+            } else {
+                visual.push_str(text);
+            }
+        }
+    }
+
+    visual
+}
+
+/// Reverses a right-to-left run for left-to-right layout, keeping marks after
+/// the letters they belong to and mirroring brackets.
+fn push_reversed(visual: &mut String, text: &str) {
+    let mut clusters: Vec<String> = vec![];
+
+    for c in text.chars() {
+        match clusters.last_mut() {
+            Some(cluster) if is_transparent(c) => cluster.push(c),
+            _ => {
+                let mirrored = MIRRORED.iter().find(|(from, _)| *from == c).map_or(c, |(_, to)| *to);
+                clusters.push(mirrored.to_string());
+            },
+        }
+    }
+
+    for cluster in clusters.iter().rev() {
+        visual.push_str(cluster);
+    }
+}
+
+/// Replaces Arabic letters, in logical order, with their contextual forms.
+fn shape_arabic(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut shaped = String::with_capacity(text.len());
+
+    // The nearest letters on each side, skipping marks
+    let neighbour = |range: &mut dyn Iterator<Item = usize>| {
+        range.map(|j| chars[j]).find(|c| !is_transparent(*c))
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let Some(letter_forms) = forms(c) else {
+            shaped.push(c);
+            i += 1;
+            continue;
+        };
+
+        let previous = neighbour(&mut (0..i).rev());
+        let joins_previous = joining(c) != Joining::None
+            && previous.map_or(false, |p| joining(p) == Joining::Dual);
+
+        if c == LAM {
+            // Marks between the lam and alef stay with the ligature
+            let marks = chars[i + 1..].iter().take_while(|m| is_transparent(**m)).count();
+            let alef = chars.get(i + 1 + marks)
+                .and_then(|next| LAM_ALEF_FORMS.iter().find(|(alef, _)| alef == next));
+
+            if let Some((_, ligature)) = alef {
+                shaped.push(char::from_u32(ligature[joins_previous as usize]).unwrap_or(c));
+                shaped.extend(&chars[i + 1..i + 1 + marks]);
+                i += marks + 2;
+                continue;
+            }
+        }
+
+        let next = neighbour(&mut (i + 1..chars.len()));
+        let joins_next = joining(c) == Joining::Dual
+            && next.map_or(false, |n| joining(n) != Joining::None);
+
+        let form = match (joins_previous, joins_next) {
+            (false, false) => letter_forms[0],
+            (true, false) => letter_forms[1],
+            (false, true) => letter_forms[2],
+            (true, true) => letter_forms[3],
+        };
+
+        shaped.push(char::from_u32(form).unwrap_or(c));
+        i += 1;
+    }
+
+    shaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visual_order_keeps_left_to_right_pieces() {
+        assert_eq!(visual_order(&["one", "two", "three"], " "), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn visual_order_reverses_right_to_left_words() {
+        assert_eq!(visual_order(&["hello", "שלום", "עולם"], " "), vec![0, 2, 1]);
+        assert_eq!(visual_order(&["שלום", "עולם", "abc"], " "), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn visual_order_reorders_runs_without_separators() {
+        assert_eq!(visual_order(&["שלום ", "עולם"], ""), vec![1, 0]);
+    }
+
+    #[test]
+    fn shape_arabic_picks_forms_by_position() {
+        assert_eq!(shape_arabic("\u{0628}"), "\u{FE8F}");
+        assert_eq!(shape_arabic("\u{0628}\u{064A}\u{062A}"), "\u{FE91}\u{FEF4}\u{FE96}");
+    }
+
+    #[test]
+    fn shape_arabic_stops_joins_after_right_joining_letters() {
+        assert_eq!(shape_arabic("\u{062F}\u{0628}"), "\u{FEA9}\u{FE8F}");
+        assert_eq!(shape_arabic("\u{0628}\u{062F}\u{0628}"), "\u{FE91}\u{FEAA}\u{FE8F}");
+    }
+
+    #[test]
+    fn shape_arabic_joins_across_marks() {
+        assert_eq!(shape_arabic("\u{0628}\u{064E}\u{062A}"), "\u{FE91}\u{064E}\u{FE96}");
+    }
+
+    #[test]
+    fn shape_arabic_forms_lam_alef_ligatures() {
+        assert_eq!(shape_arabic("\u{0644}\u{0627}"), "\u{FEFB}");
+        assert_eq!(shape_arabic("\u{0628}\u{0644}\u{0623}"), "\u{FE91}\u{FEF8}");
+    }
+
+    #[test]
+    fn shape_arabic_forms_lam_alef_ligatures_across_marks() {
+        assert_eq!(shape_arabic("\u{0644}\u{064E}\u{0627}"), "\u{FEFB}\u{064E}");
+        assert_eq!(shape_arabic("\u{0644}\u{064E}\u{0628}"), "\u{FEDF}\u{064E}\u{FE90}");
+    }
+
+    #[test]
+    fn shape_arabic_leaves_other_text_alone() {
+        assert_eq!(shape_arabic("abc \u{05E9}"), "abc \u{05E9}");
+    }
+}
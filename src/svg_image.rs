@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use resvg::usvg::{Options, Tree};
+use speedy2d::dimen::UVec2;
+use tiny_skia::{Pixmap, Transform};
+
+// Keeps a huge requested size from allocating an enormous pixmap
+const MAX_SIZE: u32 = 8192;
+
+pub fn is_svg(path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+    path.ends_with(".svg") || path.ends_with(".svgz")
+}
+
+/// An SVG, parsed once and rasterized at each size it is drawn at.
+pub struct SvgImage {
+    tree: Tree,
+}
+
+impl SvgImage {
+    pub fn load(path: &Path) -> Option<Self> {
+        let data = std::fs::read(path).ok()?;
+        let mut options = Options::default();
+        options.resources_dir = path.parent().map(|dir| dir.to_path_buf());
+        options.fontdb_mut().load_system_fonts();
+
+        let tree = Tree::from_data(&data, &options).ok()?;
+        Some(SvgImage { tree })
+    }
+
+    /// The size given by the SVG's `width` and `height`, or its view box.
+    pub fn natural_size(&self) -> UVec2 {
+        let size = self.tree.size();
+        UVec2::new(size.width().ceil() as u32, size.height().ceil() as u32)
+    }
+
+    /// Renders the SVG stretched to `size` pixels.
+    pub fn rasterize(&self, size: UVec2) -> Option<Pixmap> {
+        let mut pixmap = Pixmap::new(size.x.clamp(1, MAX_SIZE), size.y.clamp(1, MAX_SIZE))?;
+        let natural = self.tree.size();
+        let transform = Transform::from_scale(
+            pixmap.width() as f32 / natural.width(),
+            pixmap.height() as f32 / natural.height(),
+        );
+
+        resvg::render(&self.tree, transform, &mut pixmap.as_mut());
+        Some(pixmap)
+    }
+}
+
+/// The pixel size to rasterize at for an image drawn `width` by `height` in
+/// the app's coordinates, `scale` times larger on screen.
+pub fn target_size(width: f32, height: f32, scale: (f32, f32)) -> UVec2 {
+    UVec2::new(
+        (width.abs() * scale.0).round().max(1.) as u32,
+        (height.abs() * scale.1).round().max(1.) as u32,
+    )
+}
+
+/// Rasterized sizes are cached alongside other images under this key.
+pub fn cache_key(path: &str, size: UVec2) -> String {
+    format!("{}#{}x{}", path, size.x, size.y)
+}
+
+/// The size in a cache key made by `cache_key` for `path`.
+pub fn cached_size(key: &str, path: &str) -> Option<UVec2> {
+    let (width, height) = key.strip_prefix(path)?.strip_prefix('#')?.split_once('x')?;
+    Some(UVec2::new(width.parse().ok()?, height.parse().ok()?))
+}
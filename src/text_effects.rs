@@ -0,0 +1,225 @@
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use tiny_skia::{Pixmap, PremultipliedColorU8};
+
+use crate::js_env::TextSource;
+use crate::text_raster::rasterize_text;
+
+// Glow is brighter than a shadow of the same blur, so it stays visible when
+// spread thin
+const GLOW_STRENGTH: f32 = 2.;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outline {
+    pub width: f32,
+    pub color: Color,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shadow {
+    pub offset: Vec2,
+    pub blur: f32,
+    pub color: Color,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glow {
+    pub radius: f32,
+    pub color: Color,
+}
+
+/// Layers drawn beneath text: a shadow, then a glow, then an outline.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextEffects {
+    pub outline: Option<Outline>,
+    pub shadow: Option<Shadow>,
+    pub glow: Option<Glow>,
+}
+
+impl TextEffects {
+    pub fn is_empty(&self) -> bool {
+        self.outline.is_none() && self.shadow.is_none() && self.glow.is_none()
+    }
+
+    pub fn cache_key(&self) -> Vec<u32> {
+        let mut values = vec![];
+
+        if let Some(outline) = &self.outline {
+            values.extend([1., outline.width]);
+            values.extend(color_values(outline.color));
+        }
+        if let Some(shadow) = &self.shadow {
+            values.extend([2., shadow.offset.x, shadow.offset.y, shadow.blur]);
+            values.extend(color_values(shadow.color));
+        }
+        if let Some(glow) = &self.glow {
+            values.extend([3., glow.radius]);
+            values.extend(color_values(glow.color));
+        }
+
+        values.iter().map(|value| value.to_bits()).collect()
+    }
+
+    /// How far the effects reach beyond the text's own pixels.
+    fn padding(&self) -> usize {
+        let outline = self.outline.as_ref().map_or(0., |outline| outline.width.max(0.));
+        let shadow = self.shadow.as_ref().map_or(0., |shadow| {
+            shadow.blur.max(0.) + shadow.offset.x.abs().max(shadow.offset.y.abs())
+        });
+        let glow = self.glow.as_ref().map_or(0., |glow| glow.radius.max(0.));
+
+        (outline + shadow.max(glow)).ceil() as usize + 1
+    }
+}
+
+fn color_values(color: Color) -> [f32; 4] {
+    [color.r(), color.g(), color.b(), color.a()]
+}
+
+/// A grid of coverage values from 0 to 1.
+#[derive(Clone)]
+struct Coverage {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+impl Coverage {
+    fn get(&self, x: isize, y: isize) -> f32 {
+        if x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize {
+            return 0.;
+        }
+        self.values[y as usize * self.width + x as usize]
+    }
+
+    /// Grows the covered area by `radius` pixels, antialiasing the new edge.
+    fn dilate(&self, radius: f32) -> Coverage {
+        let reach = radius.ceil() as isize;
+        let mut offsets = vec![];
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let weight = (radius + 0.5 - ((dx * dx + dy * dy) as f32).sqrt()).clamp(0., 1.);
+                if weight > 0. {
+                    offsets.push((dx, dy, weight));
+                }
+            }
+        }
+
+        let mut values = vec![0.; self.values.len()];
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                values[y as usize * self.width + x as usize] = offsets.iter()
+                    .map(|(dx, dy, weight)| self.get(x + dx, y + dy) * weight)
+                    .fold(0., f32::max);
+            }
+        }
+
+        Coverage { values, ..*self }
+    }
+
+    /// Approximates a gaussian blur with three box blurs in each direction.
+    fn blur(&self, radius: f32) -> Coverage {
+        let box_radius = (radius / 3.).round() as usize;
+        if box_radius == 0 {
+            return self.clone();
+        }
+
+        let mut blurred = self.clone();
+        for _ in 0..3 {
+            blurred = blurred.box_blur(box_radius, true).box_blur(box_radius, false);
+        }
+        blurred
+    }
+
+    fn box_blur(&self, radius: usize, horizontal: bool) -> Coverage {
+        let (length, lines) = if horizontal { (self.width, self.height) } else { (self.height, self.width) };
+        let index = |line: usize, i: usize| {
+            if horizontal { line * self.width + i } else { i * self.width + line }
+        };
+
+        let size = (2 * radius + 1) as f32;
+        let mut values = vec![0.; self.values.len()];
+
+        for line in 0..lines {
+            // A running sum over the window, treating pixels past the edges as empty
+            let mut sum: f32 = (0..radius.min(length)).map(|i| self.values[index(line, i)]).sum();
+
+            for i in 0..length {
+                if i + radius < length {
+                    sum += self.values[index(line, i + radius)];
+                }
+                values[index(line, i)] = sum / size;
+                if i >= radius {
+                    sum -= self.values[index(line, i - radius)];
+                }
+            }
+        }
+
+        Coverage { values, ..*self }
+    }
+
+    /// Composites this coverage in `color` under the pixels already in
+    /// `pixmap`, moved by `offset`.
+    fn draw_under(&self, pixmap: &mut Pixmap, color: Color, strength: f32, offset: (isize, isize)) {
+        let width = pixmap.width() as usize;
+
+        for (i, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+            let x = (i % width) as isize - offset.0;
+            let y = (i / width) as isize - offset.1;
+            let alpha = (self.get(x, y) * strength).min(1.) * color.a();
+            if alpha <= 0. {
+                continue;
+            }
+
+            // Destination-over, in premultiplied color
+            let remaining = 1. - pixel.alpha() as f32 / 255.;
+            let channel = |existing: u8, value: f32| {
+                (existing as f32 + value * alpha * remaining * 255.).round().min(255.) as u8
+            };
+            let a = channel(pixel.alpha(), 1.);
+            let r = channel(pixel.red(), color.r()).min(a);
+            let g = channel(pixel.green(), color.g()).min(a);
+            let b = channel(pixel.blue(), color.b()).min(a);
+            *pixel = PremultipliedColorU8::from_rgba(r, g, b, a).unwrap_or(*pixel);
+        }
+    }
+}
+
+/// Rasterizes the effect layers for text, without the text itself, so the
+/// text can be drawn on top in any fill. Returns the pixmap and its offset
+/// from the text position, like `rasterize_text`.
+pub fn rasterize_effects(source: &TextSource, effects: &TextEffects) -> Option<(Pixmap, (i32, i32))> {
+    let (text, (dx, dy)) = rasterize_text(source, Color::WHITE)?;
+    let padding = effects.padding();
+    let width = text.width() as usize + 2 * padding;
+    let height = text.height() as usize + 2 * padding;
+
+    let mut coverage = Coverage { width, height, values: vec![0.; width * height] };
+    for (i, pixel) in text.pixels().iter().enumerate() {
+        let x = i % text.width() as usize + padding;
+        let y = i / text.width() as usize + padding;
+        coverage.values[y * width + x] = pixel.alpha() as f32 / 255.;
+    }
+
+    // The shadow and glow follow the outline, if there is one
+    let outlined = match &effects.outline {
+        Some(outline) => coverage.dilate(outline.width),
+        None => coverage,
+    };
+
+    let mut pixmap = Pixmap::new(width as u32, height as u32)?;
+
+    // Drawn under one another, so the topmost layer comes first
+    if let Some(outline) = &effects.outline {
+        outlined.draw_under(&mut pixmap, outline.color, 1., (0, 0));
+    }
+    if let Some(glow) = &effects.glow {
+        outlined.blur(glow.radius).draw_under(&mut pixmap, glow.color, GLOW_STRENGTH, (0, 0));
+    }
+    if let Some(shadow) = &effects.shadow {
+        let offset = (shadow.offset.x.round() as isize, shadow.offset.y.round() as isize);
+        outlined.blur(shadow.blur).draw_under(&mut pixmap, shadow.color, 1., offset);
+    }
+
+    Some((pixmap, (dx - padding as i32, dy - padding as i32)))
+}
@@ -0,0 +1,547 @@
+use speedy2d::dimen::Vec2;
+
+use crate::font_chain::FontChain;
+use crate::shaping;
+
+// Smallest size autoShrink will try, and how closely it searches for the fit
+const MIN_SHRINK_SIZE: f32 = 1.;
+const SHRINK_STEPS: usize = 12;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum VAlign {
+    Top,
+    Middle,
+    Bottom,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextBoxOptions {
+    pub size: f32,
+    pub align: Align,
+    pub valign: VAlign,
+    /// A multiple of the font's natural line height.
+    pub line_height: f32,
+    pub ellipsis: Option<String>,
+    pub auto_shrink: bool,
+}
+
+/// A run of text and its position relative to the top-left of the box.
+#[derive(Debug, Clone)]
+pub struct PlacedText {
+    pub offset: Vec2,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextBoxLayout {
+    pub size: f32,
+    pub pieces: Vec<PlacedText>,
+    pub line_count: usize,
+    pub truncated: bool,
+}
+
+struct Line {
+    words: Vec<String>,
+    ends_paragraph: bool,
+}
+
+impl Line {
+    fn text(&self) -> String {
+        self.words.join(" ")
+    }
+}
+
+/// Wraps text to fit `width`, shrinking or truncating it to fit `height` if
+/// asked to, and positions each line or, when justified, each word.
+pub fn layout_text_box(
+    font: &FontChain, text: &str, width: f32, height: f32, options: &TextBoxOptions
+) -> TextBoxLayout {
+    let mut size = options.size;
+
+    if options.auto_shrink && !fits(font, text, size, width, height, options.line_height) {
+        let mut lower = MIN_SHRINK_SIZE.min(size);
+        let mut upper = size;
+
+        for _ in 0..SHRINK_STEPS {
+            let middle = (lower + upper) / 2.;
+            if fits(font, text, middle, width, height, options.line_height) {
+                lower = middle;
+            } else {
+                upper = middle;
+            }
+        }
+
+        size = lower;
+    }
+
+    let mut lines = wrap(font, text, size, width);
+    let line_height = natural_line_height(font, size) * options.line_height;
+    let mut truncated = false;
+
+    if let Some(ellipsis) = &options.ellipsis {
+        let max_lines = ((height / line_height).floor() as usize).max(1);
+
+        if lines.len() > max_lines {
+            lines.truncate(max_lines);
+            let last = lines.last_mut().unwrap();
+            let text = truncate_with_ellipsis(font, &last.text(), ellipsis, size, width);
+            *last = Line { words: vec![text], ends_paragraph: true };
+            truncated = true;
+        }
+    }
+
+    let block_height = lines.len() as f32 * line_height;
+    let top = match options.valign {
+        VAlign::Top => 0.,
+        VAlign::Middle => (height - block_height) / 2.,
+        VAlign::Bottom => height - block_height,
+    };
+
+    // Extra line height is split above and below each line, like CSS
+    let leading = (line_height - natural_line_height(font, size)) / 2.;
+    let mut pieces = vec![];
+
+    for (i, line) in lines.iter().enumerate() {
+        let y = top + i as f32 * line_height + leading;
+
+        if options.align == Align::Justify && !line.ends_paragraph && line.words.len() > 1 {
+            let word_widths: Vec<f32> = line.words.iter().map(|word| measure(font, word, size)).collect();
+            let gap = (width - word_widths.iter().sum::<f32>()) / (line.words.len() - 1) as f32;
+            let mut x = 0.;
+
+            // Words are reordered on their own when drawn, so right-to-left
+            // lines also place the words themselves in visual order
+            for i in shaping::visual_order(&line.words, " ") {
+                pieces.push(PlacedText { offset: Vec2::new(x, y), text: line.words[i].clone() });
+                x += word_widths[i] + gap;
+            }
+            continue;
+        }
+
+        let text = line.text();
+        if text.is_empty() {
+            continue;
+        }
+
+        let line_width = measure(font, &text, size);
+        let x = match options.align {
+            Align::Left | Align::Justify => 0.,
+            Align::Center => (width - line_width) / 2.,
+            Align::Right => width - line_width,
+        };
+
+        pieces.push(PlacedText { offset: Vec2::new(x, y), text });
+    }
+
+    TextBoxLayout { size, pieces, line_count: lines.len(), truncated }
+}
+
+/// The width of a single line, matching the layout of `rasterize_text`.
+pub fn measure(font: &FontChain, text: &str, size: f32) -> f32 {
+    font.measure(&shaping::visual_text(text), size)
+}
+
+pub fn natural_line_height(font: &FontChain, size: f32) -> f32 {
+    font.line_height(size)
+}
+
+fn fits(font: &FontChain, text: &str, size: f32, width: f32, height: f32, line_height: f32) -> bool {
+    let lines = wrap(font, text, size, width);
+    let block_height = lines.len() as f32 * natural_line_height(font, size) * line_height;
+
+    // Words are only split when they can't fit a line by themselves
+    let words_fit = text.split_whitespace().all(|word| measure(font, word, size) <= width);
+
+    block_height <= height && words_fit
+}
+
+/// Greedily fills lines word by word, keeping explicit line breaks and
+/// splitting words too long for a line of their own.
+fn wrap(font: &FontChain, text: &str, size: f32, width: f32) -> Vec<Line> {
+    let mut lines = vec![];
+
+    for paragraph in text.lines() {
+        let mut words: Vec<String> = vec![];
+
+        for word in paragraph.split_whitespace() {
+            let pieces = if measure(font, word, size) > width {
+                split_word(font, word, size, width)
+            } else {
+                vec![word.to_owned()]
+            };
+
+            for piece in pieces {
+                let candidate = if words.is_empty() {
+                    piece.clone()
+                } else {
+                    format!("{} {}", words.join(" "), piece)
+                };
+
+                if !words.is_empty() && measure(font, &candidate, size) > width {
+                    lines.push(Line { words: std::mem::take(&mut words), ends_paragraph: false });
+                }
+
+                words.push(piece);
+            }
+        }
+
+        lines.push(Line { words, ends_paragraph: true });
+    }
+
+    lines
+}
+
+fn split_word(font: &FontChain, word: &str, size: f32, width: f32) -> Vec<String> {
+    let mut pieces = vec![];
+    let mut piece = String::new();
+
+    for c in word.chars() {
+        piece.push(c);
+
+        // Every piece keeps at least one character, however narrow the box
+        if measure(font, &piece, size) > width && piece.chars().count() > 1 {
+            piece.pop();
+            pieces.push(std::mem::take(&mut piece));
+            piece.push(c);
+        }
+    }
+
+    if !piece.is_empty() {
+        pieces.push(piece);
+    }
+
+    pieces
+}
+
+fn truncate_with_ellipsis(font: &FontChain, text: &str, ellipsis: &str, size: f32, width: f32) -> String {
+    let mut text = text.trim_end().to_owned();
+
+    while !text.is_empty() && measure(font, &format!("{}{}", text, ellipsis), size) > width {
+        text.pop();
+        text.truncate(text.trim_end().len());
+    }
+
+    text + ellipsis
+}
+
+/// A run of text in one font and size for `layout_rich_text`.
+pub struct RichSpan<'a> {
+    pub font: &'a FontChain,
+    pub text: &'a str,
+    pub size: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RichTextOptions {
+    /// Wraps lines at spaces to fit the width, if set.
+    pub width: Option<f32>,
+    pub align: Align,
+    pub line_height: f32,
+}
+
+/// Text from one span and its position relative to the top-left of the block.
+#[derive(Debug, Clone)]
+pub struct RichPiece {
+    pub span: usize,
+    pub offset: Vec2,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RichTextLayout {
+    pub pieces: Vec<RichPiece>,
+    pub size: Vec2,
+}
+
+enum RichItem {
+    Word { span: usize, text: String, width: f32 },
+    Space { span: usize, width: f32 },
+    Break { span: usize },
+}
+
+#[derive(Default)]
+struct RichLine {
+    // Consecutive text from the same span is merged into one entry of
+    // (span, text, x)
+    runs: Vec<(usize, String, f32)>,
+    width: f32,
+    last_span: usize,
+}
+
+impl RichLine {
+    fn push(&mut self, span: usize, text: &str, width: f32) {
+        match self.runs.last_mut() {
+            Some((last_span, last_text, _)) if *last_span == span => last_text.push_str(text),
+            _ => self.runs.push((span, text.to_owned(), self.width)),
+        }
+        self.width += width;
+        self.last_span = span;
+    }
+}
+
+/// Lays out spans as one block, breaking lines at newlines and, given a
+/// width, between words. Spans on a line share a baseline, and each line is
+/// as tall as its largest span.
+pub fn layout_rich_text(spans: &[RichSpan], options: &RichTextOptions) -> RichTextLayout {
+    if spans.is_empty() {
+        return RichTextLayout { pieces: vec![], size: Vec2::ZERO };
+    }
+
+    let items = rich_items(spans);
+    let mut lines = vec![];
+    let mut line = RichLine::default();
+    let mut spaces: Vec<(usize, f32)> = vec![];
+    let mut i = 0;
+
+    while i < items.len() {
+        match &items[i] {
+            RichItem::Space { span, width } => {
+                spaces.push((*span, *width));
+                i += 1;
+            },
+            RichItem::Break { span } => {
+                line.last_span = *span;
+                lines.push(std::mem::take(&mut line));
+                line.last_span = *span;
+                spaces.clear();
+                i += 1;
+            },
+            RichItem::Word { .. } => {
+                // Words may continue across spans, and only break at spaces
+                let end = items[i..].iter()
+                    .position(|item| !matches!(item, RichItem::Word { .. }))
+                    .map_or(items.len(), |length| i + length);
+
+                let word_width: f32 = items[i..end].iter().map(|item| match item {
+                    RichItem::Word { width, .. } => *width,
+                    _ => 0.,
+                }).sum();
+                let spaces_width: f32 = spaces.iter().map(|(_, width)| width).sum();
+
+                if let Some(max_width) = options.width {
+                    if !line.runs.is_empty() && line.width + spaces_width + word_width > max_width {
+                        let last_span = line.last_span;
+                        lines.push(std::mem::take(&mut line));
+                        line.last_span = last_span;
+                        spaces.clear();
+                    }
+                }
+
+                for (span, width) in spaces.drain(..) {
+                    line.push(span, " ", width);
+                }
+
+                for item in items[i..end].iter() {
+                    if let RichItem::Word { span, text, width } = item {
+                        line.push(*span, text, *width);
+                    }
+                }
+
+                i = end;
+            },
+        }
+    }
+    lines.push(line);
+
+    let v_metrics: Vec<_> = spans.iter()
+        .map(|span| span.font.v_metrics(span.size))
+        .collect();
+
+    let block_width = lines.iter().map(|line| line.width).fold(0., f32::max);
+    let align_width = options.width.unwrap_or(block_width);
+    let mut pieces = vec![];
+    let mut y = 0.;
+
+    for line in lines.iter() {
+        let line_spans: Vec<usize> = if line.runs.is_empty() {
+            vec![line.last_span]
+        } else {
+            line.runs.iter().map(|(span, _, _)| *span).collect()
+        };
+
+        let ascent = line_spans.iter().map(|span| v_metrics[*span].ascent).fold(f32::MIN, f32::max);
+        let descent = line_spans.iter().map(|span| v_metrics[*span].descent).fold(f32::MAX, f32::min);
+        let line_gap = line_spans.iter().map(|span| v_metrics[*span].line_gap).fold(0., f32::max);
+        let natural_height = ascent - descent + line_gap;
+        let line_height = natural_height * options.line_height;
+        let leading = (line_height - natural_height) / 2.;
+
+        let x = match options.align {
+            Align::Left | Align::Justify => 0.,
+            Align::Center => (align_width - line.width) / 2.,
+            Align::Right => align_width - line.width,
+        };
+
+        // Runs are reordered on their own when drawn, so mixed-direction
+        // lines also place the runs themselves in visual order
+        let texts: Vec<&str> = line.runs.iter().map(|(_, text, _)| text.as_str()).collect();
+        let mut run_xs = vec![0.; line.runs.len()];
+        let mut visual_x = 0.;
+        for i in shaping::visual_order(&texts, "") {
+            run_xs[i] = visual_x;
+            visual_x += line.runs.get(i + 1).map_or(line.width, |(_, _, next_x)| *next_x) - line.runs[i].2;
+        }
+
+        for ((span, text, _), run_x) in line.runs.iter().zip(run_xs) {
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            // Text is drawn from the top of the span's ascent, so shift each
+            // span down onto the line's shared baseline
+            let baseline_offset = ascent - v_metrics[*span].ascent;
+            pieces.push(RichPiece {
+                span: *span,
+                offset: Vec2::new(x + run_x, y + leading + baseline_offset),
+                text: text.clone(),
+            });
+        }
+
+        y += line_height;
+    }
+
+    RichTextLayout { pieces, size: Vec2::new(options.width.unwrap_or(block_width), y) }
+}
+
+fn rich_items(spans: &[RichSpan]) -> Vec<RichItem> {
+    let mut items = vec![];
+
+    for (i, span) in spans.iter().enumerate() {
+        let mut word = String::new();
+        let flush = |word: &mut String, items: &mut Vec<RichItem>| {
+            if !word.is_empty() {
+                let width = measure(span.font, word, span.size);
+                items.push(RichItem::Word { span: i, text: std::mem::take(word), width });
+            }
+        };
+
+        for c in span.text.chars() {
+            match c {
+                '\n' => {
+                    flush(&mut word, &mut items);
+                    items.push(RichItem::Break { span: i });
+                },
+                c if c.is_whitespace() => {
+                    flush(&mut word, &mut items);
+                    items.push(RichItem::Space { span: i, width: measure(span.font, " ", span.size) });
+                },
+                c => word.push(c),
+            }
+        }
+
+        flush(&mut word, &mut items);
+    }
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rusttype::Font;
+
+    use super::*;
+
+    const SIZE: f32 = 20.;
+
+    fn font() -> FontChain {
+        let data = std::fs::read("examples/app2/assets/Roboto-Regular.ttf").unwrap();
+        FontChain::new(vec![Arc::new(Font::try_from_vec(data).unwrap())])
+    }
+
+    fn options(align: Align) -> TextBoxOptions {
+        TextBoxOptions {
+            size: SIZE,
+            align,
+            valign: VAlign::Top,
+            line_height: 1.,
+            ellipsis: None,
+            auto_shrink: false,
+        }
+    }
+
+    fn line_texts(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(Line::text).collect()
+    }
+
+    #[test]
+    fn wrap_fills_lines_to_the_width() {
+        let font = font();
+        let width = measure(&font, "one two", SIZE) + 1.;
+
+        let lines = wrap(&font, "one two one two", SIZE, width);
+        assert_eq!(line_texts(&lines), vec!["one two", "one two"]);
+        assert!(!lines[0].ends_paragraph);
+        assert!(lines[1].ends_paragraph);
+    }
+
+    #[test]
+    fn wrap_keeps_explicit_line_breaks() {
+        let font = font();
+
+        let lines = wrap(&font, "first\n\nthird", SIZE, 1000.);
+        assert_eq!(line_texts(&lines), vec!["first", "", "third"]);
+        assert!(lines.iter().all(|line| line.ends_paragraph));
+    }
+
+    #[test]
+    fn wrap_splits_words_longer_than_a_line() {
+        let font = font();
+        let width = measure(&font, "abcd", SIZE) + 1.;
+
+        let lines = wrap(&font, "abcdefghij", SIZE, width);
+        assert!(lines.len() > 1);
+        assert_eq!(line_texts(&lines).concat(), "abcdefghij");
+        assert!(lines.iter().all(|line| measure(&font, &line.text(), SIZE) <= width));
+    }
+
+    #[test]
+    fn ellipsis_truncates_to_the_box_height() {
+        let font = font();
+        let width = measure(&font, "one two", SIZE) + 1.;
+        let height = natural_line_height(&font, SIZE) * 1.5;
+        let options = TextBoxOptions { ellipsis: Some("…".to_owned()), ..options(Align::Left) };
+
+        let layout = layout_text_box(&font, "one two three four", width, height, &options);
+        assert!(layout.truncated);
+        assert_eq!(layout.line_count, 1);
+        assert!(layout.pieces[0].text.ends_with('…'));
+        assert!(measure(&font, &layout.pieces[0].text, SIZE) <= width);
+    }
+
+    #[test]
+    fn auto_shrink_fits_the_box() {
+        let font = font();
+        let text = "a few words that are much too big for the box";
+        let options = TextBoxOptions { auto_shrink: true, ..options(Align::Left) };
+
+        let layout = layout_text_box(&font, text, 100., 40., &options);
+        assert!(layout.size < SIZE);
+        assert!(fits(&font, text, layout.size, 100., 40., 1.));
+    }
+
+    #[test]
+    fn justify_spreads_words_across_the_width() {
+        let font = font();
+        let width = measure(&font, "one two", SIZE) + 30.;
+
+        let layout = layout_text_box(&font, "one two three", width, 100., &options(Align::Justify));
+        let first_line: Vec<&PlacedText> = layout.pieces.iter()
+            .filter(|piece| piece.offset.y == layout.pieces[0].offset.y)
+            .collect();
+
+        assert_eq!(first_line.len(), 2);
+        assert_eq!(first_line[0].offset.x, 0.);
+        let end = first_line[1].offset.x + measure(&font, &first_line[1].text, SIZE);
+        assert!((end - width).abs() < 0.01);
+    }
+}
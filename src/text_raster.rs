@@ -0,0 +1,80 @@
+use rusttype::{point, Scale, PositionedGlyph};
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use tiny_skia::{ColorU8, Pixmap};
+
+use crate::gradient::Fill;
+use crate::js_env::TextSource;
+
+/// Rasterizes text on the CPU, matching the layout speedy2d uses for
+/// `draw_text` where the position is the top-left corner of the block.
+///
+/// Returns the pixmap and the offset of its top-left corner relative to the
+/// text position, or `None` if the text has no visible glyphs.
+pub fn rasterize_text(source: &TextSource, color: Color) -> Option<(Pixmap, (i32, i32))> {
+    let scale = Scale::uniform(source.scale);
+    let v_metrics = source.font.v_metrics(scale);
+    let line_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
+
+    let glyphs: Vec<PositionedGlyph> = source.text
+        .lines()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            let baseline = point(0., v_metrics.ascent + i as f32 * line_height);
+            source.font.layout(line, scale, baseline)
+        })
+        .collect();
+
+    let (min, max) = glyphs.iter()
+        .filter_map(|glyph| glyph.pixel_bounding_box())
+        .fold(None, |bounds: Option<((i32, i32), (i32, i32))>, bb| {
+            Some(match bounds {
+                None => ((bb.min.x, bb.min.y), (bb.max.x, bb.max.y)),
+                Some((min, max)) => (
+                    (min.0.min(bb.min.x), min.1.min(bb.min.y)),
+                    (max.0.max(bb.max.x), max.1.max(bb.max.y)),
+                ),
+            })
+        })?;
+
+    let width = (max.0 - min.0) as u32;
+    let height = (max.1 - min.1) as u32;
+    let mut coverage = vec![0f32; (width * height) as usize];
+
+    for glyph in glyphs.iter() {
+        let Some(bb) = glyph.pixel_bounding_box() else { continue };
+
+        glyph.draw(|x, y, v| {
+            let px = (bb.min.x - min.0) as u32 + x;
+            let py = (bb.min.y - min.1) as u32 + y;
+            let index = (py * width + px) as usize;
+            coverage[index] = (coverage[index] + v).min(1.0);
+        });
+    }
+
+    let mut pixmap = Pixmap::new(width, height)?;
+    let r = (color.r() * 255.).round() as u8;
+    let g = (color.g() * 255.).round() as u8;
+    let b = (color.b() * 255.).round() as u8;
+
+    for (pixel, v) in pixmap.pixels_mut().iter_mut().zip(coverage) {
+        let a = (v * color.a() * 255.).round() as u8;
+        *pixel = ColorU8::from_rgba(r, g, b, a).premultiply();
+    }
+
+    Some((pixmap, min))
+}
+
+/// Like `rasterize_text`, but painted with a fill. Gradients are positioned
+/// relative to `pos`, the text position in the drawing call's coordinates.
+pub fn rasterize_fill_text(source: &TextSource, fill: &Fill, pos: Vec2) -> Option<(Pixmap, (i32, i32))> {
+    match fill {
+        Fill::Solid(color) => rasterize_text(source, *color),
+        Fill::Gradient(gradient) => {
+            let (mut pixmap, offset) = rasterize_text(source, Color::WHITE)?;
+            let origin = pos + Vec2::new(offset.0 as f32, offset.1 as f32);
+            gradient.fill_masked(&mut pixmap, origin);
+            Some((pixmap, offset))
+        },
+    }
+}
@@ -0,0 +1,83 @@
+use speedy2d::dimen::Vec2;
+
+/// A 2D affine transform mapping `(x, y)` to
+/// `(a * x + c * y + e, b * x + d * y + f)`, as in the canvas API.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Affine {
+    pub const IDENTITY: Affine = Affine { a: 1., b: 0., c: 0., d: 1., e: 0., f: 0. };
+
+    pub fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Affine { a, b, c, d, e, f }
+    }
+
+    pub fn translate(x: f32, y: f32) -> Self {
+        Affine::new(1., 0., 0., 1., x, y)
+    }
+
+    pub fn scale(sx: f32, sy: f32) -> Self {
+        Affine::new(sx, 0., 0., sy, 0., 0.)
+    }
+
+    /// A clockwise rotation in radians, since y points down.
+    pub fn rotate(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Affine::new(cos, sin, -sin, cos, 0., 0.)
+    }
+
+    /// Applies `other` first, then `self`.
+    pub fn then_apply(&self, other: &Affine) -> Affine {
+        Affine {
+            a: self.a * other.a + self.c * other.b,
+            b: self.b * other.a + self.d * other.b,
+            c: self.a * other.c + self.c * other.d,
+            d: self.b * other.c + self.d * other.d,
+            e: self.a * other.e + self.c * other.f + self.e,
+            f: self.b * other.e + self.d * other.f + self.f,
+        }
+    }
+
+    pub fn apply(&self, p: Vec2) -> Vec2 {
+        Vec2::new(
+            self.a * p.x + self.c * p.y + self.e,
+            self.b * p.x + self.d * p.y + self.f,
+        )
+    }
+
+    pub fn translation(&self) -> Vec2 {
+        Vec2::new(self.e, self.f)
+    }
+
+    /// True if the transform only moves points, so axis-aligned drawing can be
+    /// offset rather than transformed.
+    pub fn is_translation(&self) -> bool {
+        self.a == 1. && self.b == 0. && self.c == 0. && self.d == 1.
+    }
+
+    /// How much the transform stretches lengths along the x and y axes.
+    pub fn scale_factors(&self) -> Vec2 {
+        Vec2::new(self.a.hypot(self.b), self.c.hypot(self.d))
+    }
+
+    /// Transforms the corners of the rectangle, clockwise from the top left.
+    pub fn apply_rect(&self, top_left: Vec2, bottom_right: Vec2) -> [Vec2; 4] {
+        [
+            self.apply(top_left),
+            self.apply(Vec2::new(bottom_right.x, top_left.y)),
+            self.apply(bottom_right),
+            self.apply(Vec2::new(top_left.x, bottom_right.y)),
+        ]
+    }
+
+    pub fn to_skia(self) -> tiny_skia::Transform {
+        tiny_skia::Transform::from_row(self.a, self.b, self.c, self.d, self.e, self.f)
+    }
+}
@@ -0,0 +1,598 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError};
+use std::thread;
+
+use speedy2d::dimen::UVec2;
+use thiserror::Error;
+use tracing::warn;
+
+// Decoded frames queued ahead of playback
+const QUEUE_FRAMES: usize = 8;
+// Ids stay unique across script reloads, so renderers never mistake a new
+// video's frames for an old one's
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+// Frame times are sums of 1 / fps, so allow for rounding when comparing
+const TIME_EPSILON: f64 = 1e-6;
+
+#[derive(Error, Debug)]
+pub enum VideoError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unsupported Y4M stream: {0}")]
+    Y4m(String),
+    #[error("Could not probe video: {0}")]
+    Probe(String),
+    #[error("{0} not found on PATH, install ffmpeg to play formats other than Y4M")]
+    NotFound(&'static str),
+}
+
+/// Reports a missing ffmpeg tool by name, as the OS error doesn't say which
+/// program it couldn't find.
+fn spawn_error(program: &'static str, err: std::io::Error) -> VideoError {
+    match err.kind() {
+        std::io::ErrorKind::NotFound => VideoError::NotFound(program),
+        _ => VideoError::Io(err),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VideoInfo {
+    pub size: UVec2,
+    pub fps: f64,
+    /// Zero when the container doesn't report one.
+    pub duration: f64,
+}
+
+/// The frame a video is showing, shared with the renderers. `index` changes
+/// whenever the pixels do, so renderers only upload new frames.
+#[derive(Debug, Clone)]
+pub struct VideoFrame {
+    pub video: u32,
+    pub index: u64,
+    pub size: UVec2,
+    /// Straight RGBA, row by row.
+    pub pixels: Arc<Vec<u8>>,
+}
+
+/// Frames are compared by index rather than by their pixels.
+impl PartialEq for VideoFrame {
+    fn eq(&self, other: &Self) -> bool {
+        self.video == other.video && self.index == other.index
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Chroma {
+    Mono,
+    Yuv420,
+    Yuv444,
+}
+
+/// Uncompressed YUV4MPEG2, read without ffmpeg so sample clips play anywhere.
+#[derive(Debug, Clone)]
+struct Y4mHeader {
+    chroma: Chroma,
+    full_range: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Format {
+    Y4m(Y4mHeader),
+    Ffmpeg,
+}
+
+fn is_y4m(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("y4m"))
+}
+
+fn probe(path: &Path) -> Result<(VideoInfo, Format), VideoError> {
+    if is_y4m(path) {
+        probe_y4m(path)
+    } else {
+        probe_ffmpeg(path).map(|info| (info, Format::Ffmpeg))
+    }
+}
+
+fn probe_y4m(path: &Path) -> Result<(VideoInfo, Format), VideoError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut line = String::new();
+    let header_len = reader.read_line(&mut line)?;
+
+    let mut tokens = line.trim_end().split(' ');
+    if tokens.next() != Some("YUV4MPEG2") {
+        return Err(VideoError::Y4m("missing YUV4MPEG2 signature".to_owned()));
+    }
+
+    let (mut width, mut height, mut fps) = (0, 0, 25.);
+    let mut header = Y4mHeader { chroma: Chroma::Yuv420, full_range: false };
+
+    for token in tokens.filter(|token| !token.is_empty()) {
+        let (tag, value) = token.split_at(1);
+        match tag {
+            "W" => width = value.parse().unwrap_or(0),
+            "H" => height = value.parse().unwrap_or(0),
+            "F" => {
+                if let Some((num, den)) = value.split_once(':') {
+                    let num: f64 = num.parse().unwrap_or(0.);
+                    let den: f64 = den.parse().unwrap_or(0.);
+                    if num > 0. && den > 0. {
+                        fps = num / den;
+                    }
+                }
+            },
+            "C" => {
+                header.chroma = match value {
+                    "mono" => Chroma::Mono,
+                    "444" => Chroma::Yuv444,
+                    _ if value.starts_with("420") => Chroma::Yuv420,
+                    _ => return Err(VideoError::Y4m(format!("colorspace {}", value))),
+                };
+            },
+            "X" => {
+                if value == "COLORRANGE=FULL" {
+                    header.full_range = true;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    if width == 0 || height == 0 {
+        return Err(VideoError::Y4m("missing frame size".to_owned()));
+    }
+
+    let size = UVec2::new(width, height);
+    // Assumes plain "FRAME\n" headers, which is what encoders write
+    let frame_len = 6 + y4m_frame_bytes(size, header.chroma) as u64;
+    let stream_len = std::fs::metadata(path)?.len().saturating_sub(header_len as u64);
+    let duration = (stream_len / frame_len) as f64 / fps;
+
+    Ok((VideoInfo { size, fps, duration }, Format::Y4m(header)))
+}
+
+fn y4m_frame_bytes(size: UVec2, chroma: Chroma) -> usize {
+    let luma = (size.x * size.y) as usize;
+    match chroma {
+        Chroma::Mono => luma,
+        Chroma::Yuv420 => luma + 2 * (((size.x + 1) / 2) * ((size.y + 1) / 2)) as usize,
+        Chroma::Yuv444 => 3 * luma,
+    }
+}
+
+fn probe_ffmpeg(path: &Path) -> Result<VideoInfo, VideoError> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=width,height,avg_frame_rate:format=duration"])
+        .args(["-of", "default=noprint_wrappers=1"])
+        .arg(path)
+        .output()
+        .map_err(|err| spawn_error("ffprobe", err))?;
+
+    if !output.status.success() {
+        return Err(VideoError::Probe(String::from_utf8_lossy(&output.stderr).trim().to_owned()));
+    }
+
+    let (mut width, mut height, mut fps, mut duration) = (0, 0, 0., 0.);
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        match key {
+            "width" => width = value.parse().unwrap_or(0),
+            "height" => height = value.parse().unwrap_or(0),
+            "avg_frame_rate" => {
+                if let Some((num, den)) = value.split_once('/') {
+                    let num: f64 = num.parse().unwrap_or(0.);
+                    let den: f64 = den.parse().unwrap_or(0.);
+                    if den > 0. {
+                        fps = num / den;
+                    }
+                }
+            },
+            "duration" => duration = value.parse().unwrap_or(0.),
+            _ => {},
+        }
+    }
+
+    if width == 0 || height == 0 {
+        return Err(VideoError::Probe("no video stream".to_owned()));
+    }
+
+    Ok(VideoInfo {
+        size: UVec2::new(width, height),
+        fps: if fps > 0. { fps } else { 30. },
+        duration,
+    })
+}
+
+/// Reads decoded RGBA frames from `start` seconds onwards.
+enum FrameReader {
+    Y4m { reader: BufReader<File>, header: Y4mHeader, size: UVec2 },
+    Ffmpeg { child: Child, stdout: ChildStdout, size: UVec2 },
+}
+
+impl FrameReader {
+    fn open(path: &Path, format: &Format, info: &VideoInfo, start: f64) -> Result<Self, VideoError> {
+        match format {
+            Format::Y4m(header) => {
+                let mut reader = BufReader::new(File::open(path)?);
+                reader.read_line(&mut String::new())?;
+
+                let mut frame_reader = FrameReader::Y4m { reader, header: header.clone(), size: info.size };
+                let skip = (start * info.fps + TIME_EPSILON).floor() as u64;
+                for _ in 0..skip {
+                    if !frame_reader.skip_y4m_frame()? {
+                        break;
+                    }
+                }
+                Ok(frame_reader)
+            },
+            Format::Ffmpeg => {
+                let mut child = Command::new("ffmpeg")
+                    .args(["-v", "error", "-ss", &start.to_string()])
+                    .arg("-i").arg(path)
+                    .args(["-an", "-r", &info.fps.to_string()])
+                    .args(["-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .map_err(|err| spawn_error("ffmpeg", err))?;
+                let stdout = child.stdout.take().expect("ffmpeg stdout is piped");
+                Ok(FrameReader::Ffmpeg { child, stdout, size: info.size })
+            },
+        }
+    }
+
+    /// Returns `None` at the end of the stream.
+    fn read_frame(&mut self) -> Result<Option<Vec<u8>>, VideoError> {
+        match self {
+            FrameReader::Y4m { reader, header, size } => {
+                if !read_y4m_frame_header(reader)? {
+                    return Ok(None);
+                }
+                let mut yuv = vec![0; y4m_frame_bytes(*size, header.chroma)];
+                reader.read_exact(&mut yuv)?;
+                Ok(Some(yuv_to_rgba(&yuv, *size, header)))
+            },
+            FrameReader::Ffmpeg { stdout, size, .. } => {
+                let mut rgba = vec![0; (size.x * size.y * 4) as usize];
+                match stdout.read_exact(&mut rgba) {
+                    Ok(()) => Ok(Some(rgba)),
+                    Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+                    Err(err) => Err(err.into()),
+                }
+            },
+        }
+    }
+
+    fn skip_y4m_frame(&mut self) -> Result<bool, VideoError> {
+        let FrameReader::Y4m { reader, header, size } = self else { return Ok(false) };
+        if !read_y4m_frame_header(reader)? {
+            return Ok(false);
+        }
+        reader.seek_relative(y4m_frame_bytes(*size, header.chroma) as i64)?;
+        Ok(true)
+    }
+}
+
+impl Drop for FrameReader {
+    fn drop(&mut self) {
+        if let FrameReader::Ffmpeg { child, .. } = self {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn read_y4m_frame_header(reader: &mut BufReader<File>) -> Result<bool, VideoError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(false);
+    }
+    if !line.starts_with("FRAME") {
+        return Err(VideoError::Y4m("missing FRAME header".to_owned()));
+    }
+    Ok(true)
+}
+
+/// Converts BT.601 YUV planes to opaque RGBA.
+fn yuv_to_rgba(yuv: &[u8], size: UVec2, header: &Y4mHeader) -> Vec<u8> {
+    let (width, height) = (size.x as usize, size.y as usize);
+    let luma = width * height;
+    let chroma_width = match header.chroma {
+        Chroma::Yuv420 => (width + 1) / 2,
+        _ => width,
+    };
+    let chroma_len = match header.chroma {
+        Chroma::Mono => 0,
+        Chroma::Yuv420 => chroma_width * ((height + 1) / 2),
+        Chroma::Yuv444 => luma,
+    };
+    let (u_plane, v_plane) = yuv[luma..].split_at(chroma_len);
+
+    let mut rgba = Vec::with_capacity(luma * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let chroma_index = match header.chroma {
+                Chroma::Yuv420 => (y / 2) * chroma_width + x / 2,
+                _ => y * width + x,
+            };
+            let (u, v) = match header.chroma {
+                Chroma::Mono => (128., 128.),
+                _ => (u_plane[chroma_index] as f32, v_plane[chroma_index] as f32),
+            };
+
+            let (luma, u, v) = if header.full_range {
+                (yuv[y * width + x] as f32, u - 128., v - 128.)
+            } else {
+                (
+                    (yuv[y * width + x] as f32 - 16.) * 255. / 219.,
+                    (u - 128.) * 255. / 224.,
+                    (v - 128.) * 255. / 224.,
+                )
+            };
+
+            let channel = |value: f32| value.round().clamp(0., 255.) as u8;
+            rgba.extend([
+                channel(luma + 1.402 * v),
+                channel(luma - 0.344136 * u - 0.714136 * v),
+                channel(luma + 1.772 * u),
+                255,
+            ]);
+        }
+    }
+
+    rgba
+}
+
+enum DecoderCommand {
+    Seek { generation: u32, time: f64 },
+}
+
+enum DecoderMsg {
+    Frame { generation: u32, time: f64, pixels: Vec<u8> },
+    End { generation: u32 },
+}
+
+/// Decodes frames in order on a background thread, restarting from a new
+/// position on each seek. `generation` tells frames from before a seek apart.
+fn decode_thread(
+    path: PathBuf, format: Format, info: VideoInfo,
+    commands: Receiver<DecoderCommand>, frames: SyncSender<DecoderMsg>
+) {
+    thread::spawn(move || {
+        let mut generation = 0;
+        let mut start = 0.;
+
+        'seek: loop {
+            let mut reader = FrameReader::open(&path, &format, &info, start)
+                .map_err(|err| warn!("Could not decode {}: {}", path.display(), err))
+                .ok();
+            let mut index = 0;
+
+            loop {
+                match commands.try_recv() {
+                    Ok(DecoderCommand::Seek { generation: next, time }) => {
+                        (generation, start) = (next, time);
+                        continue 'seek;
+                    },
+                    Err(TryRecvError::Disconnected) => return,
+                    Err(TryRecvError::Empty) => {},
+                }
+
+                let frame = reader.as_mut().and_then(|reader| {
+                    reader.read_frame()
+                        .map_err(|err| warn!("Could not decode {}: {}", path.display(), err))
+                        .ok()
+                        .flatten()
+                });
+
+                let msg = match frame {
+                    Some(pixels) => {
+                        let time = start + index as f64 / info.fps;
+                        index += 1;
+                        DecoderMsg::Frame { generation, time, pixels }
+                    },
+                    None => DecoderMsg::End { generation },
+                };
+                let at_end = matches!(msg, DecoderMsg::End { .. });
+
+                // Blocks while the queue is full, which paces decoding to playback
+                if frames.send(msg).is_err() {
+                    return;
+                }
+
+                if at_end {
+                    reader = None;
+                    match commands.recv() {
+                        Ok(DecoderCommand::Seek { generation: next, time }) => {
+                            (generation, start) = (next, time);
+                            continue 'seek;
+                        },
+                        Err(_) => return,
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Playback state for one video, advanced by the app's `dt` like timers.
+pub struct VideoPlayer {
+    id: u32,
+    info: VideoInfo,
+    commands: Sender<DecoderCommand>,
+    frames: Receiver<DecoderMsg>,
+    generation: u32,
+    pub current_time: f64,
+    pub playing: bool,
+    pub looping: bool,
+    pub ended: bool,
+    /// The next decoded frame, waiting for its time to come.
+    pending: Option<(f64, Vec<u8>)>,
+    reached_end: bool,
+    /// Where the decoded stream stops, as far as has been decoded.
+    stream_end: f64,
+    frame: Option<VideoFrame>,
+    frame_count: u64,
+}
+
+impl VideoPlayer {
+    pub fn open(path: &Path) -> Result<Self, VideoError> {
+        let (info, format) = probe(path)?;
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let (command_tx, command_rx) = mpsc::channel();
+        let (frame_tx, frame_rx) = mpsc::sync_channel(QUEUE_FRAMES);
+        decode_thread(path.to_owned(), format, info, command_rx, frame_tx);
+
+        Ok(VideoPlayer {
+            id,
+            info,
+            commands: command_tx,
+            frames: frame_rx,
+            generation: 0,
+            current_time: 0.,
+            playing: false,
+            looping: false,
+            ended: false,
+            pending: None,
+            reached_end: false,
+            stream_end: 0.,
+            frame: None,
+            frame_count: 0,
+        })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn info(&self) -> &VideoInfo {
+        &self.info
+    }
+
+    /// The probed duration, or how far decoding got for streams without one.
+    pub fn duration(&self) -> f64 {
+        if self.info.duration > 0. {
+            self.info.duration
+        } else {
+            self.stream_end
+        }
+    }
+
+    pub fn frame(&self) -> Option<&VideoFrame> {
+        self.frame.as_ref()
+    }
+
+    pub fn play(&mut self) {
+        if self.ended {
+            self.seek(0.);
+        }
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn seek(&mut self, time: f64) {
+        let time = match self.info.duration {
+            duration if duration > 0. => time.clamp(0., duration),
+            _ => time.max(0.),
+        };
+
+        self.generation += 1;
+        let _ = self.commands.send(DecoderCommand::Seek { generation: self.generation, time });
+
+        // The current frame stays up until the decoder catches up
+        self.current_time = time;
+        self.pending = None;
+        self.reached_end = false;
+        self.stream_end = time;
+        self.ended = false;
+    }
+
+    /// Moves playback on by `dt` seconds and picks up the frame for the new
+    /// time. With `wait`, blocks until that frame is decoded, so offline
+    /// renders are deterministic; otherwise it keeps the last frame while
+    /// the decoder catches up. Returns true when playback has just ended.
+    pub fn advance(&mut self, dt: f64, wait: bool) -> bool {
+        if self.playing {
+            self.current_time += dt;
+        }
+        self.pull_frames(wait);
+
+        let finished = self.reached_end && self.pending.is_none()
+            && self.current_time + TIME_EPSILON >= self.stream_end;
+        if !(self.playing && finished) {
+            return false;
+        }
+
+        if self.looping && self.stream_end > 0. {
+            let overshoot = (self.current_time - self.stream_end) % self.stream_end;
+            self.seek(0.);
+            self.current_time = overshoot.max(0.);
+            self.pull_frames(wait);
+            false
+        } else {
+            self.playing = false;
+            self.ended = true;
+            self.current_time = self.stream_end;
+            true
+        }
+    }
+
+    fn pull_frames(&mut self, wait: bool) {
+        loop {
+            if let Some((time, _)) = &self.pending {
+                if *time > self.current_time + TIME_EPSILON {
+                    return;
+                }
+                let (_, pixels) = self.pending.take().unwrap();
+                self.show(pixels);
+                continue;
+            }
+
+            if self.reached_end {
+                return;
+            }
+
+            let msg = if wait {
+                self.frames.recv().ok()
+            } else {
+                match self.frames.try_recv() {
+                    Ok(msg) => Some(msg),
+                    Err(TryRecvError::Empty) => return,
+                    Err(TryRecvError::Disconnected) => None,
+                }
+            };
+
+            match msg {
+                Some(DecoderMsg::Frame { generation, time, pixels }) if generation == self.generation => {
+                    self.stream_end = time + 1. / self.info.fps;
+                    self.pending = Some((time, pixels));
+                },
+                Some(DecoderMsg::End { generation }) if generation == self.generation => {
+                    self.reached_end = true;
+                },
+                // Left over from before a seek
+                Some(_) => {},
+                None => self.reached_end = true,
+            }
+        }
+    }
+
+    fn show(&mut self, pixels: Vec<u8>) {
+        self.frame_count += 1;
+        self.frame = Some(VideoFrame {
+            video: self.id,
+            index: self.frame_count,
+            size: self.info.size,
+            pixels: Arc::new(pixels),
+        });
+    }
+}
@@ -8,19 +8,41 @@ use std::sync::{Arc, Mutex, RwLock, mpsc, atomic};
 use std::thread;
 use std::time::{Instant, Duration};
 
-use speedy2d::image::{ImageHandle, ImageSmoothingMode};
+use speedy2d::image::{ImageDataType, ImageHandle, ImageSmoothingMode};
 use speedy2d::window::{
     WindowHandler, WindowHelper, WindowStartupInfo,
     MouseButton, WindowFullscreenMode
 };
 use speedy2d::Graphics2D;
 use speedy2d::color::Color;
-use speedy2d::dimen::Vec2;
+use speedy2d::dimen::{IVec2, UVec2, Vec2};
 use speedy2d::numeric::RoundFloat;
+use speedy2d::shape::Rectangle;
 use thiserror::Error;
+use tiny_skia::{ColorU8, Pixmap, PixmapPaint, Transform};
+use tracing::warn;
 
-use crate::js_env::{JsEnv, GraphicsCalls};
+use crate::animated_image;
+use crate::blend::BlendMode;
+use crate::clock::SharedClock;
+use crate::color_adjust::{AdjustedImageKey, ColorAdjust};
+use crate::gradient::Fill;
+use crate::headless::{self, HeadlessRenderer};
+use crate::nine_slice::{self, Insets};
+use crate::js_env::{JsEnv, GraphicsCalls, ImageRef, TextSource};
 use crate::perf::Perf;
+use crate::svg_image::{self, SvgImage};
+use crate::text_effects::{rasterize_effects, TextEffects};
+use crate::text_raster::rasterize_fill_text;
+use crate::transform::Affine;
+use crate::video::VideoFrame;
+
+// The layer id, never given to a JS layer, that blended calls are drawn into
+const BLEND_LAYER_ID: u32 = u32::MAX;
+
+// Frames a rasterized text image, SVG raster or video texture can go unused
+// before it is dropped
+const TEXT_IMAGE_LIFETIME: u64 = 60;
 
 #[derive(Error, Debug)]
 enum SignError {
@@ -38,16 +60,110 @@ enum JsThreadMsg {
 //     Index,
 // }
 
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+struct TextImageKey {
+    font: usize,
+    text: String,
+    scale: i32,
+    gradient: Option<Vec<u32>>,
+    effects: Option<Vec<u32>>,
+}
+
+impl TextImageKey {
+    fn new(source: &TextSource, fill: &Fill, pos: Vec2) -> Self {
+        let gradient = match fill {
+            Fill::Solid(_) => None,
+            Fill::Gradient(gradient) => Some(gradient.cache_key(pos)),
+        };
+        
+        TextImageKey {
+            font: Arc::as_ptr(&source.font) as usize,
+            text: source.text.clone(),
+            scale: (source.scale * 100.) as i32,
+            gradient,
+            effects: None,
+        }
+    }
+    
+    fn with_effects(source: &TextSource, effects: &TextEffects) -> Self {
+        TextImageKey {
+            effects: Some(effects.cache_key()),
+            ..TextImageKey::new(source, &Fill::Solid(Color::WHITE), Vec2::ZERO)
+        }
+    }
+}
+
+/// Text or text effects rasterized on the CPU, so they can be drawn with
+/// transforms and fills that speedy2d's text rendering doesn't support. Solid
+/// colors are rasterized in white and tinted.
+struct TextImage {
+    handle: ImageHandle,
+    offset: Vec2,
+    last_used_frame: u64,
+}
+
+/// The texture holding the frame a video showed when it was last drawn.
+struct VideoImage {
+    handle: ImageHandle,
+    index: u64,
+    last_used_frame: u64,
+}
+
+/// A texture drawn in software, such as an adjusted image or SVG raster.
+struct CachedImage {
+    handle: ImageHandle,
+    last_used_frame: u64,
+}
+
+/// The screen with a blend mode's calls composited in software, and the
+/// frame's calls up to the end of the blend mode, to reuse it while they stay
+/// the same.
+struct BlendImage {
+    handle: ImageHandle,
+    calls: Vec<GraphicsCalls>,
+    drawn_frame: u64,
+}
+
+/// A layer's texture and the calls it was drawn from, so a layer is only
+/// drawn again when its calls change.
+struct LayerImage {
+    handle: ImageHandle,
+    /// Emptied when an image file changes, as the calls may draw it.
+    calls: Vec<GraphicsCalls>,
+    drawn_frame: u64,
+    last_used_frame: u64,
+}
+
 pub struct SignWindowHandler {
     graphics_calls: Arc<RwLock<Vec<GraphicsCalls>>>,
     js_thread_tx: Sender<JsThreadMsg>,
     last_frame_time: Instant,
     last_mouse_down_time: Option<Instant>,
     pub is_fullscreen: Arc<Mutex<bool>>,
-    draw_offset_stack: Vec<Vec2>,
-    draw_offset: Vec2,
+    transform_stack: Vec<Affine>,
+    transform: Affine,
+    clip_stack: Vec<Option<Rectangle<i32>>>,
+    clip: Option<Rectangle<i32>>,
     pub root_path: Arc<Mutex<PathBuf>>,
+    pub clock: SharedClock,
     image_handles: Rc<RefCell<HashMap<String, ImageHandle>>>,
+    /// Every frame of animated images, which aren't in `image_handles`.
+    animated_image_handles: HashMap<String, Vec<ImageHandle>>,
+    /// Parsed SVGs, rasterized into `svg_handles` at each size drawn.
+    svg_images: HashMap<String, SvgImage>,
+    svg_handles: HashMap<String, CachedImage>,
+    text_images: HashMap<TextImageKey, TextImage>,
+    video_images: HashMap<u32, VideoImage>,
+    /// Draws layers on the CPU, as speedy2d can't draw into textures.
+    layer_renderer: HeadlessRenderer,
+    layer_images: HashMap<u32, LayerImage>,
+    /// Images as pixels, for adjusting their colors in software.
+    image_pixmaps: HashMap<String, Vec<Pixmap>>,
+    adjusted_images: HashMap<AdjustedImageKey, CachedImage>,
+    /// The blend modes composited in software in the last frame, in order.
+    blend_images: Vec<BlendImage>,
+    blend_count: usize,
+    frame_count: u64,
     draw_perf: Perf,
     server_port: u16,
 }
@@ -70,51 +186,34 @@ impl WindowHandler<String> for SignWindowHandler {
         self.draw_perf.start();
         
         // Perform queued graphic calls
-        self.draw_offset_stack.clear();
-        self.draw_offset = (0., 0.).into();
+        self.transform_stack.clear();
+        self.transform = Affine::IDENTITY;
+        self.clip_stack.clear();
+        self.clip = None;
+        graphics.set_clip(None);
+        self.blend_count = 0;
+        self.frame_count += 1;
         
-        for call in graphics_calls.iter() {
-            use GraphicsCalls::*;
-            match call {
-                ClearScreenBlack => graphics.clear_screen(Color::BLACK),
-                ClearScreen(c) => graphics.clear_screen(*c),
-                DrawRectangle(r, c) => {
-                    graphics.draw_rectangle(r.with_offset(self.draw_offset), *c)
-                },
-                DrawRectangleImageTinted(r, path_string, c) => {
-                    let image_handle = self.get_image_handle(path_string, graphics);
-                    graphics.draw_rectangle_image_tinted(
-                        r.with_offset(self.draw_offset),
-                        *c,
-                        &image_handle
-                    );
-                },
-                DrawText(pos, c, block) => {
-                    // Rounding position avoids subpixel positions to improve performance
-                    let relative_pos = (pos + self.draw_offset).round();
-                    graphics.draw_text(relative_pos, *c, block);
-                },
-                DrawImage(pos, path_string) => {
-                    let image_handle = self.get_image_handle(path_string, graphics);
-                    let relative_pos = pos + self.draw_offset;
-                    graphics.draw_image(relative_pos, &image_handle);
-                },
-                PushOffset(vec2) => {
-                    self.draw_offset += *vec2;
-                    self.draw_offset_stack.push(*vec2);
-                }
-                PopOffset => {
-                    self.draw_offset -= self.draw_offset_stack.pop().unwrap_or(Vec2::ZERO);
-                },
-                SetResolution(uvec2) => {
-                    graphics.set_resolution(*uvec2);
-                    helper.set_size_pixels(uvec2);
-                },
-                ImageFileUpdate(pathbuf) => {
-                    self.update_image_handle(pathbuf, graphics)
-                }
-            }
-        }
+        self.draw_calls(&graphics_calls, helper, graphics);
+        self.blend_images.truncate(self.blend_count);
+        
+        let frame_count = self.frame_count;
+        self.text_images.retain(|_, image| {
+            frame_count - image.last_used_frame < TEXT_IMAGE_LIFETIME
+        });
+        // SVGs drawn at a changing size leave a raster behind every frame
+        self.svg_handles.retain(|_, image| {
+            frame_count - image.last_used_frame < TEXT_IMAGE_LIFETIME
+        });
+        self.video_images.retain(|_, image| {
+            frame_count - image.last_used_frame < TEXT_IMAGE_LIFETIME
+        });
+        self.layer_images.retain(|_, image| {
+            frame_count - image.last_used_frame < TEXT_IMAGE_LIFETIME
+        });
+        self.adjusted_images.retain(|_, image| {
+            frame_count - image.last_used_frame < TEXT_IMAGE_LIFETIME
+        });
         
         self.draw_perf.stop();
         self.draw_perf.report_after(Duration::from_secs(1));
@@ -135,6 +234,11 @@ impl WindowHandler<String> for SignWindowHandler {
         self.last_mouse_down_time = Some(now);
     }
     
+    fn on_resize(&mut self, _helper: &mut WindowHelper<String>, _size_pixels: UVec2) {
+        // The captured screens no longer fit
+        self.blend_images.clear();
+    }
+    
     fn on_fullscreen_status_changed(&mut self, _helper: &mut WindowHelper<String>, fullscreen: bool) {
         *self.is_fullscreen.lock().unwrap() = fullscreen;
     }
@@ -156,12 +260,13 @@ impl Drop for SignWindowHandler {
 
 fn js_thread(app_root: PathBuf, ready: Arc<AtomicBool>,
     arc_graphics_calls: Arc<RwLock<Vec<GraphicsCalls>>>,
-    js_thread_rx: Receiver<JsThreadMsg>
+    js_thread_rx: Receiver<JsThreadMsg>,
+    clock: SharedClock,
 ) {
     thread::spawn(move || {
         let mut js_frame_perf = Perf::new("JS frame");
         
-        let mut script_env = JsEnv::new(&app_root);
+        let mut script_env = JsEnv::new(&app_root, clock);
         if let Err(err) = script_env.call_init() {
             dbg!(err);
         }
@@ -176,7 +281,7 @@ fn js_thread(app_root: PathBuf, ready: Arc<AtomicBool>,
                     let mut arcgc = arc_graphics_calls.write().unwrap();
     
                     script_env.handle_file_changes();
-                    if let Err(err) = script_env.call_draw(dt) {
+                    if let Err(err) = script_env.run_frame(dt) {
                         dbg!(err);
                     }
                     
@@ -202,7 +307,7 @@ impl SignWindowHandler {
         }
     }
     
-    pub fn new<P: AsRef<Path>>(app_root: P, server_port: u16) -> Self {       
+    pub fn new<P: AsRef<Path>>(app_root: P, server_port: u16, clock: SharedClock) -> Self {       
         let (js_thread_tx, js_thread_rx) = mpsc::channel();
         let arc_graphics_calls: Arc<RwLock<Vec<GraphicsCalls>>> = Arc::new(RwLock::new(vec![]));
         let js_ready = Arc::new(AtomicBool::new(false));
@@ -211,7 +316,8 @@ impl SignWindowHandler {
             app_root.as_ref().to_owned(),
             js_ready.clone(),
             arc_graphics_calls.clone(),
-            js_thread_rx
+            js_thread_rx,
+            clock.clone(),
         );
         
         print!("Waiting for JS environment to start...");
@@ -226,16 +332,37 @@ impl SignWindowHandler {
             last_frame_time: Instant::now(),
             last_mouse_down_time: None,
             is_fullscreen: Arc::new(Mutex::new(false)),
-            draw_offset: Vec2::ZERO,
-            draw_offset_stack: vec![],
+            transform: Affine::IDENTITY,
+            transform_stack: vec![],
+            clip: None,
+            clip_stack: vec![],
             root_path: Arc::new(Mutex::new(app_root.as_ref().to_path_buf())),
+            clock,
             image_handles: Rc::new(RefCell::new(HashMap::new())),
+            animated_image_handles: HashMap::new(),
+            svg_images: HashMap::new(),
+            svg_handles: HashMap::new(),
+            text_images: HashMap::new(),
+            video_images: HashMap::new(),
+            layer_renderer: HeadlessRenderer::new(app_root.as_ref()),
+            layer_images: HashMap::new(),
+            image_pixmaps: HashMap::new(),
+            adjusted_images: HashMap::new(),
+            blend_images: vec![],
+            blend_count: 0,
+            frame_count: 0,
             draw_perf: Perf::new("Graphics draw"),
             server_port,
         }
     }
     
-    fn get_image_handle(&mut self, path_string: &str, graphics: &mut Graphics2D) -> ImageHandle {               
+    fn get_image_handle(&mut self, image_ref: &ImageRef, graphics: &mut Graphics2D) -> ImageHandle {
+        let path_string = &image_ref.path;
+        
+        if let Some(frames) = self.animated_image_handles.get(path_string) {
+            return frames[image_ref.frame % frames.len()].clone();
+        }
+        
         if let Some(image_handle) = self.image_handles.borrow_mut().get_mut(path_string) {
             return image_handle.clone();
         }
@@ -243,18 +370,686 @@ impl SignWindowHandler {
         // The path_string wasn't found in the image_handles map, so we need to create it.
         let mut path = self.root_path.lock().unwrap().clone();
         path.push(path_string);
+        
+        if let Some(frames) = load_animated_frames(&path, graphics) {
+            let image_handle = frames[image_ref.frame % frames.len()].clone();
+            self.animated_image_handles.insert(path_string.to_owned(), frames);
+            return image_handle;
+        }
+        
         let image_handle = graphics.create_image_from_file_path(None, ImageSmoothingMode::Linear, path).unwrap();
         self.image_handles.borrow_mut().insert(path_string.to_owned(), image_handle.clone());
         image_handle
     }
 
+    fn get_svg_image(&mut self, path_string: &str) -> Option<&SvgImage> {
+        if !self.svg_images.contains_key(path_string) {
+            let mut path = self.root_path.lock().unwrap().clone();
+            path.push(path_string);
+            let Some(svg) = SvgImage::load(&path) else {
+                warn!("Could not load SVG {}", path.display());
+                return None;
+            };
+            self.svg_images.insert(path_string.to_owned(), svg);
+        }
+        
+        self.svg_images.get(path_string)
+    }
+    
+    /// Draws an SVG rasterized at the size it covers on screen, so it stays
+    /// sharp when scaled up.
+    fn draw_svg(&mut self, r: &Rectangle, path_string: &str, tint: Color, graphics: &mut Graphics2D) {
+        let Some(image_handle) = self.get_svg_handle(path_string, r.width(), r.height(), graphics) else {
+            return
+        };
+        self.draw_image_quad(r, tint, &image_handle, graphics);
+    }
+    
+    /// The SVG rasterized for drawing `width` by `height` in the current
+    /// transform.
+    fn get_svg_handle(
+        &mut self, path_string: &str, width: f32, height: f32, graphics: &mut Graphics2D
+    ) -> Option<ImageHandle> {
+        let scale = self.transform.scale_factors();
+        let size = svg_image::target_size(width, height, (scale.x, scale.y));
+        let key = svg_image::cache_key(path_string, size);
+        
+        if let Some(image) = self.svg_handles.get_mut(&key) {
+            image.last_used_frame = self.frame_count;
+            return Some(image.handle.clone());
+        }
+        
+        let pixmap = self.get_svg_image(path_string)?.rasterize(size)?;
+        let handle = pixmap_to_image(&pixmap, graphics)?;
+        self.svg_handles.insert(key, CachedImage {
+            handle: handle.clone(),
+            last_used_frame: self.frame_count,
+        });
+        Some(handle)
+    }
+    
+    /// Draws the `source` part of an image, given in image pixels, over `r`.
+    fn draw_image_region(
+        &mut self, source: &Rectangle, r: &Rectangle, image_ref: &ImageRef, tint: Color,
+        graphics: &mut Graphics2D
+    ) {
+        if source.width() <= 0. || source.height() <= 0. {
+            return;
+        }
+        
+        let (image_handle, image_size) = if svg_image::is_svg(&image_ref.path) {
+            // Rasterize the whole SVG at the scale the region is drawn at
+            let Some(svg) = self.get_svg_image(&image_ref.path) else { return };
+            let natural = svg.natural_size().into_f32();
+            let width = natural.x * r.width() / source.width();
+            let height = natural.y * r.height() / source.height();
+            let Some(image_handle) = self.get_svg_handle(&image_ref.path, width, height, graphics) else {
+                return
+            };
+            (image_handle, natural)
+        } else {
+            let image_handle = self.get_image_handle(image_ref, graphics);
+            let size = image_handle.size().into_f32();
+            (image_handle, size)
+        };
+        
+        let uv = Rectangle::new(
+            Vec2::new(source.top_left().x / image_size.x, source.top_left().y / image_size.y),
+            Vec2::new(source.bottom_right().x / image_size.x, source.bottom_right().y / image_size.y),
+        );
+        
+        if self.transform.is_translation() {
+            graphics.draw_rectangle_image_subset_tinted(
+                r.with_offset(self.transform.translation()), tint, uv, &image_handle
+            );
+        } else {
+            graphics.draw_quad_image_tinted_four_color(
+                self.transform.apply_rect(*r.top_left(), *r.bottom_right()),
+                [tint; 4],
+                uv,
+                &image_handle
+            );
+        }
+    }
+    
+    /// Draws an image with its colors adjusted in software, caching the
+    /// result for each adjustment.
+    fn draw_adjusted_image(
+        &mut self, r: &Rectangle, image_ref: &ImageRef, tint: Color, adjust: &ColorAdjust,
+        graphics: &mut Graphics2D
+    ) {
+        let svg_size = svg_image::is_svg(&image_ref.path).then(|| {
+            let scale = self.transform.scale_factors();
+            svg_image::target_size(r.width(), r.height(), (scale.x, scale.y))
+        });
+        let key = AdjustedImageKey::new(image_ref, svg_size, adjust);
+        
+        if !self.adjusted_images.contains_key(&key) {
+            let Some(mut pixmap) = self.image_pixmap(image_ref, svg_size) else { return };
+            adjust.apply(&mut pixmap);
+            let Some(handle) = pixmap_to_image(&pixmap, graphics) else { return };
+            self.adjusted_images.insert(key.clone(), CachedImage {
+                handle,
+                last_used_frame: self.frame_count,
+            });
+        }
+        
+        let image = self.adjusted_images.get_mut(&key).unwrap();
+        image.last_used_frame = self.frame_count;
+        let handle = image.handle.clone();
+        self.draw_image_quad(r, tint, &handle, graphics);
+    }
+    
+    /// A copy of an image's pixels, with SVGs rasterized at `svg_size`.
+    fn image_pixmap(&mut self, image_ref: &ImageRef, svg_size: Option<UVec2>) -> Option<Pixmap> {
+        if let Some(size) = svg_size {
+            return self.get_svg_image(&image_ref.path)?.rasterize(size);
+        }
+        
+        if !self.image_pixmaps.contains_key(&image_ref.path) {
+            let mut path = self.root_path.lock().unwrap().clone();
+            path.push(&image_ref.path);
+            
+            let frames = match headless::load_animated_pixmaps(&path) {
+                Some(frames) => frames,
+                None => {
+                    let Some(pixmap) = headless::load_pixmap(&path) else {
+                        warn!("Could not load image {}", path.display());
+                        return None;
+                    };
+                    vec![pixmap]
+                },
+            };
+            self.image_pixmaps.insert(image_ref.path.clone(), frames);
+        }
+        
+        let frames = &self.image_pixmaps[&image_ref.path];
+        frames.get(image_ref.frame % frames.len()).cloned()
+    }
+    
+    fn draw_image_nine_slice(
+        &mut self, r: &Rectangle, insets: &Insets, image_ref: &ImageRef, tint: Color,
+        graphics: &mut Graphics2D
+    ) {
+        let image_size = if svg_image::is_svg(&image_ref.path) {
+            let Some(svg) = self.get_svg_image(&image_ref.path) else { return };
+            svg.natural_size().into_f32()
+        } else {
+            self.get_image_handle(image_ref, graphics).size().into_f32()
+        };
+
+        for (source, dest) in nine_slice::regions(image_size, insets, r) {
+            self.draw_image_region(&source, &dest, image_ref, tint, graphics);
+        }
+    }
+
+    fn draw_image_quad(
+        &self, r: &Rectangle, tint: Color, image_handle: &ImageHandle, graphics: &mut Graphics2D
+    ) {
+        if self.transform.is_translation() {
+            graphics.draw_rectangle_image_tinted(
+                r.with_offset(self.transform.translation()),
+                tint,
+                image_handle
+            );
+        } else {
+            graphics.draw_quad_image_tinted_four_color(
+                self.transform.apply_rect(*r.top_left(), *r.bottom_right()),
+                [tint; 4],
+                Rectangle::from_tuples((0., 0.), (1., 1.)),
+                image_handle
+            );
+        }
+    }
+    
+    fn draw_triangles(&self, triangles: &[[Vec2; 3]], fill: &Fill, graphics: &mut Graphics2D) {
+        for (triangle, colors) in fill.shade_triangles(triangles) {
+            graphics.draw_triangle_three_color(triangle.map(|p| self.transform.apply(p)), colors);
+        }
+    }
+    
+    /// Returns the current clip intersected with a rectangle in the current
+    /// transform's coordinates. The scissor test is axis-aligned, so rotated
+    /// clips use their bounding box.
+    fn intersect_clip(&self, r: &Rectangle) -> Rectangle<i32> {
+        let corners = self.transform.apply_rect(*r.top_left(), *r.bottom_right());
+        
+        let min_x = corners.iter().map(|p| p.x).fold(f32::INFINITY, f32::min).round() as i32;
+        let min_y = corners.iter().map(|p| p.y).fold(f32::INFINITY, f32::min).round() as i32;
+        let max_x = corners.iter().map(|p| p.x).fold(f32::NEG_INFINITY, f32::max).round() as i32;
+        let max_y = corners.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max).round() as i32;
+        
+        let (min_x, min_y, max_x, max_y) = match &self.clip {
+            Some(clip) => (
+                min_x.max(clip.top_left().x),
+                min_y.max(clip.top_left().y),
+                max_x.min(clip.bottom_right().x),
+                max_y.min(clip.bottom_right().y),
+            ),
+            None => (min_x, min_y, max_x, max_y),
+        };
+        
+        // Clips that don't overlap become empty rather than inverted
+        Rectangle::new(
+            IVec2::new(min_x, min_y),
+            IVec2::new(max_x.max(min_x), max_y.max(min_y))
+        )
+    }
+    
+    fn draw_text_image(
+        &mut self, pos: Vec2, fill: &Fill, source: &TextSource, graphics: &mut Graphics2D
+    ) {
+        let key = TextImageKey::new(source, fill, pos);
+        let (raster_fill, tint) = match fill {
+            Fill::Solid(color) => (Fill::Solid(Color::WHITE), *color),
+            Fill::Gradient(_) => (fill.clone(), Color::WHITE),
+        };
+        
+        self.draw_cached_text_image(key, pos, tint, graphics, || {
+            rasterize_fill_text(source, &raster_fill, pos)
+        });
+    }
+    
+    fn draw_text_effects(
+        &mut self, pos: Vec2, source: &TextSource, effects: &TextEffects, graphics: &mut Graphics2D
+    ) {
+        let key = TextImageKey::with_effects(source, effects);
+        self.draw_cached_text_image(key, pos, Color::WHITE, graphics, || {
+            rasterize_effects(source, effects)
+        });
+    }
+    
+    fn draw_cached_text_image(
+        &mut self, key: TextImageKey, pos: Vec2, tint: Color, graphics: &mut Graphics2D,
+        rasterize: impl FnOnce() -> Option<(Pixmap, (i32, i32))>
+    ) {
+        if !self.text_images.contains_key(&key) {
+            let Some((pixmap, (dx, dy))) = rasterize() else { return };
+            let Some(handle) = pixmap_to_image(&pixmap, graphics) else { return };
+            
+            self.text_images.insert(key.clone(), TextImage {
+                handle,
+                offset: Vec2::new(dx as f32, dy as f32),
+                last_used_frame: 0,
+            });
+        }
+        
+        let Some(text_image) = self.text_images.get_mut(&key) else { return };
+        text_image.last_used_frame = self.frame_count;
+        
+        // Round to whole pixels like draw_text, so effects line up with the text
+        let pos = if self.transform.is_translation() {
+            (pos + self.transform.translation()).round() - self.transform.translation()
+        } else {
+            pos
+        };
+        
+        let top_left = pos + text_image.offset;
+        let r = Rectangle::new(top_left, top_left + text_image.handle.size().into_f32());
+        let handle = text_image.handle.clone();
+        self.draw_image_quad(&r, tint, &handle, graphics);
+    }
+    
+    /// Uploads the video's frame when it has changed since the video was last
+    /// drawn, then draws it like an image.
+    fn draw_video(
+        &mut self, r: &Rectangle, frame: &VideoFrame, tint: Color, graphics: &mut Graphics2D
+    ) {
+        let current = self.video_images.get(&frame.video)
+            .map_or(false, |image| image.index == frame.index);
+        
+        if !current {
+            let Ok(handle) = graphics.create_image_from_raw_pixels(
+                ImageDataType::RGBA,
+                ImageSmoothingMode::Linear,
+                frame.size,
+                &frame.pixels
+            ) else { return };
+            
+            self.video_images.insert(frame.video, VideoImage {
+                handle,
+                index: frame.index,
+                last_used_frame: 0,
+            });
+        }
+        
+        let Some(video_image) = self.video_images.get_mut(&frame.video) else { return };
+        video_image.last_used_frame = self.frame_count;
+        let handle = video_image.handle.clone();
+        self.draw_image_quad(r, tint, &handle, graphics);
+    }
+    
+    /// Draws calls in order, collecting each layer or non-normal blend mode
+    /// to draw as one scope.
+    fn draw_calls(
+        &mut self, calls: &[GraphicsCalls],
+        helper: &mut WindowHelper<String>, graphics: &mut Graphics2D
+    ) {
+        // Where the outermost layer or blend mode being collected starts, and
+        // how deep it is
+        let mut scope_start = 0;
+        let mut scope_depth = 0;
+        
+        for (index, call) in calls.iter().enumerate() {
+            use GraphicsCalls::*;
+            
+            if scope_depth > 0 {
+                match call {
+                    BeginLayer(_, _) | PushBlendMode(_) => scope_depth += 1,
+                    EndLayer | PopBlendMode => scope_depth -= 1,
+                    _ => {},
+                }
+                if scope_depth == 0 {
+                    self.render_scope(&calls[..=index], scope_start, graphics);
+                }
+                continue;
+            }
+            
+            match call {
+                ClearScreenBlack => graphics.clear_screen(Color::BLACK),
+                ClearScreen(c) => graphics.clear_screen(*c),
+                DrawRectangle(r, Fill::Solid(c)) => {
+                    if self.transform.is_translation() {
+                        graphics.draw_rectangle(r.with_offset(self.transform.translation()), *c)
+                    } else {
+                        graphics.draw_quad(
+                            self.transform.apply_rect(*r.top_left(), *r.bottom_right()), *c
+                        );
+                    }
+                },
+                DrawRectangle(r, fill) => {
+                    let (tl, br) = (*r.top_left(), *r.bottom_right());
+                    let (tr, bl) = (Vec2::new(br.x, tl.y), Vec2::new(tl.x, br.y));
+                    self.draw_triangles(&[[tl, tr, br], [tl, br, bl]], fill, graphics);
+                },
+                FillShape(shape, fill) => {
+                    self.draw_triangles(&shape.fill_triangles(), fill, graphics);
+                },
+                StrokeShape(shape, thickness, fill) => {
+                    self.draw_triangles(&shape.stroke_triangles(*thickness), fill, graphics);
+                },
+                DrawRectangleImageTinted(r, image_ref, c) if svg_image::is_svg(&image_ref.path) => {
+                    self.draw_svg(r, &image_ref.path, *c, graphics);
+                },
+                DrawRectangleImageTinted(r, image_ref, c) => {
+                    let image_handle = self.get_image_handle(image_ref, graphics);
+                    self.draw_image_quad(r, *c, &image_handle, graphics);
+                },
+                DrawVideo(r, frame, c) => self.draw_video(r, frame, *c, graphics),
+                DrawText(pos, Fill::Solid(c), block, _) if self.transform.is_translation() => {
+                    // Rounding position avoids subpixel positions to improve performance
+                    let relative_pos = (pos + self.transform.translation()).round();
+                    graphics.draw_text(relative_pos, *c, block);
+                },
+                DrawText(pos, fill, _, source) => {
+                    self.draw_text_image(*pos, fill, source, graphics);
+                },
+                DrawTextEffects(pos, source, effects) => {
+                    self.draw_text_effects(*pos, source, effects, graphics);
+                },
+                DrawImageRegion(source, r, image_ref, c) => {
+                    self.draw_image_region(source, r, image_ref, *c, graphics);
+                },
+                DrawImageAdjusted(r, image_ref, c, adjust) => {
+                    self.draw_adjusted_image(r, image_ref, *c, adjust, graphics);
+                },
+                DrawImageNineSlice(r, insets, image_ref, c) => {
+                    self.draw_image_nine_slice(r, insets, image_ref, *c, graphics);
+                },
+                DrawImage(pos, image_ref) if svg_image::is_svg(&image_ref.path) => {
+                    let Some(svg) = self.get_svg_image(&image_ref.path) else { continue };
+                    let r = Rectangle::new(*pos, pos + svg.natural_size().into_f32());
+                    self.draw_svg(&r, &image_ref.path, Color::WHITE, graphics);
+                },
+                DrawImage(pos, image_ref) => {
+                    let image_handle = self.get_image_handle(image_ref, graphics);
+                    if self.transform.is_translation() {
+                        let relative_pos = pos + self.transform.translation();
+                        graphics.draw_image(relative_pos, &image_handle);
+                    } else {
+                        let r = Rectangle::new(*pos, pos + image_handle.size().into_f32());
+                        self.draw_image_quad(&r, Color::WHITE, &image_handle, graphics);
+                    }
+                },
+                PushTransform(transform) => {
+                    self.transform_stack.push(self.transform);
+                    self.transform = self.transform.then_apply(transform);
+                }
+                PopTransform => {
+                    self.transform = self.transform_stack.pop().unwrap_or(Affine::IDENTITY);
+                },
+                PushClip(r) => {
+                    let clip = self.intersect_clip(r);
+                    self.clip_stack.push(std::mem::replace(&mut self.clip, Some(clip.clone())));
+                    graphics.set_clip(Some(clip));
+                },
+                PopClip => {
+                    self.clip = self.clip_stack.pop().unwrap_or(None);
+                    graphics.set_clip(self.clip.clone());
+                },
+                SetResolution(uvec2) => {
+                    graphics.set_resolution(*uvec2);
+                    helper.set_size_pixels(uvec2);
+                },
+                // Normal blending needs nothing collected
+                PushBlendMode(BlendMode::Normal) | PopBlendMode | EndLayer => {},
+                BeginLayer(_, _) | PushBlendMode(_) => {
+                    scope_start = index;
+                    scope_depth = 1;
+                },
+                DrawLayer(r, id, c) => {
+                    let Some(image) = self.layer_images.get_mut(id) else { continue };
+                    image.last_used_frame = self.frame_count;
+                    self.layer_renderer.use_layer(*id);
+                    let handle = image.handle.clone();
+                    self.draw_image_quad(r, *c, &handle, graphics);
+                },
+                ImageFileUpdate(pathbuf) => {
+                    self.update_image_handle(pathbuf, graphics)
+                }
+            }
+        }
+        
+        // A scope left open by a failed callback is still drawn
+        if scope_depth > 0 {
+            self.render_scope(calls, scope_start, graphics);
+        }
+    }
+    
+    /// Draws a layer or blend mode's calls, which run from `start` to the end
+    /// of the frame's calls so far.
+    fn render_scope(&mut self, frame_calls: &[GraphicsCalls], start: usize, graphics: &mut Graphics2D) {
+        let calls = &frame_calls[start..];
+        match calls.first() {
+            Some(GraphicsCalls::BeginLayer(id, _)) => self.render_layer(*id, calls, graphics),
+            Some(GraphicsCalls::PushBlendMode(mode)) => self.draw_blended(*mode, frame_calls, start, graphics),
+            _ => {},
+        }
+    }
+    
+    /// Draws a layer's calls on the CPU and uploads the result as the layer's
+    /// texture, unless they are the calls it was last drawn from. The layer
+    /// renderer keeps its own copy, so layers and blend modes drawn later on
+    /// the CPU can draw the layer too.
+    ///
+    /// Text in a layer is rasterized by rusttype rather than speedy2d, so its
+    /// antialiasing can differ slightly from the same text drawn on screen.
+    fn render_layer(&mut self, id: u32, calls: &[GraphicsCalls], graphics: &mut Graphics2D) {
+        let current = self.layer_is_current(id, calls);
+        if current && self.layer_renderer.use_layer(id).is_some() {
+            if let Some(image) = self.layer_images.get_mut(&id) {
+                image.last_used_frame = self.frame_count;
+            }
+            return;
+        }
+        
+        self.rasterize(calls);
+        let Some(pixmap) = self.layer_renderer.use_layer(id) else { return };
+        let Some(handle) = pixmap_to_image(pixmap, graphics) else { return };
+        self.layer_images.insert(id, LayerImage {
+            handle,
+            calls: calls.to_vec(),
+            drawn_frame: self.frame_count,
+            last_used_frame: self.frame_count,
+        });
+    }
+    
+    /// Whether a layer was last drawn from the same calls, and the layers
+    /// those calls draw haven't been drawn again since.
+    fn layer_is_current(&self, id: u32, calls: &[GraphicsCalls]) -> bool {
+        let Some(image) = self.layer_images.get(&id) else { return false };
+        image.calls == calls && self.layers_unchanged_since(calls, image.drawn_frame)
+    }
+    
+    /// Whether the layers `calls` draw haven't been drawn again since `frame`.
+    fn layers_unchanged_since(&self, calls: &[GraphicsCalls], frame: u64) -> bool {
+        calls.iter().all(|call| match call {
+            GraphicsCalls::DrawLayer(_, id, _) => self.layer_images.get(id)
+                .map_or(false, |image| image.drawn_frame <= frame),
+            _ => true,
+        })
+    }
+    
+    fn rasterize(&mut self, calls: &[GraphicsCalls]) {
+        let root_path = self.root_path.lock().unwrap().clone();
+        self.layer_renderer.set_root_path(&root_path);
+        self.layer_renderer.render(calls);
+    }
+    
+    /// Draws a blend mode's calls on the CPU, blended with a capture of the
+    /// screen. The blend mode's calls run from `start` to the end of
+    /// `frame_calls`.
+    ///
+    /// Capturing reads the whole screen back from the GPU, so the result is
+    /// reused while the frame's calls up to the end of the blend mode don't
+    /// change, and blend modes that draw nothing are skipped.
+    fn draw_blended(
+        &mut self, mode: BlendMode, frame_calls: &[GraphicsCalls], start: usize,
+        graphics: &mut Graphics2D
+    ) {
+        use GraphicsCalls::*;
+        
+        let calls = &frame_calls[start..];
+        let inner = match calls.last() {
+            Some(PopBlendMode) if calls.len() > 1 => &calls[1..calls.len() - 1],
+            _ => &calls[1..],
+        };
+        
+        let draws_nothing = inner.iter()
+            .all(|call| matches!(call, PushTransform(_) | PopTransform | PushClip(_) | PopClip));
+        if draws_nothing {
+            return;
+        }
+        
+        let index = self.blend_count;
+        self.blend_count += 1;
+        
+        let cached = self.blend_images.get(index).filter(|image| {
+            image.calls == frame_calls && self.layers_unchanged_since(frame_calls, image.drawn_frame)
+        });
+        if let Some(image) = cached {
+            let handle = image.handle.clone();
+            self.draw_screen_image(&handle, graphics);
+            return;
+        }
+        
+        let screen = graphics.capture(ImageDataType::RGBA);
+        let size = *screen.size();
+        let Some(mut backdrop) = Pixmap::new(size.x, size.y) else { return };
+        for (pixel, rgba) in backdrop.pixels_mut().iter_mut().zip(screen.data().chunks_exact(4)) {
+            *pixel = ColorU8::from_rgba(rgba[0], rgba[1], rgba[2], rgba[3]).premultiply();
+        }
+        
+        // Draw the calls in a screen-sized layer with the current clip and
+        // transform
+        let mut layer_calls = vec![BeginLayer(BLEND_LAYER_ID, size)];
+        if let Some(clip) = &self.clip {
+            layer_calls.push(PushClip(Rectangle::new(
+                clip.top_left().into_f32(), clip.bottom_right().into_f32()
+            )));
+        }
+        layer_calls.push(PushTransform(self.transform));
+        layer_calls.extend(inner.iter().cloned());
+        layer_calls.push(EndLayer);
+        self.rasterize(&layer_calls);
+        let Some(group) = self.layer_renderer.take_layer(BLEND_LAYER_ID) else { return };
+        
+        let paint = PixmapPaint {
+            blend_mode: mode.to_skia(),
+            ..PixmapPaint::default()
+        };
+        backdrop.draw_pixmap(0, 0, group.as_ref(), &paint, Transform::identity(), None);
+        
+        let Some(handle) = pixmap_to_image(&backdrop, graphics) else { return };
+        self.draw_screen_image(&handle, graphics);
+        
+        let image = BlendImage {
+            handle,
+            calls: frame_calls.to_vec(),
+            drawn_frame: self.frame_count,
+        };
+        if index < self.blend_images.len() {
+            self.blend_images[index] = image;
+        } else {
+            self.blend_images.push(image);
+        }
+    }
+    
+    /// Draws an image over the whole screen, ignoring the clip.
+    fn draw_screen_image(&self, handle: &ImageHandle, graphics: &mut Graphics2D) {
+        graphics.set_clip(None);
+        graphics.draw_rectangle_image(
+            Rectangle::new(Vec2::ZERO, handle.size().into_f32()), handle
+        );
+        graphics.set_clip(self.clip.clone());
+    }
+    
     fn update_image_handle(&mut self, path: &Path, graphics: &mut Graphics2D) {
-        let image_handle = graphics.create_image_from_file_path(
-            None, ImageSmoothingMode::Linear, path
-        ).unwrap();
         let root_path = self.root_path.lock().unwrap().clone();
+        self.layer_renderer.set_root_path(&root_path);
+        self.layer_renderer.update_image(path);
         let key = path.strip_prefix(root_path).unwrap()
             .to_str().unwrap().to_owned();
+        
+        self.image_pixmaps.remove(&key);
+        self.adjusted_images.retain(|adjusted, _| adjusted.path != key);
+        for image in self.layer_images.values_mut() {
+            image.calls.clear();
+        }
+        self.blend_images.clear();
+        
+        if svg_image::is_svg(&key) {
+            self.update_svg_handles(path, &key, graphics);
+            return;
+        }
+        
+        // A file can change between a still and an animated image
+        if let Some(frames) = load_animated_frames(path, graphics) {
+            self.image_handles.borrow_mut().remove(&key);
+            self.animated_image_handles.insert(key, frames);
+            return;
+        }
+        
+        let image_handle = graphics.create_image_from_file_path(
+            None, ImageSmoothingMode::Linear, path
+        ).unwrap();
+        self.animated_image_handles.remove(&key);
         self.image_handles.borrow_mut().insert(key, image_handle);
     }
-}
\ No newline at end of file
+    
+    /// Reparses a changed SVG and re-rasterizes it at every size it is cached at.
+    fn update_svg_handles(&mut self, path: &Path, key: &str, graphics: &mut Graphics2D) {
+        let Some(svg) = SvgImage::load(path) else {
+            warn!("Could not load SVG {}", path.display());
+            return;
+        };
+        
+        let sizes: Vec<UVec2> = self.svg_handles.keys()
+            .filter_map(|cache_key| svg_image::cached_size(cache_key, key))
+            .collect();
+        
+        for size in sizes {
+            let Some(pixmap) = svg.rasterize(size) else { continue };
+            let Some(handle) = pixmap_to_image(&pixmap, graphics) else { continue };
+            if let Some(image) = self.svg_handles.get_mut(&svg_image::cache_key(key, size)) {
+                image.handle = handle;
+            }
+        }
+        
+        self.svg_images.insert(key.to_owned(), svg);
+    }
+}
+
+/// Uploads every frame of an animated image, or returns `None` for still images.
+fn load_animated_frames(path: &Path, graphics: &mut Graphics2D) -> Option<Vec<ImageHandle>> {
+    animated_image::load_frames(path)?
+        .iter()
+        .map(|frame| {
+            let buffer = frame.buffer();
+            graphics.create_image_from_raw_pixels(
+                ImageDataType::RGBA,
+                ImageSmoothingMode::Linear,
+                UVec2::new(buffer.width(), buffer.height()),
+                buffer.as_raw()
+            ).ok()
+        })
+        .collect()
+}
+
+/// Uploads a premultiplied pixmap as an image.
+fn pixmap_to_image(pixmap: &Pixmap, graphics: &mut Graphics2D) -> Option<ImageHandle> {
+    let data: Vec<u8> = pixmap.pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let c = pixel.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+    
+    graphics.create_image_from_raw_pixels(
+        ImageDataType::RGBA,
+        ImageSmoothingMode::Linear,
+        UVec2::new(pixmap.width(), pixmap.height()),
+        &data
+    ).ok()
+}
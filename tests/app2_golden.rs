@@ -0,0 +1,55 @@
+//! Checks `examples/app2` against its reference frames in `examples/app2/golden`.
+//! The player runs as its own process so the time zone is only set for it.
+//! Update the references with the same options plus `--update`, from a copy
+//! of the app with `"info": false` in `data/debug.json`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const START_TIME: &str = "2023-03-25T18:00:00Z";
+
+fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            std::fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    dir.push(format!("signrs-app2-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn app2_matches_golden() {
+    let app_dir = scratch_dir("app");
+    copy_dir(Path::new("examples/app2"), &app_dir);
+    assert!(app_dir.join("golden").is_dir(), "examples/app2/golden is missing");
+
+    // The debug overlay shows the host's name and address, which differ
+    // between machines
+    std::fs::write(
+        app_dir.join("data/debug.json"),
+        r#"{"info": false}"#,
+    ).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_signrs"))
+        .arg("test")
+        .arg("--start-time").arg(START_TIME)
+        .arg("--out").arg(scratch_dir("diff"))
+        .arg(&app_dir)
+        // The clock slide formats local times
+        .env("TZ", "UTC")
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+}